@@ -15,6 +15,28 @@ impl StringId {
         self.0
     }
 
+    /// Returns the identifier that follows this one.
+    ///
+    /// The maximum identifier is returned unchanged.
+    pub const fn next(self) -> Self {
+        Self(self.0.saturating_add(1))
+    }
+
+    /// Returns `true` if the identifier refers to one of the standard
+    /// strings rather than to the CFF string INDEX.
+    pub const fn is_standard(self) -> bool {
+        (self.0 as usize) < STANDARD_STRINGS.len()
+    }
+
+    /// Returns the index of the string in the CFF string INDEX, or `None`
+    /// for a standard string.
+    ///
+    /// Custom strings follow the 391 standard strings, so the identifier
+    /// 391 refers to the first string in the INDEX.
+    pub const fn custom_index(self) -> Option<usize> {
+        (self.0 as usize).checked_sub(STANDARD_STRINGS.len())
+    }
+
     /// Resolves the identifier as a standard string.
     ///
     /// If the identifier represents a standard string, returns `Ok(string)`,
@@ -24,13 +46,13 @@ impl StringId {
     /// The standard string set is available in the section
     /// "Appendix A - Standard Strings" at <https://adobe-type-tools.github.io/font-tech-notes/pdfs/5176.CFF.pdf>.
     pub fn standard_string(self) -> Result<Latin1String<'static>, usize> {
-        let ix = self.0 as usize;
-        if let Some(string) = STANDARD_STRINGS.get(ix) {
+        match self.custom_index() {
             // The standard strings are all ASCII so it's safe to interpret them
             // as Latin-1. This is verified in a unit test.
-            Ok(Latin1String::new(string.as_bytes()))
-        } else {
-            Err(ix - STANDARD_STRINGS.len())
+            None => Ok(Latin1String::new(
+                STANDARD_STRINGS[self.0 as usize].as_bytes(),
+            )),
+            Some(ix) => Err(ix),
         }
     }
 }
@@ -516,4 +538,31 @@ mod tests {
         assert!(sid.standard_string().is_err());
         assert_eq!(sid.standard_string().unwrap_err(), 0);
     }
+
+    #[test]
+    fn next() {
+        assert_eq!(StringId::new(0).next(), StringId::new(1));
+        assert_eq!(StringId::new(390).next(), StringId::new(391));
+        assert_eq!(StringId::new(u16::MAX).next(), StringId::new(u16::MAX));
+    }
+
+    #[test]
+    fn is_standard() {
+        assert!(StringId::new(0).is_standard());
+        assert!(StringId::new(390).is_standard());
+        assert!(!StringId::new(391).is_standard());
+        assert!(!StringId::new(u16::MAX).is_standard());
+    }
+
+    #[test]
+    fn custom_index() {
+        assert_eq!(StringId::new(0).custom_index(), None);
+        assert_eq!(StringId::new(390).custom_index(), None);
+        assert_eq!(StringId::new(391).custom_index(), Some(0));
+        assert_eq!(StringId::new(400).custom_index(), Some(9));
+        assert_eq!(
+            StringId::new(u16::MAX).custom_index(),
+            Some(u16::MAX as usize - 391)
+        );
+    }
 }
@@ -3,14 +3,16 @@
 /// [Compact Font Format](https://learn.microsoft.com/en-us/typography/opentype/spec/cff) table header
 table CffHeader {
     /// Format major version (starting at 1).
-    #[compile(1)]
+    #[default(1)]
     major: u8,
     /// Format minor version (starting at 0).
-    #[compile(0)]
+    #[default(0)]
     minor: u8,
     /// Header size (bytes).
+    #[default(4)]
     hdr_size: u8,
     /// Absolute offset size.
+    #[default(1)]
     off_size: u8,
     /// Padding bytes before the start of the Name INDEX.
     #[count(subtract($hdr_size, 4))]
@@ -9,6 +9,7 @@ table Cff2Header {
     #[compile(0)]
     minor_version: u8,
     /// Header size (bytes).
+    #[default(5)]
     header_size: u8,
     /// Length of Top DICT structure in bytes.
     top_dict_length: u16,
@@ -1,6 +1,9 @@
 #![parse_module(read_fonts::tables::postscript)]
 
 /// An array of variable-sized objects in a `CFF` table.
+#[skip_font_write]
+#[skip_from_obj]
+#[validate(check_offsets)]
 table Index1 {
     /// Number of objects stored in INDEX.
     count: u16,
@@ -15,6 +18,9 @@ table Index1 {
 }
 
 /// An array of variable-sized objects in a `CFF2` table.
+#[skip_font_write]
+#[skip_from_obj]
+#[validate(check_offsets)]
 table Index2 {
     /// Number of objects stored in INDEX.
     count: u32,
@@ -6,9 +6,13 @@
 use crate::codegen_prelude::*;
 
 /// [Compact Font Format](https://learn.microsoft.com/en-us/typography/opentype/spec/cff) table header
-#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CffHeader {
+    /// Format major version (starting at 1).
+    pub major: u8,
+    /// Format minor version (starting at 0).
+    pub minor: u8,
     /// Header size (bytes).
     pub hdr_size: u8,
     /// Absolute offset size.
@@ -19,23 +23,34 @@ pub struct CffHeader {
     pub trailing_data: Vec<u8>,
 }
 
+impl Default for CffHeader {
+    fn default() -> Self {
+        Self {
+            major: 1,
+            minor: 0,
+            hdr_size: 4,
+            off_size: 1,
+            _padding: Default::default(),
+            trailing_data: Default::default(),
+        }
+    }
+}
+
 impl CffHeader {
     /// Construct a new `CffHeader`
-    pub fn new(hdr_size: u8, off_size: u8, _padding: Vec<u8>, trailing_data: Vec<u8>) -> Self {
+    pub fn new(_padding: Vec<u8>, trailing_data: Vec<u8>) -> Self {
         Self {
-            hdr_size,
-            off_size,
             _padding,
             trailing_data,
+            ..Default::default()
         }
     }
 }
 
 impl FontWrite for CffHeader {
-    #[allow(clippy::unnecessary_cast)]
     fn write_into(&self, writer: &mut TableWriter) {
-        (1 as u8).write_into(writer);
-        (0 as u8).write_into(writer);
+        self.major.write_into(writer);
+        self.minor.write_into(writer);
         self.hdr_size.write_into(writer);
         self.off_size.write_into(writer);
         self._padding.write_into(writer);
@@ -54,6 +69,8 @@ impl<'a> FromObjRef<read_fonts::tables::cff::CffHeader<'a>> for CffHeader {
     fn from_obj_ref(obj: &read_fonts::tables::cff::CffHeader<'a>, _: FontData) -> Self {
         let offset_data = obj.offset_data();
         CffHeader {
+            major: obj.major(),
+            minor: obj.minor(),
             hdr_size: obj.hdr_size(),
             off_size: obj.off_size(),
             _padding: obj._padding().to_owned_obj(offset_data),
@@ -6,7 +6,7 @@
 use crate::codegen_prelude::*;
 
 /// [Compact Font Format (CFF) version 2](https://learn.microsoft.com/en-us/typography/opentype/spec/cff2) table header
-#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Cff2Header {
     /// Header size (bytes).
@@ -21,21 +21,32 @@ pub struct Cff2Header {
     pub trailing_data: Vec<u8>,
 }
 
+impl Default for Cff2Header {
+    fn default() -> Self {
+        Self {
+            header_size: 5,
+            top_dict_length: Default::default(),
+            _padding: Default::default(),
+            top_dict_data: Default::default(),
+            trailing_data: Default::default(),
+        }
+    }
+}
+
 impl Cff2Header {
     /// Construct a new `Cff2Header`
     pub fn new(
-        header_size: u8,
         top_dict_length: u16,
         _padding: Vec<u8>,
         top_dict_data: Vec<u8>,
         trailing_data: Vec<u8>,
     ) -> Self {
         Self {
-            header_size,
             top_dict_length,
             _padding,
             top_dict_data,
             trailing_data,
+            ..Default::default()
         }
     }
 }
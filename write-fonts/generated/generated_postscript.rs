@@ -31,40 +31,11 @@ impl Index1 {
     }
 }
 
-impl FontWrite for Index1 {
-    fn write_into(&self, writer: &mut TableWriter) {
-        self.count.write_into(writer);
-        self.off_size.write_into(writer);
-        self.offsets.write_into(writer);
-        self.data.write_into(writer);
-    }
-    fn table_type(&self) -> TableType {
-        TableType::Named("Index1")
-    }
-}
-
 impl Validate for Index1 {
-    fn validate_impl(&self, _ctx: &mut ValidationCtx) {}
-}
-
-impl<'a> FromObjRef<read_fonts::tables::postscript::Index1<'a>> for Index1 {
-    fn from_obj_ref(obj: &read_fonts::tables::postscript::Index1<'a>, _: FontData) -> Self {
-        let offset_data = obj.offset_data();
-        Index1 {
-            count: obj.count(),
-            off_size: obj.off_size(),
-            offsets: obj.offsets().to_owned_obj(offset_data),
-            data: obj.data().to_owned_obj(offset_data),
-        }
-    }
-}
-
-#[allow(clippy::needless_lifetimes)]
-impl<'a> FromTableRef<read_fonts::tables::postscript::Index1<'a>> for Index1 {}
-
-impl<'a> FontRead<'a> for Index1 {
-    fn read(data: FontData<'a>) -> Result<Self, ReadError> {
-        <read_fonts::tables::postscript::Index1 as FontRead>::read(data).map(|x| x.to_owned_table())
+    fn validate_impl(&self, ctx: &mut ValidationCtx) {
+        ctx.in_table("Index1", |ctx| {
+            self.check_offsets(ctx);
+        })
     }
 }
 
@@ -94,40 +65,11 @@ impl Index2 {
     }
 }
 
-impl FontWrite for Index2 {
-    fn write_into(&self, writer: &mut TableWriter) {
-        self.count.write_into(writer);
-        self.off_size.write_into(writer);
-        self.offsets.write_into(writer);
-        self.data.write_into(writer);
-    }
-    fn table_type(&self) -> TableType {
-        TableType::Named("Index2")
-    }
-}
-
 impl Validate for Index2 {
-    fn validate_impl(&self, _ctx: &mut ValidationCtx) {}
-}
-
-impl<'a> FromObjRef<read_fonts::tables::postscript::Index2<'a>> for Index2 {
-    fn from_obj_ref(obj: &read_fonts::tables::postscript::Index2<'a>, _: FontData) -> Self {
-        let offset_data = obj.offset_data();
-        Index2 {
-            count: obj.count(),
-            off_size: obj.off_size(),
-            offsets: obj.offsets().to_owned_obj(offset_data),
-            data: obj.data().to_owned_obj(offset_data),
-        }
-    }
-}
-
-#[allow(clippy::needless_lifetimes)]
-impl<'a> FromTableRef<read_fonts::tables::postscript::Index2<'a>> for Index2 {}
-
-impl<'a> FontRead<'a> for Index2 {
-    fn read(data: FontData<'a>) -> Result<Self, ReadError> {
-        <read_fonts::tables::postscript::Index2 as FontRead>::read(data).map(|x| x.to_owned_table())
+    fn validate_impl(&self, ctx: &mut ValidationCtx) {
+        ctx.in_table("Index2", |ctx| {
+            self.check_offsets(ctx);
+        })
     }
 }
 
@@ -5,6 +5,8 @@
 
 pub mod avar;
 pub mod base;
+pub mod cff;
+pub mod cff2;
 pub mod cmap;
 pub mod colr;
 pub mod cpal;
@@ -28,6 +30,7 @@ pub mod mvar;
 pub mod name;
 pub mod os2;
 pub mod post;
+pub mod postscript;
 pub mod sbix;
 pub mod stat;
 pub mod variations;
@@ -46,6 +49,8 @@ fn do_we_even_serde() {
     struct AllTables {
         avar: avar::Avar,
         base: base::Base,
+        cff: cff::Cff,
+        cff2: cff2::Cff2,
         cmap: cmap::Cmap,
         cpal: cpal::Cpal,
         dsig: dsig::Dsig,
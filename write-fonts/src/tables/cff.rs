@@ -0,0 +1,6957 @@
+//! The [CFF](https://learn.microsoft.com/en-us/typography/opentype/spec/cff) table
+
+include!("../../generated/generated_cff.rs");
+
+use super::postscript::{
+    charstring::{self, OutlinePen, Parser, PenSink, Token},
+    check_wellformed_offsets,
+    dict::{self, encoded_offset, encoded_private_range, offset_operand, private_dict_range},
+    dict::{DictKey, Entry, Operand},
+    min_offset_size, split_off, subroutinize, write_to_vec, BlendedValue, CffIndex, CowIndex1,
+    CustomCharset, FdSelect, FontDict, Index1, PrivateDict, PrivateDictData, StringId,
+};
+
+pub use super::postscript::CffError;
+
+/// The number of strings in the standard string set.
+const STANDARD_STRING_COUNT: usize = read_fonts::tables::postscript::STANDARD_STRINGS.len();
+
+use std::{
+    cell::OnceCell,
+    collections::{BTreeMap, BTreeSet},
+    ops::Range,
+};
+
+use read_fonts::tables::postscript::STANDARD_ENCODING;
+
+/// The Top DICT operators with string values that are exposed as fields on
+/// [`TopDictData`].
+const TOP_DICT_STRING_KEYS: [DictKey; 8] = [
+    DictKey::Version,
+    DictKey::Notice,
+    DictKey::Copyright,
+    DictKey::FullName,
+    DictKey::FamilyName,
+    DictKey::Weight,
+    DictKey::PostScript,
+    DictKey::BaseFontName,
+];
+
+/// The new glyph id returned by [`Cff::subset`] for a glyph that was
+/// removed.
+pub const REMOVED_GLYPH: u16 = u16::MAX;
+
+/// The maximum length of a string that is accepted when the table is
+/// validated.
+///
+/// Longer strings are valid CFF, but many consumers assume that the length
+/// of a string fits in two bytes.
+pub const MAX_STRING_LEN: usize = 0xFFFF;
+
+/// The maximum number of elements in an XUID array.
+pub const MAX_XUID_LEN: usize = 16;
+
+/// Controls how [`Cff::add_string_with_options`] and
+/// [`Cff::set_top_dict_data_with_options`] add strings to the string INDEX.
+///
+/// By default, both standard strings and strings already present in the
+/// string INDEX are reused.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StringIndexOptions {
+    /// Whether a string already present in the string INDEX is reused,
+    /// rather than added again.
+    pub dedup: bool,
+    /// Whether the identifier of a standard string is used, rather than
+    /// adding the string to the string INDEX.
+    pub reuse_standard: bool,
+}
+
+impl Default for StringIndexOptions {
+    fn default() -> Self {
+        Self {
+            dedup: true,
+            reuse_standard: true,
+        }
+    }
+}
+
+/// The [Compact Font Format](https://learn.microsoft.com/en-us/typography/opentype/spec/cff) table.
+///
+/// The locations of subtables are computed when the table is written; any
+/// offsets present in the Top DICT, Font DICT and Private DICT data are
+/// replaced.
+///
+/// INDEXes are owned [`Index1`]s by default. A table created with
+/// [`Cff::borrow_from`] instead holds [`CowIndex1`]s, which are only copied
+/// from the source font when they are modified; the editing methods are
+/// available on the owned table.
+///
+/// The default table has no fonts and empty INDEXes. Accessors that cover
+/// the whole font set return empty results for it, and those that take a
+/// font index return [`CffError::FontIndexOutOfBounds`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Cff<I = Index1> {
+    /// The table header.
+    ///
+    /// The `trailing_data` field is ignored when writing, and `off_size` is
+    /// widened if it can't represent an offset to the end of the table.
+    pub header: CffHeader,
+    /// The PostScript names of all fonts in the font set.
+    pub names: I,
+    /// The Top DICTs of all fonts in the font set.
+    pub top_dicts: I,
+    /// Strings shared by all fonts in the font set.
+    pub strings: I,
+    /// Subroutines shared by all fonts in the font set.
+    pub global_subrs: I,
+    /// The subtables belonging to each font, in the same order as `top_dicts`.
+    pub font_tables: Vec<FontTables<I>>,
+    /// The raw data of an item variation store, referenced by the
+    /// `VariationStoreOffset` operator of a Top DICT, excluding the length
+    /// that precedes it.
+    ///
+    /// CFF2 tables always carry their variation store in the Top DICT, but
+    /// some CFF tables do as well. The store is written after the Global
+    /// Subr INDEX and referenced from every Top DICT, or the operator is
+    /// removed if this is `None`.
+    pub variation_store: Option<Vec<u8>>,
+    /// If `true`, the Top DICT INDEX and each FDArray INDEX keep the offset
+    /// size of the source table when written, rather than using the
+    /// smallest offset size.
+    ///
+    /// The other INDEXes are always written with their own offset size.
+    /// This defaults to `false`.
+    pub preserve_offset_sizes: bool,
+    /// If `true`, null bytes are appended to the end of the table, after
+    /// the global subr INDEX and the subtables of each font, so that its
+    /// length is a multiple of four.
+    ///
+    /// The padding isn't referenced by any offset, so it is ignored by
+    /// consumers. This defaults to `false`.
+    pub pad_to_four_bytes: bool,
+    /// The maximum size in bytes of the string INDEX when strings are added
+    /// with [`Cff::add_string`] and the methods that use it, or `None` if
+    /// the size is unlimited, which is the default.
+    ///
+    /// This guards against runaway growth of the string INDEX when strings
+    /// are added repeatedly without deduplication.
+    pub string_index_limit: Option<usize>,
+}
+
+/// A CFF table that is written by copying its source data.
+///
+/// This is created with [`Cff::passthrough`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PassthroughCff<'a> {
+    data: &'a [u8],
+}
+
+/// The subtables referenced by the Top DICT of a single font.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FontTables<I = Index1> {
+    /// The charstrings for each glyph.
+    pub char_strings: I,
+    /// A custom charset, or `None` if the font uses a predefined charset.
+    pub charset: Option<CustomCharset>,
+    /// The raw data of a custom encoding, or `None` if the font uses a
+    /// predefined encoding.
+    ///
+    /// Empty data is treated as `None`. The Standard encoding is the
+    /// default, so its Encoding operator is omitted when writing, while the
+    /// operand selecting the Expert encoding is kept.
+    pub encoding: Option<Vec<u8>>,
+    /// The Private DICT of a name-keyed font.
+    ///
+    /// An empty Private DICT is written if this is `None` and `fd_array` is
+    /// empty.
+    pub private_dict: Option<PrivateDict<I>>,
+    /// The Font DICTs of a CID-keyed font.
+    pub fd_array: Vec<FontDict<I>>,
+    /// The mapping from glyphs to entries in `fd_array` for a CID-keyed font.
+    ///
+    /// This is written in whichever of formats 0 and 3 is smaller. It must
+    /// map every glyph, which is checked when the table is validated.
+    pub fd_select: Option<FdSelect>,
+    /// The offset size of the FDArray INDEX in the source table, which is
+    /// kept if [`Cff::preserve_offset_sizes`] is set.
+    pub fd_array_off_size: u8,
+}
+
+/// The commonly edited values of a Top DICT.
+///
+/// All other entries are available in `raw_entries`.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TopDictData {
+    /// The version of the font, such as `1.000`.
+    pub version: Option<String>,
+    /// The trademark or other notice of the font.
+    pub notice: Option<String>,
+    /// The copyright notice of the font.
+    pub copyright: Option<String>,
+    /// The full name of the font, such as `Noto Serif Display Regular`.
+    pub full_name: Option<String>,
+    /// The family name of the font, such as `Noto Serif Display`.
+    pub family_name: Option<String>,
+    /// The weight of the font, such as `Regular` or `Bold`.
+    pub weight: Option<String>,
+    /// PostScript code to be embedded in the font's `/FontInfo` dictionary,
+    /// such as `/OrigFontType /TrueType def`.
+    pub post_script: Option<String>,
+    /// The name of the base font of a synthetic or multiple master font.
+    pub base_font_name: Option<String>,
+    /// The bounding box of all glyphs, as `[x_min, y_min, x_max, y_max]`.
+    pub font_bbox: Option<[f64; 4]>,
+    /// The transformation from glyph space to text space, as
+    /// `[a, b, c, d, tx, ty]`.
+    pub font_matrix: Option<[f64; 6]>,
+    /// Whether all glyphs have the same advance width.
+    ///
+    /// This is encoded as the integer 0 or 1, and omitted when `false`,
+    /// which is the default.
+    pub is_fixed_pitch: Option<bool>,
+    /// The unique identifier of the font.
+    pub unique_id: Option<i32>,
+    /// The extended unique identifier of the font, with at most
+    /// [`MAX_XUID_LEN`] elements.
+    pub xuid: Option<Vec<i32>>,
+    /// The number of CIDs in a CID-keyed font.
+    ///
+    /// This is omitted when it is the default of 8720.
+    pub cid_count: Option<u32>,
+    /// The version of a CID-keyed font.
+    pub cid_font_version: Option<f64>,
+    /// The revision of a CID-keyed font.
+    pub cid_font_revision: Option<f64>,
+    /// The remaining entries of the Top DICT, in their original order.
+    pub raw_entries: Vec<Entry>,
+    /// The order of the operators in the Top DICT this was read from.
+    ///
+    /// When written, entries follow this order and entries for any other
+    /// operators are appended at the end.
+    pub operator_order: Vec<DictKey>,
+}
+
+impl TopDictData {
+    /// Creates the typed representation of the given Top DICT entries.
+    ///
+    /// String values are resolved with `resolve`; entries with identifiers
+    /// that can't be resolved are retained in `raw_entries`.
+    pub fn from_entries(
+        entries: &[Entry],
+        resolve: impl Fn(StringId) -> Option<String>,
+    ) -> TopDictData {
+        let mut data = TopDictData::default();
+        for entry in entries {
+            data.operator_order.push(entry.key);
+            if let (DictKey::FontBbox, [x_min, y_min, x_max, y_max]) =
+                (entry.key, entry.operands.as_slice())
+            {
+                data.font_bbox = Some([x_min, y_min, x_max, y_max].map(|value| value.to_f64()));
+                continue;
+            }
+            if let (DictKey::FontMatrix, [a, b, c, d, tx, ty]) =
+                (entry.key, entry.operands.as_slice())
+            {
+                data.font_matrix = Some([a, b, c, d, tx, ty].map(|value| value.to_f64()));
+                continue;
+            }
+            if let [value] = entry.operands.as_slice() {
+                let field = match entry.key {
+                    DictKey::CidFontVersion => Some(&mut data.cid_font_version),
+                    DictKey::CidFontRevision => Some(&mut data.cid_font_revision),
+                    _ => None,
+                };
+                if let Some(field) = field {
+                    *field = Some(value.to_f64());
+                    continue;
+                }
+            }
+            let integers = entry
+                .operands
+                .iter()
+                .map(|operand| match operand {
+                    Operand::Integer(value) => Some(*value),
+                    Operand::Real(_) => None,
+                })
+                .collect::<Option<Vec<_>>>();
+            match (entry.key, integers) {
+                (DictKey::IsFixedPitch, Some(values)) if matches!(values[..], [0 | 1]) => {
+                    data.is_fixed_pitch = Some(values[0] == 1);
+                    continue;
+                }
+                (DictKey::UniqueId, Some(values)) if values.len() == 1 => {
+                    data.unique_id = Some(values[0]);
+                    continue;
+                }
+                (DictKey::Xuid, Some(values)) if (1..=MAX_XUID_LEN).contains(&values.len()) => {
+                    data.xuid = Some(values);
+                    continue;
+                }
+                (DictKey::CidCount, Some(values)) if values.len() == 1 && values[0] >= 0 => {
+                    data.cid_count = Some(values[0] as u32);
+                    continue;
+                }
+                _ => (),
+            }
+            let string = string_operand(entry).and_then(&resolve);
+            match (data.string_field(entry.key), string) {
+                (Some(field), Some(string)) => *field = Some(string),
+                _ => data.raw_entries.push(entry.clone()),
+            }
+        }
+        data
+    }
+
+    /// Sets the FontMatrix to scale glyph space by the given units per em,
+    /// as `[1/upm, 0, 0, 1/upm, 0, 0]`.
+    ///
+    /// A value of 1000 gives the default matrix. In a CID-keyed font the
+    /// Font DICTs may have their own FontMatrix, which is combined with this
+    /// one and isn't changed.
+    pub fn set_units_per_em(&mut self, upm: f64) {
+        let scale = 1.0 / upm;
+        self.font_matrix = Some([scale, 0.0, 0.0, scale, 0.0, 0.0]);
+    }
+
+    fn string_field(&mut self, key: DictKey) -> Option<&mut Option<String>> {
+        match key {
+            DictKey::Version => Some(&mut self.version),
+            DictKey::Notice => Some(&mut self.notice),
+            DictKey::Copyright => Some(&mut self.copyright),
+            DictKey::FullName => Some(&mut self.full_name),
+            DictKey::FamilyName => Some(&mut self.family_name),
+            DictKey::Weight => Some(&mut self.weight),
+            DictKey::PostScript => Some(&mut self.post_script),
+            DictKey::BaseFontName => Some(&mut self.base_font_name),
+            _ => None,
+        }
+    }
+}
+
+/// The byte ranges of the structures in a compiled CFF table, as returned
+/// by [`Cff::layout`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CffLayout {
+    /// The header, including any padding.
+    pub header: Range<usize>,
+    /// The Name INDEX.
+    pub names: Range<usize>,
+    /// The Top DICT INDEX.
+    pub top_dicts: Range<usize>,
+    /// The String INDEX.
+    pub strings: Range<usize>,
+    /// The Global Subr INDEX.
+    pub global_subrs: Range<usize>,
+    /// The variation store, including its length, which is empty if there
+    /// is no variation store.
+    pub variation_store: Range<usize>,
+    /// The subtables belonging to each font, in the order of the fonts in
+    /// the font set.
+    pub fonts: Vec<Range<usize>>,
+}
+
+/// A single font in a CFF font set.
+#[derive(Clone, Copy, Debug)]
+pub struct CffFontView<'a> {
+    cff: &'a Cff,
+    index: usize,
+}
+
+impl<'a> CffFontView<'a> {
+    /// The index of the font in the font set.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// The PostScript name of the font.
+    pub fn name(&self) -> Option<String> {
+        self.cff.names.get(self.index).map(latin1_to_string)
+    }
+
+    /// The raw data of the Top DICT.
+    pub fn top_dict_bytes(&self) -> &'a [u8] {
+        self.cff.top_dicts.get(self.index).unwrap_or_default()
+    }
+
+    /// The string values and remaining entries of the Top DICT.
+    pub fn top_dict_data(&self) -> Result<TopDictData, CffError> {
+        self.cff.get_top_dict_data(self.index)
+    }
+
+    /// The charstring of the given glyph.
+    pub fn charstring_bytes(&self, gid: u16) -> Result<&'a [u8], CffError> {
+        self.cff.charstring_bytes(self.index, gid)
+    }
+
+    /// The custom charset of the font, if any.
+    pub fn charset(&self) -> Option<&'a CustomCharset> {
+        self.cff
+            .font_tables
+            .get(self.index)
+            .and_then(|font| font.charset.as_ref())
+    }
+
+    /// The subtables of the font.
+    pub fn tables(&self) -> Option<&'a FontTables> {
+        self.cff.font_tables.get(self.index)
+    }
+}
+
+impl Cff {
+    /// Returns `true` if the first font in the font set is CID-keyed.
+    ///
+    /// A CID-keyed font is identified by the presence of the `ROS` operator
+    /// in its Top DICT.
+    pub fn is_cid_keyed(&self) -> bool {
+        self.top_dicts
+            .get(0)
+            .and_then(|data| dict::parse(data).ok())
+            .is_some_and(|entries| is_cid_keyed(&entries))
+    }
+
+    /// Returns the raw data of the Top DICT for the font at the given index.
+    ///
+    /// Returns an error if the index is out of range or the offsets of the
+    /// DICT are malformed.
+    pub fn get_top_dict_bytes(&self, font_index: usize) -> Result<&[u8], CffError> {
+        if font_index >= self.top_dicts.len() {
+            return Err(CffError::FontIndexOutOfBounds(font_index));
+        }
+        self.top_dicts
+            .get(font_index)
+            .ok_or(CffError::InvalidOffsetRange)
+    }
+
+    /// Returns the range of bytes occupied by an operator and its operands
+    /// in the Top DICT data of the font at the given index, or `None` if the
+    /// operator isn't present.
+    ///
+    /// Offsets are always encoded as five byte integers when written, so
+    /// the span of an offset operator in a written table doesn't change when
+    /// the offset does, and the operand can be patched in place.
+    pub fn top_dict_operator_span(
+        &self,
+        font_index: usize,
+        key: DictKey,
+    ) -> Result<Option<Range<usize>>, CffError> {
+        let entries = dict::parse_with_ranges(self.get_top_dict_bytes(font_index)?)?;
+        Ok(entries
+            .into_iter()
+            .find(|(entry, _)| entry.key == key)
+            .map(|(_, range)| range))
+    }
+
+    /// Returns the charstring of a glyph in the font at the given index.
+    ///
+    /// Returns an error if either index is out of range or the offsets of
+    /// the charstring are malformed.
+    pub fn charstring_bytes(&self, font_index: usize, gid: u16) -> Result<&[u8], CffError> {
+        let char_strings = &self
+            .font_tables
+            .get(font_index)
+            .ok_or(CffError::FontIndexOutOfBounds(font_index))?
+            .char_strings;
+        if gid as usize >= char_strings.len() {
+            return Err(CffError::GlyphIndexOutOfBounds(gid));
+        }
+        char_strings
+            .get(gid as usize)
+            .ok_or(CffError::InvalidOffsetRange)
+    }
+
+    /// Returns the local subroutines of a Private DICT of the font at the
+    /// given index, or `None` if the Private DICT has none.
+    ///
+    /// A name-keyed font has a single Private DICT, with `fd_index` 0, and a
+    /// CID-keyed font has one for each Font DICT in its FDArray.
+    pub fn local_subrs(
+        &self,
+        font_index: usize,
+        fd_index: usize,
+    ) -> Result<Option<&Index1>, CffError> {
+        let font = self
+            .font_tables
+            .get(font_index)
+            .ok_or(CffError::FontIndexOutOfBounds(font_index))?;
+        let private = font
+            .private_dicts()
+            .get(fd_index)
+            .copied()
+            .ok_or(CffError::FdIndexOutOfBounds(fd_index))?;
+        Ok(private.and_then(|private| private.subrs.as_ref()))
+    }
+
+    /// Returns the index of the Font DICT selected for the given glyph of
+    /// the font at the given index.
+    ///
+    /// This is always 0 for a name-keyed font, which has no FDSelect, and
+    /// for glyphs that the FDSelect doesn't cover.
+    pub fn fd_index(&self, font_index: usize, gid: u16) -> Result<u8, CffError> {
+        let font = self
+            .font_tables
+            .get(font_index)
+            .ok_or(CffError::FontIndexOutOfBounds(font_index))?;
+        if gid as usize >= font.char_strings.len() {
+            return Err(CffError::GlyphIndexOutOfBounds(gid));
+        }
+        Ok(font.fd_index(gid as usize) as u8)
+    }
+
+    /// Returns the `defaultWidthX` and `nominalWidthX` of the Private DICT
+    /// used by the given glyph of the font at the given index.
+    ///
+    /// For a CID-keyed font, the Private DICT is that of the Font DICT
+    /// selected by the FDSelect. Values missing from the Private DICT, or a
+    /// missing Private DICT, give the default of 0.
+    pub fn widths_for_glyph(&self, font_index: usize, gid: u16) -> Result<(f64, f64), CffError> {
+        let fd_index = self.fd_index(font_index, gid)? as usize;
+        let private = self.font_tables[font_index]
+            .private_dicts()
+            .get(fd_index)
+            .copied()
+            .ok_or(CffError::FdIndexOutOfBounds(fd_index))?;
+        let data = match private {
+            Some(private) => PrivateDictData::parse(&private.dict, &[])?,
+            None => PrivateDictData::default(),
+        };
+        let width = |value: Option<BlendedValue>| value.map_or(0.0, |value| value.default);
+        Ok((width(data.default_width_x), width(data.nominal_width_x)))
+    }
+
+    /// Replaces the local subroutines of a Private DICT of the font at the
+    /// given index, as selected for [`Cff::local_subrs`].
+    ///
+    /// An empty Private DICT is added if necessary. The `Subrs` offset of the
+    /// Private DICT is computed when the table is written, and removed if
+    /// `subrs` is `None`.
+    pub fn set_local_subrs(
+        &mut self,
+        font_index: usize,
+        fd_index: usize,
+        subrs: Option<Index1>,
+    ) -> Result<(), CffError> {
+        let font = self
+            .font_tables
+            .get_mut(font_index)
+            .ok_or(CffError::FontIndexOutOfBounds(font_index))?;
+        let private = if font.fd_array.is_empty() && fd_index == 0 {
+            &mut font.private_dict
+        } else {
+            &mut font
+                .fd_array
+                .get_mut(fd_index)
+                .ok_or(CffError::FdIndexOutOfBounds(fd_index))?
+                .private_dict
+        };
+        private.get_or_insert_with(Default::default).subrs = subrs;
+        Ok(())
+    }
+
+    /// Replaces the global subroutines.
+    ///
+    /// The subroutine at each index of `subrs` replaces the subroutine with
+    /// the same number. If the subroutine bias changes with the number of
+    /// subroutines, the operands of the `callgsubr` operators in every
+    /// charstring and local subroutine are updated to keep calling the same
+    /// numbers; calls within `subrs` must already use the new bias. Local
+    /// subroutines that aren't called by any charstring can't be parsed, so
+    /// they are left unchanged.
+    ///
+    /// Returns [`CffError::InvalidCharstring`] if the bias changes and a
+    /// charstring calls a subroutine that isn't in `subrs`.
+    pub fn set_global_subrs(&mut self, subrs: Vec<Vec<u8>>) -> Result<(), CffError> {
+        let old_count = self.global_subrs.len();
+        if charstring::subr_bias(old_count) != charstring::subr_bias(subrs.len()) {
+            self.renumber_global_subr_calls(&SubrMap::rebias(old_count, subrs.len()), false)?;
+        }
+        self.global_subrs = Index1::from_bytes(subrs);
+        Ok(())
+    }
+
+    /// Updates the operands of the `callgsubr` operators in every charstring
+    /// and local subroutine, and in the global subroutines if
+    /// `in_global_subrs` is set.
+    fn renumber_global_subr_calls(
+        &mut self,
+        global_map: &SubrMap,
+        in_global_subrs: bool,
+    ) -> Result<(), CffError> {
+        let mut parsed = self.parse_charstrings()?;
+        if in_global_subrs {
+            for tokens in parsed.global_subrs.iter_mut().flatten() {
+                renumber_subr_calls(tokens, global_map, None)?;
+            }
+        }
+        for (char_strings, local_subrs) in
+            parsed.char_strings.iter_mut().zip(&mut parsed.local_subrs)
+        {
+            for (_, tokens) in char_strings {
+                renumber_subr_calls(tokens, global_map, None)?;
+            }
+            for tokens in local_subrs.iter_mut().flatten().flatten() {
+                renumber_subr_calls(tokens, global_map, None)?;
+            }
+        }
+        self.set_parsed_charstrings(parsed);
+        Ok(())
+    }
+
+    /// Returns the string identifiers referenced by the Top DICT of the font
+    /// at the given index and by the Font DICTs in its FDArray, in ascending
+    /// order and without duplicates.
+    ///
+    /// This includes the registry and ordering of the `ROS` operator, but
+    /// not the glyph names of the charset.
+    pub fn referenced_string_ids(&self, font_index: usize) -> Result<Vec<StringId>, CffError> {
+        let mut sids = dict_string_ids(&dict::parse(self.get_top_dict_bytes(font_index)?)?)
+            .collect::<Vec<_>>();
+        for font_dict in self
+            .font_tables
+            .get(font_index)
+            .map(|font| font.fd_array.as_slice())
+            .unwrap_or_default()
+        {
+            sids.extend(dict_string_ids(&dict::parse(&font_dict.dict)?));
+        }
+        sids.sort_unstable_by_key(|sid| sid.to_u16());
+        sids.dedup();
+        Ok(sids)
+    }
+
+    /// Returns the bytes of the string with the given identifier.
+    ///
+    /// Identifiers below 391 refer to the standard strings; the remainder
+    /// index into the string INDEX. Returns an error if the identifier is out
+    /// of range or the offsets of the string are malformed.
+    pub fn get_string_bytes(&self, sid: StringId) -> Result<&[u8], CffError> {
+        match sid.standard_string() {
+            Ok(string) => Ok(string.bytes()),
+            Err(index) if index >= self.strings.len() => Err(CffError::InvalidStringId(sid)),
+            Err(index) => self.strings.get(index).ok_or(CffError::InvalidOffsetRange),
+        }
+    }
+
+    /// Returns the string with the given identifier.
+    ///
+    /// Strings in CFF are Latin-1 encoded.
+    pub fn resolve_string(&self, sid: StringId) -> Option<String> {
+        self.get_string_bytes(sid).ok().map(latin1_to_string)
+    }
+
+    /// Returns each string in the string INDEX with its identifier.
+    ///
+    /// Custom strings follow the 391 standard strings, so the first has the
+    /// identifier 391. Returns an error if the offsets of a string are
+    /// malformed.
+    pub fn custom_strings(&self) -> Result<Vec<(StringId, String)>, CffError> {
+        let first = StringId::new(STANDARD_STRING_COUNT as u16);
+        std::iter::successors(Some(first), |sid| Some(sid.next()))
+            .take(self.strings.len())
+            .map(|sid| {
+                let bytes = sid
+                    .custom_index()
+                    .and_then(|index| self.strings.get(index))
+                    .ok_or(CffError::InvalidOffsetRange)?;
+                Ok((sid, latin1_to_string(bytes)))
+            })
+            .collect()
+    }
+
+    /// Returns the identifier of each standard and custom string.
+    ///
+    /// If a string occurs more than once, its first identifier is used, so
+    /// standard strings take precedence over identical custom strings.
+    /// Custom strings with malformed offsets are skipped.
+    pub fn string_map(&self) -> BTreeMap<String, StringId> {
+        let mut map = BTreeMap::new();
+        let standard = read_fonts::tables::postscript::STANDARD_STRINGS
+            .iter()
+            .map(|string| Some(string.as_bytes()));
+        let custom = (0..self.strings.len()).map(|index| self.strings.get(index));
+        for (sid, bytes) in standard.chain(custom).enumerate() {
+            if let Some(bytes) = bytes {
+                map.entry(latin1_to_string(bytes))
+                    .or_insert(StringId::new(sid as u16));
+            }
+        }
+        map
+    }
+
+    /// Limits the size in bytes of the string INDEX when strings are added.
+    ///
+    /// See [`Cff::string_index_limit`] for details.
+    pub fn set_string_index_limit(&mut self, max_bytes: usize) {
+        self.string_index_limit = Some(max_bytes);
+    }
+
+    /// Returns an identifier for the given string, adding it to the string
+    /// INDEX if necessary.
+    ///
+    /// Standard strings and strings already present in the string INDEX are
+    /// reused. Characters that can't be represented in Latin-1 are replaced
+    /// with '?'.
+    ///
+    /// Returns [`CffError::StringIndexTooLarge`] if adding the string would
+    /// make the string INDEX larger than [`Cff::string_index_limit`], or if
+    /// every string identifier is already in use.
+    pub fn add_string(&mut self, string: &str) -> Result<StringId, CffError> {
+        self.add_string_with_options(string, StringIndexOptions::default())
+    }
+
+    /// Returns an identifier for the given string, adding it to the string
+    /// INDEX unless `options` allow an existing identifier to be reused.
+    ///
+    /// Characters that can't be represented in Latin-1 are replaced with '?'.
+    /// See [`Cff::add_string`] for the errors.
+    pub fn add_string_with_options(
+        &mut self,
+        string: &str,
+        options: StringIndexOptions,
+    ) -> Result<StringId, CffError> {
+        let bytes = string_to_latin1(string);
+        let standard = options
+            .reuse_standard
+            .then(|| {
+                read_fonts::tables::postscript::STANDARD_STRINGS
+                    .iter()
+                    .position(|standard| standard.as_bytes() == bytes)
+            })
+            .flatten();
+        let existing = standard.or_else(|| {
+            options
+                .dedup
+                .then(|| self.strings.iter().position(|custom| custom == bytes))
+                .flatten()
+                .map(|ix| ix + STANDARD_STRING_COUNT)
+        });
+        let index = match existing {
+            Some(index) => index,
+            None => {
+                // string identifiers are limited to 16 bits
+                let index = self.strings.len() + STANDARD_STRING_COUNT;
+                if index > u16::MAX as usize {
+                    return Err(CffError::StringIndexTooLarge);
+                }
+                if let Some(limit) = self.string_index_limit {
+                    // the size of the INDEX with the string appended
+                    let data_len = self.strings.data.len() + bytes.len();
+                    let offsets_len =
+                        (self.strings.len() + 2) * min_offset_size(data_len + 1) as usize;
+                    if 3 + offsets_len + data_len > limit {
+                        return Err(CffError::StringIndexTooLarge);
+                    }
+                }
+                self.strings.push(&bytes);
+                index
+            }
+        };
+        Ok(StringId::new(index as u16))
+    }
+
+    /// Returns an iterator over the fonts in the font set.
+    pub fn fonts(&self) -> impl Iterator<Item = CffFontView<'_>> + '_ {
+        (0..self.top_dicts.len()).map(|index| CffFontView { cff: self, index })
+    }
+
+    /// Returns the string values and remaining entries of the Top DICT for
+    /// the font at the given index.
+    pub fn get_top_dict_data(&self, font_index: usize) -> Result<TopDictData, CffError> {
+        let entries = dict::parse(self.get_top_dict_bytes(font_index)?)?;
+        Ok(TopDictData::from_entries(&entries, |sid| {
+            self.resolve_string(sid)
+        }))
+    }
+
+    /// Returns the string values and remaining entries of the Top DICT for
+    /// the font at the given index, rejecting operators that aren't defined
+    /// by the specification.
+    ///
+    /// [`get_top_dict_data`](Self::get_top_dict_data) keeps such operators
+    /// in `raw_entries`; this returns [`CffError::UnknownOperator`] with the
+    /// opcode of the first one instead.
+    pub fn get_top_dict_data_strict(&self, font_index: usize) -> Result<TopDictData, CffError> {
+        let entries = dict::parse(self.get_top_dict_bytes(font_index)?)?;
+        if let Some(DictKey::Unknown(opcode)) = entries
+            .iter()
+            .map(|entry| entry.key)
+            .find(|key| matches!(key, DictKey::Unknown(_)))
+        {
+            return Err(CffError::UnknownOperator(opcode));
+        }
+        Ok(TopDictData::from_entries(&entries, |sid| {
+            self.resolve_string(sid)
+        }))
+    }
+
+    /// Replaces the Top DICT for the font at the given index.
+    ///
+    /// The DICT is rebuilt from the typed fields and the raw entries, and
+    /// strings are added to the string INDEX as required. The string INDEX
+    /// is shared by all fonts in the font set, so a string that is already
+    /// present, including one added for another font, is reused. Entries
+    /// with the same values as in the current Top DICT keep their original
+    /// encoding, and entries with the default values given by
+    /// [`DictKey::top_dict_default`] are omitted.
+    /// Strings longer than [`MAX_STRING_LEN`] bytes are added, but are
+    /// reported as an error when the table is validated.
+    ///
+    /// In a CID-keyed font the ROS entry is kept if it isn't present in
+    /// `raw_entries`, and is always written first as required.
+    ///
+    /// Returns [`CffError::NameKeyedFontUnsupported`] if `cid_count`,
+    /// `cid_font_version` or `cid_font_revision` is set for a name-keyed
+    /// font, [`CffError::InvalidDict`] if `xuid` is empty or has more than
+    /// [`MAX_XUID_LEN`] elements or `cid_count` is larger than `i32::MAX`,
+    /// [`CffError::DegenerateFontMatrix`] if
+    /// the determinant of `font_matrix` is zero, or
+    /// [`CffError::StringIndexTooLarge`] if the strings exceed
+    /// [`Cff::string_index_limit`].
+    pub fn set_top_dict_data(
+        &mut self,
+        font_index: usize,
+        data: &TopDictData,
+    ) -> Result<(), CffError> {
+        self.set_top_dict_data_with_options(font_index, data, StringIndexOptions::default())
+    }
+
+    /// Replaces the Top DICT for the font at the given index, adding strings
+    /// to the string INDEX as determined by `options`.
+    ///
+    /// See [`Cff::set_top_dict_data`] for details.
+    pub fn set_top_dict_data_with_options(
+        &mut self,
+        font_index: usize,
+        data: &TopDictData,
+        options: StringIndexOptions,
+    ) -> Result<(), CffError> {
+        let source_entries = dict::parse(self.get_top_dict_bytes(font_index)?)?;
+        let is_cid = is_cid_keyed(&source_entries);
+        let has_cid_fields = data.cid_count.is_some()
+            || data.cid_font_version.is_some()
+            || data.cid_font_revision.is_some();
+        if !is_cid && has_cid_fields {
+            return Err(CffError::NameKeyedFontUnsupported);
+        }
+        let mut data = data.clone();
+        // removing the ROS would turn a CID-keyed font into a broken
+        // name-keyed one
+        if is_cid
+            && data
+                .raw_entries
+                .iter()
+                .all(|entry| entry.key != DictKey::Ros)
+        {
+            let ros = source_entries
+                .iter()
+                .find(|entry| entry.key == DictKey::Ros);
+            data.raw_entries.extend(ros.cloned());
+        }
+        let mut entries = Vec::new();
+        for key in TOP_DICT_STRING_KEYS {
+            if let Some(string) = data.string_field(key).and_then(Option::take) {
+                let sid = self.add_string_with_options(&string, options)?;
+                entries.push(Entry::new(key, vec![Operand::Integer(sid.to_u16() as i32)]));
+            }
+        }
+        if let Some(bbox) = data.font_bbox {
+            entries.push(Entry::new(
+                DictKey::FontBbox,
+                bbox.map(Operand::Real).to_vec(),
+            ));
+        }
+        if let Some(matrix) = data.font_matrix {
+            if !is_valid_font_matrix(&matrix) {
+                return Err(CffError::DegenerateFontMatrix);
+            }
+            entries.push(Entry::new(
+                DictKey::FontMatrix,
+                matrix.map(Operand::Real).to_vec(),
+            ));
+        }
+        if let Some(is_fixed_pitch) = data.is_fixed_pitch {
+            entries.push(Entry::new(
+                DictKey::IsFixedPitch,
+                vec![Operand::Integer(is_fixed_pitch as i32)],
+            ));
+        }
+        if let Some(unique_id) = data.unique_id {
+            entries.push(Entry::new(
+                DictKey::UniqueId,
+                vec![Operand::Integer(unique_id)],
+            ));
+        }
+        if let Some(xuid) = &data.xuid {
+            if xuid.is_empty() || xuid.len() > MAX_XUID_LEN {
+                return Err(CffError::InvalidDict);
+            }
+            entries.push(Entry::new(
+                DictKey::Xuid,
+                xuid.iter().copied().map(Operand::Integer).collect(),
+            ));
+        }
+        if let Some(cid_count) = data.cid_count {
+            let cid_count = i32::try_from(cid_count).map_err(|_| CffError::InvalidDict)?;
+            entries.push(Entry::new(
+                DictKey::CidCount,
+                vec![Operand::Integer(cid_count)],
+            ));
+        }
+        for (key, value) in [
+            (DictKey::CidFontVersion, data.cid_font_version),
+            (DictKey::CidFontRevision, data.cid_font_revision),
+        ] {
+            if let Some(value) = value {
+                entries.push(Entry::new(key, vec![Operand::Real(value)]));
+            }
+        }
+        // the typed fields replace any raw entries with the same key
+        let is_typed = |key: DictKey| {
+            TOP_DICT_STRING_KEYS.contains(&key)
+                || (key == DictKey::FontBbox && data.font_bbox.is_some())
+                || (key == DictKey::FontMatrix && data.font_matrix.is_some())
+                || (key == DictKey::IsFixedPitch && data.is_fixed_pitch.is_some())
+                || (key == DictKey::UniqueId && data.unique_id.is_some())
+                || (key == DictKey::Xuid && data.xuid.is_some())
+                || (key == DictKey::CidCount && data.cid_count.is_some())
+                || (key == DictKey::CidFontVersion && data.cid_font_version.is_some())
+                || (key == DictKey::CidFontRevision && data.cid_font_revision.is_some())
+        };
+        entries.extend(
+            data.raw_entries
+                .iter()
+                .filter(|entry| !is_typed(entry.key))
+                .cloned(),
+        );
+        entries.retain(|entry| !entry.is_top_dict_default());
+        // the sort is stable, so new entries keep their relative order; the
+        // ROS must be the first operator of a CID-keyed font
+        entries.sort_by_key(|entry| {
+            let position = data
+                .operator_order
+                .iter()
+                .position(|key| *key == entry.key)
+                .unwrap_or(usize::MAX);
+            (entry.key != DictKey::Ros, position)
+        });
+        let top_dict = encode_entries(&entries, self.get_top_dict_bytes(font_index)?);
+        self.replace_top_dict(font_index, &top_dict);
+        Ok(())
+    }
+
+    /// Sets the operands of an operator in the Top DICT for the font at the
+    /// given index.
+    ///
+    /// An existing entry for the operator is replaced in place; otherwise
+    /// the entry is appended. This allows setting operators that aren't
+    /// represented by a field of [`TopDictData`], such as `PaintType` or
+    /// `CIDFontType`. Operands that are string identifiers must refer to
+    /// strings added with [`add_string`](Self::add_string). Any offsets are
+    /// replaced when the table is written.
+    pub fn set_top_dict_operator(
+        &mut self,
+        font_index: usize,
+        key: DictKey,
+        operands: Vec<Operand>,
+    ) -> Result<(), CffError> {
+        let source = self.get_top_dict_bytes(font_index)?;
+        // check the source, since rewriting malformed data has no effect
+        dict::parse(source)?;
+        let mut encoded = Vec::new();
+        for operand in operands {
+            dict::encode_operand(operand, &mut encoded);
+        }
+        let top_dict = dict::rewrite(source, &[(key, Some(encoded))]);
+        self.replace_top_dict(font_index, &top_dict);
+        Ok(())
+    }
+
+    /// Replaces the Top DICT data of the font at the given index, which
+    /// must be in bounds.
+    ///
+    /// The INDEX is rebuilt, so its offset size fits the new data.
+    fn replace_top_dict(&mut self, font_index: usize, top_dict: &[u8]) {
+        self.top_dicts = Index1::from_bytes(self.top_dicts.iter().enumerate().map(|(i, dict)| {
+            if i == font_index {
+                top_dict
+            } else {
+                dict
+            }
+        }));
+    }
+
+    /// Returns the raw data of the Private DICT for the font at the given
+    /// index, as referenced by the `Private` operator of its Top DICT.
+    ///
+    /// The data is empty if the font has no Private DICT, which is the case
+    /// for a CID-keyed font, where each Font DICT has its own.
+    pub fn private_dict_bytes(&self, font_index: usize) -> Result<&[u8], CffError> {
+        let font = self
+            .font_tables
+            .get(font_index)
+            .ok_or(CffError::FontIndexOutOfBounds(font_index))?;
+        Ok(font
+            .private_dict
+            .as_ref()
+            .map_or(&[][..], |private| private.dict.as_slice()))
+    }
+
+    /// Returns the hinting values of the Private DICT for the font at the
+    /// given index.
+    ///
+    /// If the font has no Private DICT, default values are returned.
+    pub fn get_private_dict_data(&self, font_index: usize) -> Result<PrivateDictData, CffError> {
+        PrivateDictData::parse(self.private_dict_bytes(font_index)?, &[])
+    }
+
+    /// Replaces the Private DICT for the font at the given index.
+    ///
+    /// Local subroutines are retained.
+    pub fn set_private_dict_data(
+        &mut self,
+        font_index: usize,
+        data: &PrivateDictData,
+    ) -> Result<(), CffError> {
+        let font = self
+            .font_tables
+            .get_mut(font_index)
+            .ok_or(CffError::FontIndexOutOfBounds(font_index))?;
+        font.private_dict.get_or_insert_with(Default::default).dict = data.encode();
+        Ok(())
+    }
+
+    /// Serializes the table.
+    ///
+    /// This is equivalent to [`dump_table`](crate::dump_table).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use read_fonts::{FontRef, TableProvider};
+    /// # use write_fonts::{from_obj::ToOwnedTable, tables::cff::{Cff, CffError}};
+    /// # fn main() -> Result<(), CffError> {
+    /// # let font = FontRef::new(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED)?;
+    /// let mut cff: Cff = font.cff()?.to_owned_table();
+    /// let mut top_dict = cff.get_top_dict_data(0)?;
+    /// top_dict.version = Some("2.000".into());
+    /// cff.set_top_dict_data(0, &top_dict)?;
+    /// let bytes = cff.to_bytes()?;
+    /// # assert!(!bytes.is_empty());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn to_bytes(&self) -> Result<Vec<u8>, CffError> {
+        Ok(crate::dump_table(self)?)
+    }
+
+    /// Returns a human readable description of the font set.
+    ///
+    /// This includes the resolved version and family name, the number of
+    /// glyphs and subroutines and whether each font is CID-keyed.
+    pub fn summary(&self) -> String {
+        use std::fmt::Write;
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "CFF font set: {} font(s), {} global subrs, {} custom strings",
+            self.top_dicts.len(),
+            self.global_subrs.len(),
+            self.strings.len()
+        );
+        for font in self.fonts() {
+            let name = font.name().unwrap_or_default();
+            let _ = writeln!(out, "font {}: {name}", font.index());
+            let top_dict = font.top_dict_data().unwrap_or_default();
+            let field = |value: Option<String>| value.unwrap_or_else(|| "<none>".into());
+            let _ = writeln!(out, "  version: {}", field(top_dict.version));
+            let _ = writeln!(out, "  family name: {}", field(top_dict.family_name));
+            let cid_keyed =
+                dict::parse(font.top_dict_bytes()).is_ok_and(|entries| is_cid_keyed(&entries));
+            let _ = writeln!(out, "  CID-keyed: {cid_keyed}");
+            if let Some(tables) = font.tables() {
+                let local_subrs = tables
+                    .private_dict
+                    .iter()
+                    .chain(
+                        tables
+                            .fd_array
+                            .iter()
+                            .filter_map(|font_dict| font_dict.private_dict.as_ref()),
+                    )
+                    .filter_map(|private| private.subrs.as_ref())
+                    .map(Index1::len)
+                    .sum::<usize>();
+                let _ = writeln!(out, "  glyphs: {}", tables.char_strings.len());
+                let _ = writeln!(out, "  local subrs: {local_subrs}");
+                if !tables.fd_array.is_empty() {
+                    let _ = writeln!(out, "  font dicts: {}", tables.fd_array.len());
+                }
+            }
+        }
+        out
+    }
+
+    /// Scales the outlines of every font in the font set by `factor`.
+    ///
+    /// The coordinates in all charstrings and subroutines are multiplied by
+    /// `factor` and rounded to the nearest integer. The FontBBox and
+    /// underline metrics in each Top DICT and the hinting values and widths
+    /// in each Private DICT are scaled to match; the FontMatrix is
+    /// unchanged.
+    pub fn scale_outlines(&mut self, factor: f64) -> Result<(), CffError> {
+        let mut parsed = self.parse_charstrings()?;
+        parsed.for_each_mut(|tokens| charstring::scale(tokens, factor));
+        let mut private_dicts = Vec::with_capacity(self.font_tables.len());
+        let mut top_dicts = Vec::with_capacity(self.top_dicts.len());
+        for (font_index, font) in self.font_tables.iter().enumerate() {
+            let mut font_private_dicts = Vec::new();
+            for private in font.private_dicts().into_iter().flatten() {
+                let mut data = PrivateDictData::parse(&private.dict, &[])?;
+                data.scale(factor);
+                font_private_dicts.push(data.encode());
+            }
+            private_dicts.push(font_private_dicts);
+            let top_dict = self.get_top_dict_bytes(font_index)?;
+            let updates = dict::parse(top_dict)?
+                .into_iter()
+                .filter(|entry| {
+                    matches!(
+                        entry.key,
+                        DictKey::FontBbox
+                            | DictKey::UnderlinePosition
+                            | DictKey::UnderlineThickness
+                    )
+                })
+                .map(|entry| {
+                    let mut operands = Vec::new();
+                    for operand in entry.operands {
+                        dict::encode_number((operand.to_f64() * factor).round(), &mut operands);
+                    }
+                    (entry.key, Some(operands))
+                })
+                .collect::<Vec<_>>();
+            top_dicts.push(dict::rewrite(top_dict, &updates));
+        }
+
+        self.set_parsed_charstrings(parsed);
+        for (font, private_dicts) in self.font_tables.iter_mut().zip(private_dicts) {
+            let privates = font.private_dicts_mut().into_iter().flatten();
+            for (private, dict) in privates.zip(private_dicts) {
+                private.dict = dict;
+            }
+        }
+        self.top_dicts = Index1::from_bytes(
+            self.top_dicts
+                .iter()
+                .enumerate()
+                .map(|(i, dict)| top_dicts.get(i).map(Vec::as_slice).unwrap_or(dict)),
+        );
+        Ok(())
+    }
+
+    /// Sets the FontBBox of the font at the given index to the union of the
+    /// bounds of its glyphs, returning the new `[x_min, y_min, x_max,
+    /// y_max]`.
+    ///
+    /// Every charstring is interpreted as by [`Cff::outline`], and the bounds
+    /// of the points of each outline, including the control points of
+    /// curves, are rounded outwards to integers. The FontBBox is all zeros if
+    /// no glyph has an outline.
+    pub fn recompute_font_bbox(&mut self, font_index: usize) -> Result<[f64; 4], CffError> {
+        let num_glyphs = self
+            .font_tables
+            .get(font_index)
+            .ok_or(CffError::FontIndexOutOfBounds(font_index))?
+            .char_strings
+            .len();
+        let mut pen = BoundsPen::default();
+        let outlines = OutlineData::new(self, font_index)?;
+        for gid in 0..num_glyphs {
+            outlines.draw(gid as u16, &mut pen)?;
+        }
+        let bbox = pen
+            .0
+            .map(|[x_min, y_min, x_max, y_max]| {
+                [x_min.floor(), y_min.floor(), x_max.ceil(), y_max.ceil()]
+            })
+            .unwrap_or_default();
+        self.set_top_dict_operator(
+            font_index,
+            DictKey::FontBbox,
+            bbox.map(|value| Operand::Integer(value as i32)).to_vec(),
+        )?;
+        Ok(bbox)
+    }
+
+    /// Removes the hints from every font in the font set.
+    ///
+    /// The stem hints and the `hintmask` and `cntrmask` operators are removed
+    /// from all charstrings and subroutines, and the alignment zones and
+    /// standard stem widths are removed from each Private DICT. If the
+    /// operands of a hint are split between a subroutine and its caller, all
+    /// subroutines are first inlined into the charstrings.
+    pub fn strip_hints(&mut self) -> Result<(), CffError> {
+        let mut parsed = self.parse_charstrings()?;
+        let mut stripped = parsed
+            .global_subrs
+            .iter_mut()
+            .flatten()
+            .all(|tokens| charstring::strip_hints(tokens, true));
+        for (char_strings, local_subrs) in
+            parsed.char_strings.iter_mut().zip(&mut parsed.local_subrs)
+        {
+            stripped = stripped
+                && char_strings
+                    .iter_mut()
+                    .all(|(_, tokens)| charstring::strip_hints(tokens, false))
+                && local_subrs
+                    .iter_mut()
+                    .flatten()
+                    .flatten()
+                    .all(|tokens| charstring::strip_hints(tokens, true));
+        }
+        if !stripped {
+            self.inline_subrs()?;
+            parsed = self.parse_charstrings()?;
+            for (_, tokens) in parsed.char_strings.iter_mut().flatten() {
+                if !charstring::strip_hints(tokens, false) {
+                    return Err(CffError::InvalidCharstring);
+                }
+            }
+        }
+        let mut private_dicts = Vec::with_capacity(self.font_tables.len());
+        for font in &self.font_tables {
+            let mut font_private_dicts = Vec::new();
+            for private in font.private_dicts().into_iter().flatten() {
+                let mut data = PrivateDictData::parse(&private.dict, &[])?;
+                data.clear_hints();
+                font_private_dicts.push(data.encode());
+            }
+            private_dicts.push(font_private_dicts);
+        }
+
+        self.set_parsed_charstrings(parsed);
+        for (font, private_dicts) in self.font_tables.iter_mut().zip(private_dicts) {
+            let privates = font.private_dicts_mut().into_iter().flatten();
+            for (private, dict) in privates.zip(private_dicts) {
+                private.dict = dict;
+            }
+        }
+        Ok(())
+    }
+
+    /// Extracts repeated sequences of path operators in the charstrings of
+    /// every font in the font set into global subroutines.
+    ///
+    /// Existing subroutines are first inlined into the charstrings and
+    /// removed, so that the result doesn't depend on how the source was
+    /// subroutinized. Sequences are then chosen greedily by their estimated
+    /// saving; hints, the advance width and `endchar` remain in each
+    /// charstring. This is a basic subroutinizer, so a font that was
+    /// subroutinized by a specialized tool may grow.
+    pub fn subroutinize(&mut self) -> Result<(), CffError> {
+        self.inline_subrs()?;
+        let mut tokens = Vec::new();
+        for font in &self.font_tables {
+            for charstring in font.char_strings.iter() {
+                tokens.push(Parser::new(&[]).parse(charstring)?);
+            }
+        }
+        let (global_subrs, char_strings) = subroutinize(&tokens);
+        self.global_subrs = Index1::from_bytes(global_subrs);
+        let mut char_strings = char_strings.into_iter();
+        for font in &mut self.font_tables {
+            let count = font.char_strings.len();
+            font.char_strings = Index1::from_bytes(char_strings.by_ref().take(count));
+        }
+        Ok(())
+    }
+
+    /// Replaces every subroutine call with the tokens of the subroutine,
+    /// then removes all global and local subroutines.
+    fn inline_subrs(&mut self) -> Result<(), CffError> {
+        self.check_charstring_types()?;
+        let global_subrs = self.global_subrs.iter().collect::<Vec<_>>();
+        let mut char_strings = Vec::with_capacity(self.font_tables.len());
+        for font in &self.font_tables {
+            let local_subrs = font
+                .private_dicts()
+                .into_iter()
+                .map(|private| match private.and_then(|p| p.subrs.as_ref()) {
+                    Some(subrs) => subrs.iter().collect(),
+                    None => Vec::new(),
+                })
+                .collect::<Vec<Vec<_>>>();
+            let mut inlined = Vec::with_capacity(font.char_strings.len());
+            for (gid, charstring) in font.char_strings.iter().enumerate() {
+                let fd = font.fd_index(gid);
+                let subrs = local_subrs
+                    .get(fd)
+                    .ok_or(CffError::FdIndexOutOfBounds(fd))?;
+                let tokens = Parser::new(&[])
+                    .with_subrs(&global_subrs, subrs)
+                    .with_inlined_subrs()
+                    .parse(charstring)?;
+                inlined.push(charstring::encode(&tokens));
+            }
+            char_strings.push(inlined);
+        }
+        self.global_subrs = Index1::default();
+        for (font, char_strings) in self.font_tables.iter_mut().zip(char_strings) {
+            font.char_strings = Index1::from_bytes(char_strings);
+            for private in font.private_dicts_mut().into_iter().flatten() {
+                private.subrs = None;
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes the global and local subroutines that are not called by any
+    /// charstring, renumbering the calls to the remaining subroutines.
+    ///
+    /// Local subroutines are only removed if no global subroutine calls a
+    /// local subroutine, since the numbering would then depend on the
+    /// calling glyph.
+    pub fn remove_unused_subrs(&mut self) -> Result<(), CffError> {
+        let mut parsed = self.parse_charstrings()?;
+        let global_map = SubrMap::new(&parsed.global_subrs);
+        let local_maps = parsed
+            .local_subrs
+            .iter()
+            .map(|font| {
+                font.iter()
+                    .map(|subrs| match parsed.global_calls_local {
+                        true => SubrMap::identity(subrs.len()),
+                        false => SubrMap::new(subrs),
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        // local subroutines called from global subroutines keep their
+        // numbers
+        for tokens in parsed.global_subrs.iter_mut().flatten() {
+            renumber_subr_calls(tokens, &global_map, None)?;
+        }
+        for (font_index, maps) in local_maps.iter().enumerate() {
+            for (fd, tokens) in &mut parsed.char_strings[font_index] {
+                let local_map = maps.get(*fd).ok_or(CffError::FdIndexOutOfBounds(*fd))?;
+                renumber_subr_calls(tokens, &global_map, Some(local_map))?;
+            }
+            for (subrs, local_map) in parsed.local_subrs[font_index].iter_mut().zip(maps) {
+                for tokens in subrs.iter_mut().flatten() {
+                    renumber_subr_calls(tokens, &global_map, Some(local_map))?;
+                }
+            }
+        }
+        // encode before removing, so that unused subroutines that are kept
+        // can be copied from the source
+        let global_subrs =
+            global_map.retain(encode_subrs(&parsed.global_subrs, &self.global_subrs));
+        let mut local_subrs = Vec::with_capacity(self.font_tables.len());
+        for ((font, subrs), maps) in self
+            .font_tables
+            .iter()
+            .zip(&parsed.local_subrs)
+            .zip(&local_maps)
+        {
+            let sources = font.private_dicts();
+            local_subrs.push(
+                subrs
+                    .iter()
+                    .zip(sources)
+                    .zip(maps)
+                    .map(|((subrs, source), map)| {
+                        let source = source.and_then(|private| private.subrs.as_ref());
+                        map.retain(encode_subrs(subrs, source.unwrap_or(&Index1::default())))
+                    })
+                    .collect::<Vec<_>>(),
+            );
+        }
+        self.global_subrs = Index1::from_bytes(global_subrs);
+        for ((font, parsed), local_subrs) in self
+            .font_tables
+            .iter_mut()
+            .zip(parsed.char_strings)
+            .zip(local_subrs)
+        {
+            font.char_strings =
+                Index1::from_bytes(parsed.iter().map(|(_, tokens)| charstring::encode(tokens)));
+            let privates = font.private_dicts_mut().into_iter().flatten();
+            for (private, subrs) in privates.zip(local_subrs) {
+                if let Some(local_subrs) = &mut private.subrs {
+                    *local_subrs = Index1::from_bytes(subrs);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Replaces the charstrings and subroutines with the encoding of the
+    /// given tokens.
+    fn set_parsed_charstrings(&mut self, parsed: ParsedCharstrings) {
+        self.global_subrs =
+            Index1::from_bytes(encode_subrs(&parsed.global_subrs, &self.global_subrs));
+        for ((font, char_strings), local_subrs) in self
+            .font_tables
+            .iter_mut()
+            .zip(parsed.char_strings)
+            .zip(parsed.local_subrs)
+        {
+            font.char_strings = Index1::from_bytes(
+                char_strings
+                    .iter()
+                    .map(|(_, tokens)| charstring::encode(tokens)),
+            );
+            let privates = font.private_dicts_mut().into_iter().flatten();
+            for (private, subrs) in privates.zip(local_subrs) {
+                if let Some(local_subrs) = &mut private.subrs {
+                    *local_subrs = Index1::from_bytes(encode_subrs(&subrs, local_subrs));
+                }
+            }
+        }
+    }
+
+    /// Removes all glyphs except those in `keep_gids` from the font at the
+    /// given index.
+    ///
+    /// The retained glyphs keep their relative order, so new glyph ids are
+    /// assigned in ascending order of the old ones, and `.notdef` is always
+    /// retained as glyph 0. In a name-keyed font, the base and accent glyphs
+    /// of an accented glyph composed with the `seac` form of `endchar` are
+    /// retained too. The charset, and the FDSelect of a CID-keyed
+    /// font, are rebuilt for the new glyph order. A custom encoding is
+    /// removed, since characters are mapped to glyphs by the `cmap` table.
+    /// Subroutines and strings that are no longer used are then removed with
+    /// [`remove_unused_subrs`](Self::remove_unused_subrs) and
+    /// [`repack`](Self::repack).
+    ///
+    /// Returns the new glyph id of each old glyph id, or [`REMOVED_GLYPH`]
+    /// for glyphs that weren't retained, so that references from other
+    /// tables can be updated.
+    pub fn subset(&mut self, font_index: usize, keep_gids: &[u16]) -> Result<Vec<u16>, CffError> {
+        self.check_charstring_types()?;
+        let sids = self.charset_sids(font_index)?;
+        let font = self
+            .font_tables
+            .get_mut(font_index)
+            .ok_or(CffError::FontIndexOutOfBounds(font_index))?;
+        let num_glyphs = font.char_strings.len();
+        if let Some(gid) = keep_gids.iter().find(|gid| **gid as usize >= num_glyphs) {
+            return Err(CffError::GlyphIndexOutOfBounds(*gid));
+        }
+        // the base and accent glyphs of an accented glyph composed with the
+        // seac form of endchar are also retained
+        let global_subrs = self.global_subrs.iter().collect::<Vec<_>>();
+        let local_subrs = match font.private_dict.as_ref().and_then(|p| p.subrs.as_ref()) {
+            Some(subrs) => subrs.iter().collect(),
+            None => Vec::new(),
+        };
+        let mut retained = vec![false; num_glyphs];
+        let mut pending = std::iter::once(0)
+            .chain(keep_gids.iter().copied())
+            .collect::<Vec<_>>();
+        while let Some(gid) = pending.pop() {
+            if std::mem::replace(&mut retained[gid as usize], true) || !font.fd_array.is_empty() {
+                continue;
+            }
+            let charstring = font.char_strings.get(gid as usize).unwrap_or_default();
+            let tokens = Parser::new(&[])
+                .with_subrs(&global_subrs, &local_subrs)
+                .with_inlined_subrs()
+                .parse(charstring)?;
+            let Some((bchar, achar)) = charstring::seac_codes(&tokens) else {
+                continue;
+            };
+            for code in [bchar, achar] {
+                let sid = STANDARD_ENCODING[code as usize] as u16;
+                // the charset omits .notdef
+                if let Some(index) = sids.iter().position(|s| *s == sid) {
+                    pending.push(index as u16 + 1);
+                }
+            }
+        }
+        let kept = (0..num_glyphs)
+            .filter(|gid| retained[*gid])
+            .collect::<Vec<_>>();
+        let mut remap = vec![REMOVED_GLYPH; num_glyphs];
+        for (new_gid, old_gid) in kept.iter().enumerate() {
+            remap[*old_gid] = new_gid as u16;
+        }
+
+        // the charset omits .notdef
+        let kept_sids = kept[1.min(kept.len())..]
+            .iter()
+            .map(|gid| sids.get(gid - 1).copied().unwrap_or_default())
+            .collect::<Vec<_>>();
+        font.charset = Some(CustomCharset::from_sids(&kept_sids));
+        if let Some(fd_select) = &font.fd_select {
+            let fds = kept
+                .iter()
+                .map(|gid| fd_select.font_index(*gid as u32).unwrap_or_default() as u8)
+                .collect::<Vec<_>>();
+            font.fd_select = Some(FdSelect::from_fds(&fds));
+        }
+        font.char_strings = Index1::from_bytes(
+            kept.iter()
+                .map(|gid| font.char_strings.get(*gid).unwrap_or_default()),
+        );
+        font.encoding = None;
+
+        self.remove_unused_subrs()?;
+        self.repack()?;
+        Ok(remap)
+    }
+
+    /// Reorders the glyphs of the font at the given index.
+    ///
+    /// `new_order` gives the old glyph id of each new glyph id, and must
+    /// contain every glyph exactly once, with `.notdef` first. The
+    /// charstrings, the charset, and the FDSelect of a CID-keyed font are
+    /// permuted together. A custom encoding is removed, as in
+    /// [`subset`](Self::subset), since it maps characters to the old glyph
+    /// ids.
+    ///
+    /// Returns the new glyph id of each old glyph id, so that references
+    /// from other tables can be updated, or [`CffError::InvalidGlyphOrder`]
+    /// if `new_order` isn't a valid permutation.
+    pub fn reorder_glyphs(
+        &mut self,
+        font_index: usize,
+        new_order: &[u16],
+    ) -> Result<Vec<u16>, CffError> {
+        let sids = self.charset_sids(font_index)?;
+        let font = self
+            .font_tables
+            .get_mut(font_index)
+            .ok_or(CffError::FontIndexOutOfBounds(font_index))?;
+        let num_glyphs = font.char_strings.len();
+        if let Some(gid) = new_order.iter().find(|gid| **gid as usize >= num_glyphs) {
+            return Err(CffError::GlyphIndexOutOfBounds(*gid));
+        }
+        let mut remap = vec![REMOVED_GLYPH; num_glyphs];
+        for (new_gid, old_gid) in new_order.iter().enumerate() {
+            remap[*old_gid as usize] = new_gid as u16;
+        }
+        if new_order.len() != num_glyphs
+            || remap.contains(&REMOVED_GLYPH)
+            || new_order.first().is_some_and(|gid| *gid != 0)
+        {
+            return Err(CffError::InvalidGlyphOrder);
+        }
+
+        // the charset omits .notdef
+        let new_sids = new_order[1.min(num_glyphs)..]
+            .iter()
+            .map(|gid| sids.get(*gid as usize - 1).copied().unwrap_or_default())
+            .collect::<Vec<_>>();
+        font.charset = Some(CustomCharset::from_sids(&new_sids));
+        if let Some(fd_select) = &font.fd_select {
+            let fds = new_order
+                .iter()
+                .map(|gid| fd_select.font_index(*gid as u32).unwrap_or_default() as u8)
+                .collect::<Vec<_>>();
+            font.fd_select = Some(FdSelect::from_fds(&fds));
+        }
+        font.char_strings = Index1::from_bytes(
+            new_order
+                .iter()
+                .map(|gid| font.char_strings.get(*gid as usize).unwrap_or_default()),
+        );
+        font.encoding = None;
+        Ok(remap)
+    }
+
+    /// Returns the name of the glyph with the given id in a name-keyed font.
+    ///
+    /// Returns [`CffError::CidFontUnsupported`] if the font is CID-keyed,
+    /// since its charset maps glyphs to CIDs rather than names.
+    pub fn glyph_name(&self, font_index: usize, gid: u16) -> Result<String, CffError> {
+        if is_cid_keyed(&dict::parse(self.get_top_dict_bytes(font_index)?)?) {
+            return Err(CffError::CidFontUnsupported);
+        }
+        let sid = match gid {
+            0 => StringId::new(0),
+            _ => self
+                .charset_sids(font_index)?
+                .get(gid as usize - 1)
+                .map(|sid| StringId::new(*sid))
+                .ok_or(CffError::GlyphIndexOutOfBounds(gid))?,
+        };
+        self.get_string_bytes(sid).map(latin1_to_string)
+    }
+
+    /// Renames the glyph with the given id in a name-keyed font.
+    ///
+    /// The name is added to the string INDEX if necessary and the charset is
+    /// rebuilt with [`CustomCharset::from_sids`], replacing any predefined
+    /// charset. The previous name is left in the string INDEX;
+    /// [`repack`](Self::repack) removes it if it is no longer used.
+    ///
+    /// Returns [`CffError::CidFontUnsupported`] if the font is CID-keyed, or
+    /// [`CffError::GlyphIndexOutOfBounds`] for `.notdef`, which has no
+    /// entry in the charset.
+    pub fn rename_glyph(
+        &mut self,
+        font_index: usize,
+        gid: u16,
+        new_name: &str,
+    ) -> Result<(), CffError> {
+        if is_cid_keyed(&dict::parse(self.get_top_dict_bytes(font_index)?)?) {
+            return Err(CffError::CidFontUnsupported);
+        }
+        let mut sids = self.charset_sids(font_index)?;
+        if gid == 0 || gid as usize > sids.len() {
+            return Err(CffError::GlyphIndexOutOfBounds(gid));
+        }
+        sids[gid as usize - 1] = self.add_string(new_name)?.to_u16();
+        let font = &mut self.font_tables[font_index];
+        font.charset = Some(CustomCharset::from_sids(&sids));
+        Ok(())
+    }
+
+    /// Appends a glyph with the given name and charstring to a name-keyed
+    /// font, returning its glyph id.
+    ///
+    /// The name is added to the string INDEX if necessary and the charset is
+    /// rebuilt, as in [`rename_glyph`](Self::rename_glyph). The new glyph
+    /// isn't added to a custom encoding. The first glyph of an empty font is
+    /// `.notdef`, so its name is ignored.
+    ///
+    /// Returns [`CffError::CidFontUnsupported`] if the font is CID-keyed;
+    /// use [`add_cid_glyph`](Self::add_cid_glyph) instead.
+    pub fn add_glyph(
+        &mut self,
+        font_index: usize,
+        name: &str,
+        charstring: Vec<u8>,
+    ) -> Result<u16, CffError> {
+        if is_cid_keyed(&dict::parse(self.get_top_dict_bytes(font_index)?)?) {
+            return Err(CffError::CidFontUnsupported);
+        }
+        let sid = self.add_string(name)?;
+        self.push_glyph(font_index, sid.to_u16(), charstring)
+    }
+
+    /// Appends a glyph with the given CID and charstring to a CID-keyed
+    /// font, returning its glyph id.
+    ///
+    /// The glyph uses the Font DICT at `fd_index` of the FDArray, and the
+    /// charset and FDSelect are rebuilt to include it.
+    ///
+    /// Returns [`CffError::NameKeyedFontUnsupported`] if the font is
+    /// name-keyed, or [`CffError::FdIndexOutOfBounds`] if the Font DICT
+    /// doesn't exist.
+    pub fn add_cid_glyph(
+        &mut self,
+        font_index: usize,
+        cid: u16,
+        fd_index: u8,
+        charstring: Vec<u8>,
+    ) -> Result<u16, CffError> {
+        if !is_cid_keyed(&dict::parse(self.get_top_dict_bytes(font_index)?)?) {
+            return Err(CffError::NameKeyedFontUnsupported);
+        }
+        let font =
+            (self.font_tables.get(font_index)).ok_or(CffError::FontIndexOutOfBounds(font_index))?;
+        if fd_index as usize >= font.fd_array.len() {
+            return Err(CffError::FdIndexOutOfBounds(fd_index as usize));
+        }
+        let fds = (0..font.char_strings.len())
+            .map(|gid| font.fd_index(gid) as u8)
+            .chain([fd_index])
+            .collect::<Vec<_>>();
+        let gid = self.push_glyph(font_index, cid, charstring)?;
+        let font = (self.font_tables.get_mut(font_index))
+            .ok_or(CffError::FontIndexOutOfBounds(font_index))?;
+        font.fd_select = Some(FdSelect::from_fds(&fds));
+        Ok(gid)
+    }
+
+    /// Appends a charstring to the font at the given index, adding the
+    /// string identifier or CID to its charset.
+    fn push_glyph(
+        &mut self,
+        font_index: usize,
+        charset_id: u16,
+        charstring: Vec<u8>,
+    ) -> Result<u16, CffError> {
+        let mut sids = self.charset_sids(font_index)?;
+        let font = (self.font_tables.get_mut(font_index))
+            .ok_or(CffError::FontIndexOutOfBounds(font_index))?;
+        let gid = font.char_strings.len();
+        // glyph ids are limited to 16 bits
+        let gid = u16::try_from(gid)
+            .ok()
+            .filter(|gid| *gid < u16::MAX)
+            .ok_or(CffError::GlyphIndexOutOfBounds(u16::MAX))?;
+        // the charset omits .notdef
+        if gid > 0 {
+            sids.push(charset_id);
+        }
+        font.charset = Some(CustomCharset::from_sids(&sids));
+        font.char_strings.push(&charstring);
+        Ok(gid)
+    }
+
+    /// Returns the string identifier (or CID) of each glyph after `.notdef`
+    /// in the charset of the font at the given index.
+    ///
+    /// Predefined charsets are resolved.
+    fn charset_sids(&self, font_index: usize) -> Result<Vec<u16>, CffError> {
+        let font = self
+            .font_tables
+            .get(font_index)
+            .ok_or(CffError::FontIndexOutOfBounds(font_index))?;
+        if let Some(charset) = &font.charset {
+            return Ok(charset_sids(charset));
+        }
+        let top_dict = dict::parse(self.get_top_dict_bytes(font_index)?)?;
+        let num_glyphs = font.char_strings.len();
+        let predefined = offset_operand(&top_dict, DictKey::Charset).unwrap_or_default();
+        let charset = read_fonts::tables::postscript::Charset::new(
+            FontData::new(&[]),
+            predefined,
+            num_glyphs as u32,
+        )?;
+        Ok((1..num_glyphs as u32)
+            .map(|gid| charset.string_id(GlyphId::new(gid)).map(StringId::to_u16))
+            .collect::<Result<_, _>>()?)
+    }
+
+    /// Converts the first font in the font set from name-keyed to CID-keyed,
+    /// assigning the CID at each index of `cid_map` to the glyph with that
+    /// id.
+    ///
+    /// The charset is rebuilt from the CIDs, and an `ROS` of
+    /// `Adobe-Identity-0` is added to the Top DICT, with a `CIDCount` that
+    /// covers the largest CID. The Private DICT moves to a single Font DICT,
+    /// named after the font, which the new FDSelect assigns to every glyph.
+    /// CID-keyed fonts have no encoding, so any encoding is removed. The
+    /// glyph names are left in the string INDEX; [`repack`](Self::repack)
+    /// removes them.
+    ///
+    /// Returns [`CffError::CidFontUnsupported`] if the font is already
+    /// CID-keyed, or [`CffError::InvalidCharset`] if `cid_map` doesn't have
+    /// a distinct CID for each glyph, with CID 0 for `.notdef`.
+    pub fn to_cid_keyed(&mut self, cid_map: &[u16]) -> Result<(), CffError> {
+        if is_cid_keyed(&dict::parse(self.get_top_dict_bytes(0)?)?) {
+            return Err(CffError::CidFontUnsupported);
+        }
+        let num_glyphs = (self.font_tables.first())
+            .ok_or(CffError::FontIndexOutOfBounds(0))?
+            .char_strings
+            .len();
+        let mut seen = BTreeSet::new();
+        if cid_map.len() != num_glyphs
+            || cid_map.first().is_some_and(|cid| *cid != 0)
+            || !cid_map.iter().all(|cid| seen.insert(*cid))
+        {
+            return Err(CffError::InvalidCharset);
+        }
+
+        // the ROS must be the first entry of the Top DICT, and the CIDCount
+        // replaces any existing one
+        let registry = self.add_string("Adobe")?;
+        let ordering = self.add_string("Identity")?;
+        let ros = Entry::new(
+            DictKey::Ros,
+            vec![
+                Operand::Integer(registry.to_u16() as i32),
+                Operand::Integer(ordering.to_u16() as i32),
+                Operand::Integer(0),
+            ],
+        );
+        let cid_count = Entry::new(
+            DictKey::CidCount,
+            vec![Operand::Integer(
+                cid_map.iter().max().map_or(0, |cid| *cid as i32 + 1),
+            )],
+        );
+        let mut entries = vec![ros];
+        if !cid_count.is_top_dict_default() {
+            entries.push(cid_count);
+        }
+        let mut top_dict = dict::encode(&entries);
+        top_dict.extend(dict::rewrite(
+            self.get_top_dict_bytes(0)?,
+            &[(DictKey::Encoding, None), (DictKey::CidCount, None)],
+        ));
+        let font_dict = match self.names.get(0) {
+            Some(name) => {
+                let name = self.add_string(&latin1_to_string(name))?;
+                dict::encode(&[Entry::new(
+                    DictKey::FontName,
+                    vec![Operand::Integer(name.to_u16() as i32)],
+                )])
+            }
+            None => Vec::new(),
+        };
+        self.replace_top_dict(0, &top_dict);
+
+        let font = (self.font_tables.first_mut()).ok_or(CffError::FontIndexOutOfBounds(0))?;
+        font.charset = Some(CustomCharset::from_sids(
+            cid_map.get(1..).unwrap_or_default(),
+        ));
+        font.encoding = None;
+        font.fd_array = vec![FontDict {
+            dict: font_dict,
+            private_dict: font.private_dict.take(),
+        }];
+        font.fd_select = Some(FdSelect::from_fds(&vec![0; num_glyphs]));
+        Ok(())
+    }
+
+    /// Converts the first font in the font set from CID-keyed to
+    /// name-keyed, giving the glyph with each id the name at that index of
+    /// `names`.
+    ///
+    /// The names are added to the string INDEX as necessary and the charset
+    /// is rebuilt from them. The `ROS` and the other operators specific to
+    /// CID-keyed fonts are removed from the Top DICT, and the Private DICT of
+    /// the only Font DICT becomes the Private DICT of the font; the other
+    /// entries of the Font DICT are discarded along with the FDArray and
+    /// FDSelect.
+    ///
+    /// Returns [`CffError::NameKeyedFontUnsupported`] if the font is already
+    /// name-keyed, [`CffError::InvalidCharset`] if `names` doesn't have a
+    /// distinct name for each glyph, starting with `.notdef`, or
+    /// [`CffError::MultipleFontDicts`] if the font has more than one Font
+    /// DICT.
+    pub fn to_name_keyed(&mut self, names: &[String]) -> Result<(), CffError> {
+        if !is_cid_keyed(&dict::parse(self.get_top_dict_bytes(0)?)?) {
+            return Err(CffError::NameKeyedFontUnsupported);
+        }
+        let font = (self.font_tables.first()).ok_or(CffError::FontIndexOutOfBounds(0))?;
+        if font.fd_array.len() > 1 {
+            return Err(CffError::MultipleFontDicts);
+        }
+        let num_glyphs = font.char_strings.len();
+        let mut seen = BTreeSet::new();
+        if names.len() != num_glyphs
+            || names.first().is_some_and(|name| name != ".notdef")
+            || !names.iter().all(|name| seen.insert(name.as_str()))
+        {
+            return Err(CffError::InvalidCharset);
+        }
+
+        // the charset omits .notdef
+        let sids = names
+            .iter()
+            .skip(1)
+            .map(|name| self.add_string(name).map(StringId::to_u16))
+            .collect::<Result<Vec<_>, _>>()?;
+        const CID_KEYS: [DictKey; 8] = [
+            DictKey::Ros,
+            DictKey::CidFontVersion,
+            DictKey::CidFontRevision,
+            DictKey::CidFontType,
+            DictKey::CidCount,
+            DictKey::UidBase,
+            DictKey::FdArrayOffset,
+            DictKey::FdSelectOffset,
+        ];
+        let top_dict = dict::rewrite(
+            self.get_top_dict_bytes(0)?,
+            &CID_KEYS.map(|key| (key, None)),
+        );
+        self.replace_top_dict(0, &top_dict);
+
+        let font = (self.font_tables.first_mut()).ok_or(CffError::FontIndexOutOfBounds(0))?;
+        font.charset = Some(CustomCharset::from_sids(&sids));
+        font.private_dict = font
+            .fd_array
+            .pop()
+            .and_then(|font_dict| font_dict.private_dict);
+        font.fd_select = None;
+        Ok(())
+    }
+
+    /// Removes the `Version`, `Notice`, `Copyright`, `FullName`,
+    /// `FamilyName` and `Weight` entries from the Top DICT of every font,
+    /// for example to anonymize a font.
+    ///
+    /// The strings that are no longer used are then removed with
+    /// [`repack`](Self::repack). The names in the Name INDEX are kept.
+    pub fn clear_metadata_strings(&mut self) -> Result<(), CffError> {
+        const METADATA_KEYS: [DictKey; 6] = [
+            DictKey::Version,
+            DictKey::Notice,
+            DictKey::Copyright,
+            DictKey::FullName,
+            DictKey::FamilyName,
+            DictKey::Weight,
+        ];
+        let updates = METADATA_KEYS.map(|key| (key, None));
+        let mut top_dicts = Vec::with_capacity(self.top_dicts.len());
+        for top_dict in self.top_dicts.iter() {
+            // check the source, since rewriting malformed data has no effect
+            dict::parse(top_dict)?;
+            top_dicts.push(dict::rewrite(top_dict, &updates));
+        }
+        self.top_dicts = Index1::from_bytes(top_dicts);
+        self.repack()
+    }
+
+    /// Removes unused and duplicate strings and minimizes the offset size
+    /// of every INDEX.
+    ///
+    /// Custom strings that duplicate a standard string or an earlier custom
+    /// string are replaced by the existing identifier. The order of the
+    /// remaining strings, glyphs and subroutines is unchanged.
+    pub fn repack(&mut self) -> Result<(), CffError> {
+        // find the custom strings that are in use
+        let mut used = vec![false; self.strings.len()];
+        let mut mark = |sid: StringId| {
+            if let Some(flag) = sid.custom_index().and_then(|ix| used.get_mut(ix)) {
+                *flag = true;
+            }
+        };
+        for (i, font) in self.font_tables.iter().enumerate() {
+            self.referenced_string_ids(i)?
+                .into_iter()
+                .for_each(&mut mark);
+            if !is_cid_keyed(&dict::parse(self.get_top_dict_bytes(i)?)?) {
+                if let Some(charset) = &font.charset {
+                    charset_sids(charset)
+                        .into_iter()
+                        .for_each(|sid| mark(StringId::new(sid)));
+                }
+            }
+        }
+
+        // build the new string INDEX and the mapping from old identifiers
+        let mut strings: Vec<&[u8]> = Vec::new();
+        let mut map = Vec::with_capacity(self.strings.len());
+        for (string, used) in self.strings.iter().zip(used) {
+            let standard = read_fonts::tables::postscript::STANDARD_STRINGS
+                .iter()
+                .position(|standard| standard.as_bytes() == string);
+            let new_index = match (used, standard) {
+                (false, _) => 0,
+                (true, Some(index)) => index,
+                (true, None) => match strings.iter().position(|custom| *custom == string) {
+                    Some(index) => index + STANDARD_STRING_COUNT,
+                    None => {
+                        strings.push(string);
+                        strings.len() - 1 + STANDARD_STRING_COUNT
+                    }
+                },
+            };
+            map.push(StringId::new(new_index as u16));
+        }
+        let remap = |sid: StringId| {
+            sid.custom_index()
+                .and_then(|ix| map.get(ix).copied())
+                .unwrap_or(sid)
+        };
+
+        let mut top_dicts = Vec::with_capacity(self.top_dicts.len());
+        for (i, top_dict) in self.top_dicts.iter().enumerate() {
+            top_dicts.push(remap_font_strings(
+                top_dict,
+                self.font_tables.get_mut(i),
+                remap,
+            )?);
+        }
+        self.strings = Index1::with_capacity_from(&strings);
+        self.top_dicts = Index1::from_bytes(top_dicts);
+
+        let repack_index = |index: &mut Index1| *index = Index1::from_bytes(index.iter());
+        repack_index(&mut self.names);
+        repack_index(&mut self.global_subrs);
+        for font in &mut self.font_tables {
+            repack_index(&mut font.char_strings);
+            let private_dicts = font.private_dict.iter_mut().chain(
+                font.fd_array
+                    .iter_mut()
+                    .filter_map(|font_dict| font_dict.private_dict.as_mut()),
+            );
+            for subrs in private_dicts.filter_map(|private| private.subrs.as_mut()) {
+                repack_index(subrs);
+            }
+        }
+        Ok(())
+    }
+
+    /// Combines two tables into a single font set, with the fonts of `a`
+    /// followed by those of `b`.
+    ///
+    /// The custom strings of `b` are added to the string INDEX of `a`,
+    /// reusing strings that are already present, and the string identifiers
+    /// in the Top DICTs, Font DICTs and charsets of `b` are updated. The
+    /// global subroutines of `b` are appended to those of `a`, and the
+    /// `callgsubr` operands of both tables are updated for the new numbering
+    /// and bias. The header and writing options are taken from `a`.
+    ///
+    /// Returns [`CffError::IncompatibleVariationStores`] if the tables have
+    /// different variation stores, since a font set shares a single store.
+    pub fn merge_fonts(mut a: Cff, mut b: Cff) -> Result<Cff, CffError> {
+        if a.variation_store != b.variation_store {
+            return Err(CffError::IncompatibleVariationStores);
+        }
+        let (a_count, b_count) = (a.global_subrs.len(), b.global_subrs.len());
+        if a_count != 0 && b_count != 0 {
+            let count = a_count + b_count;
+            a.renumber_global_subr_calls(&SubrMap::shift(a_count, 0, count), true)?;
+            b.renumber_global_subr_calls(&SubrMap::shift(b_count, a_count, count), true)?;
+        }
+        for subr in b.global_subrs.iter() {
+            a.global_subrs.push(subr);
+        }
+
+        let mut map = Vec::with_capacity(b.strings.len());
+        for string in b.strings.iter() {
+            let existing = a.strings.iter().position(|existing| existing == string);
+            let index = existing.unwrap_or_else(|| {
+                a.strings.push(string);
+                a.strings.len() - 1
+            });
+            map.push(StringId::new((index + STANDARD_STRING_COUNT) as u16));
+        }
+        let remap = |sid: StringId| {
+            sid.custom_index()
+                .and_then(|ix| map.get(ix).copied())
+                .unwrap_or(sid)
+        };
+
+        let mut b_fonts = b.font_tables.into_iter();
+        for (name, top_dict) in b.names.iter().zip(b.top_dicts.iter()) {
+            let mut font = b_fonts.next();
+            a.names.push(name);
+            a.top_dicts
+                .push(&remap_font_strings(top_dict, font.as_mut(), remap)?);
+            a.font_tables.extend(font);
+        }
+        Ok(a)
+    }
+}
+
+impl<I: CffIndex> Cff<I> {
+    /// Returns the `(major, minor)` format version of the table, as given
+    /// by the header.
+    ///
+    /// This type represents version 1 of the format, so a major version
+    /// other than 1 is reported when the table is validated. CFF2 tables
+    /// are represented by [`Cff2`](super::cff2::Cff2).
+    pub fn format_version(&self) -> (u8, u8) {
+        (self.header.major, self.header.minor)
+    }
+
+    /// Returns the bytes following the four byte header, up to the header
+    /// size given by `hdrSize`.
+    ///
+    /// Some fonts store non-standard data here. The bytes are kept when the
+    /// table is written.
+    pub fn header_extra_bytes(&self) -> &[u8] {
+        &self.header._padding
+    }
+
+    /// Returns the number of global subroutines and the bias that is added
+    /// to the operand of a `callgsubr` operator to give the subroutine
+    /// index.
+    pub fn global_subr_info(&self) -> (usize, i32) {
+        let count = self.global_subrs.len();
+        (count, charstring::subr_bias(count))
+    }
+
+    /// Returns the type of the charstrings of the font at the given index,
+    /// as given by the `CharstringType` operator of its Top DICT.
+    ///
+    /// The type is 2 if the operator is absent. Only Type 2 charstrings can
+    /// be interpreted or modified; other operations that process
+    /// charstrings return [`CffError::UnsupportedCharstringType`] for any
+    /// other type.
+    pub fn charstring_type(&self, font_index: usize) -> Result<i32, CffError> {
+        let top_dict = self
+            .top_dicts
+            .get(font_index)
+            .ok_or(CffError::FontIndexOutOfBounds(font_index))?;
+        Ok(dict::parse(top_dict)?
+            .iter()
+            .find(|entry| entry.key == DictKey::CharstringType)
+            .and_then(|entry| entry.operands.first())
+            .map_or(2, |operand| operand.to_f64() as i32))
+    }
+
+    /// Returns an error if any font in the font set doesn't use Type 2
+    /// charstrings.
+    fn check_charstring_types(&self) -> Result<(), CffError> {
+        for font_index in 0..self.top_dicts.len() {
+            match self.charstring_type(font_index)? {
+                2 => (),
+                other => return Err(CffError::UnsupportedCharstringType(other)),
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the byte ranges of the header, the INDEXes and the subtables
+    /// of each font in the table as it would be written.
+    ///
+    /// The ranges are contiguous and cover the entire compiled table, except
+    /// for any padding added for [`Cff::pad_to_four_bytes`].
+    pub fn layout(&self) -> CffLayout {
+        self.compile_with_layout().1
+    }
+
+    /// Returns the length of the table as it would be written by
+    /// [`to_bytes`](Cff::to_bytes), without encoding it.
+    ///
+    /// The INDEXes are sized from their offsets, so only the DICTs and the
+    /// small subtables of each font are encoded.
+    pub fn serialized_len(&self) -> usize {
+        let store_update = (
+            DictKey::VariationStoreOffset,
+            self.variation_store.as_ref().map(|_| encoded_offset(0)),
+        );
+        let mut top_dicts = Vec::with_capacity(self.top_dicts.len());
+        let mut len = 0;
+        for (i, top_dict) in self.top_dicts.iter().enumerate() {
+            match self.font_tables.get(i) {
+                Some(font) => {
+                    let (mut updates, font_len) =
+                        font.compiled_len(top_dict, self.preserve_offset_sizes);
+                    updates.push(store_update.clone());
+                    top_dicts.push(dict::rewrite(top_dict, &updates));
+                    len += font_len;
+                }
+                None => {
+                    top_dicts.push(dict::rewrite(top_dict, std::slice::from_ref(&store_update)))
+                }
+            }
+        }
+        let mut top_dicts = Index1::from_bytes(top_dicts);
+        if self.preserve_offset_sizes {
+            top_dicts.widen_offsets(self.top_dicts.off_size());
+        }
+        len += 4
+            + self.header._padding.len()
+            + self.names.byte_len()
+            + top_dicts.byte_len()
+            + self.strings.byte_len()
+            + self.global_subrs.byte_len()
+            + self
+                .variation_store
+                .as_ref()
+                .map_or(0, |store| store.len() + 2);
+        if self.pad_to_four_bytes {
+            len = len.next_multiple_of(4);
+        }
+        len
+    }
+
+    /// Returns the glyph id of a character, as mapped by the given `cmap`
+    /// table of the font containing this table.
+    ///
+    /// Returns `None` if the character isn't mapped, or if the glyph isn't
+    /// in the first font of the font set, which is the only font of a CFF
+    /// table in an OpenType font.
+    pub fn glyph_for_char(&self, ch: char, cmap: &read_fonts::tables::cmap::Cmap) -> Option<u16> {
+        let gid = u16::try_from(cmap.map_codepoint(ch)?.to_u32()).ok()?;
+        let num_glyphs = self.font_tables.first()?.char_strings.len();
+        (usize::from(gid) < num_glyphs).then_some(gid)
+    }
+
+    /// Interprets the charstring of a glyph in the font at the given index,
+    /// sending the resulting path to `pen`.
+    ///
+    /// Subroutine calls are resolved using the global subroutines and the
+    /// local subroutines of the Private DICT that applies to the glyph. A
+    /// `seac`-style `endchar` draws the base and accent glyphs.
+    ///
+    /// Returns [`CffError::InvalidCharstring`] if the charstring can't be
+    /// interpreted.
+    pub fn outline(
+        &self,
+        font_index: usize,
+        gid: u16,
+        pen: &mut impl OutlinePen,
+    ) -> Result<(), CffError> {
+        OutlineData::new(self, font_index)?.draw(gid, pen)
+    }
+
+    /// Interprets the charstring of a glyph of the font containing this
+    /// table, sending the resulting path to `pen`.
+    ///
+    /// The glyph ids of an OpenType font refer to the first font of the font
+    /// set, which is its only font. The table has no character mapping, so
+    /// the glyph for a character is found with the `cmap` table of the font,
+    /// for example with [`glyph_for_char`](Self::glyph_for_char).
+    ///
+    /// See [`outline`](Self::outline) for details.
+    pub fn outline_for_gid(&self, gid: u16, pen: &mut impl OutlinePen) -> Result<(), CffError> {
+        self.outline(0, gid, pen)
+    }
+
+    /// Returns a textual listing of the charstring of a glyph in the font at
+    /// the given index, for debugging.
+    ///
+    /// Each line contains an operator preceded by its operands, such as
+    /// `100 200 rmoveto`. Subroutine calls are not followed, and their
+    /// operands are written as subroutine indices without the bias. See
+    /// [`charstring::disassemble`] for details.
+    pub fn disassemble_charstring(&self, font_index: usize, gid: u16) -> Result<String, CffError> {
+        match self.charstring_type(font_index)? {
+            2 => (),
+            other => return Err(CffError::UnsupportedCharstringType(other)),
+        }
+        let font = self
+            .font_tables
+            .get(font_index)
+            .ok_or(CffError::FontIndexOutOfBounds(font_index))?;
+        let charstring = font
+            .char_strings
+            .get(gid as usize)
+            .ok_or(CffError::GlyphIndexOutOfBounds(gid))?;
+        let global_subrs = self.global_subrs.iter().collect::<Vec<_>>();
+        let local_subrs = font
+            .private_dicts()
+            .get(font.fd_index(gid as usize))
+            .copied()
+            .flatten()
+            .and_then(|private| private.subrs.as_ref())
+            .map(|subrs| subrs.iter().collect::<Vec<_>>())
+            .unwrap_or_default();
+        // the subroutines are followed to count the stem hints they declare
+        let tokens = Parser::new(&[])
+            .with_subrs(&global_subrs, &local_subrs)
+            .parse(charstring)?;
+        Ok(charstring::disassemble(
+            &tokens,
+            global_subrs.len(),
+            local_subrs.len(),
+        ))
+    }
+
+    /// Checks that the subroutines called by each charstring are well formed.
+    ///
+    /// Every charstring is interpreted far enough to follow its subroutine
+    /// calls. An error is returned if a call has no subroutine number or
+    /// refers to a missing subroutine, if a subroutine is truncated, or if
+    /// it doesn't end with `return` or `endchar`, either directly or
+    /// through a call to a subroutine that does. Each charstring is then
+    /// checked with its subroutines inlined, and an error is returned if an
+    /// operator takes more operands than are on the stack.
+    ///
+    /// Subroutines that are never called are not checked, since the number
+    /// of stem hints needed to parse them is unknown.
+    pub fn validate_subrs(&self) -> Result<(), CffError> {
+        let parsed = self.parse_charstrings()?;
+        let local_subrs = parsed
+            .local_subrs
+            .iter()
+            .flatten()
+            .map(Vec::as_slice)
+            .collect::<Vec<_>>();
+        let global_terminate = parsed
+            .global_subrs
+            .iter()
+            .flatten()
+            .all(|subr| charstring::subr_terminates(subr, &parsed.global_subrs, &local_subrs));
+        let local_terminate = local_subrs.iter().all(|subrs| {
+            subrs
+                .iter()
+                .flatten()
+                .all(|subr| charstring::subr_terminates(subr, &parsed.global_subrs, &[subrs]))
+        });
+        if !(global_terminate && local_terminate) {
+            return Err(CffError::InvalidCharstring);
+        }
+
+        // operands may be pushed by a subroutine and consumed by its caller
+        let global_subrs = self.global_subrs.iter().collect::<Vec<_>>();
+        for font in &self.font_tables {
+            let local_subrs = font
+                .private_dicts()
+                .into_iter()
+                .map(|private| match private.and_then(|p| p.subrs.as_ref()) {
+                    Some(subrs) => subrs.iter().collect(),
+                    None => Vec::new(),
+                })
+                .collect::<Vec<Vec<_>>>();
+            for (gid, charstring) in font.char_strings.iter().enumerate() {
+                let subrs = local_subrs
+                    .get(font.fd_index(gid))
+                    .map_or(&[][..], Vec::as_slice);
+                let tokens = Parser::new(&[])
+                    .with_subrs(&global_subrs, subrs)
+                    .with_inlined_subrs()
+                    .parse(charstring)?;
+                if charstring::stack_underflow(&tokens).is_some() {
+                    return Err(CffError::InvalidCharstring);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that the charstrings can be interpreted.
+    ///
+    /// Every operator with an invalid number of operands is reported, along
+    /// with any error found by [`validate_subrs`](Self::validate_subrs).
+    /// These checks interpret every charstring, so unlike the structural
+    /// checks of [`Validate`] they aren't run when the table is written.
+    /// Charstrings of types other than 2 aren't checked.
+    pub fn validate_charstrings(&self) -> Result<(), crate::validate::ValidationReport> {
+        CharstringChecks(self).validate()
+    }
+
+    /// Parses every charstring and every subroutine that is called.
+    ///
+    /// Calls are followed to determine the number of stem hints, which is
+    /// needed to find the end of each `hintmask` and `cntrmask`.
+    fn parse_charstrings(&self) -> Result<ParsedCharstrings, CffError> {
+        self.check_charstring_types()?;
+        let global_subrs = self.global_subrs.iter().collect::<Vec<_>>();
+        let local_subrs = self
+            .font_tables
+            .iter()
+            .map(|font| {
+                font.private_dicts()
+                    .into_iter()
+                    .map(|private| match private.and_then(|p| p.subrs.as_ref()) {
+                        Some(subrs) => subrs.iter().collect(),
+                        None => Vec::new(),
+                    })
+                    .collect::<Vec<Vec<_>>>()
+            })
+            .collect::<Vec<_>>();
+        // the stem count and local subroutines at the first call to each
+        // subroutine, which are needed to parse it
+        let mut global_calls = vec![None; global_subrs.len()];
+        let mut local_calls = local_subrs
+            .iter()
+            .map(|font| {
+                font.iter()
+                    .map(|subrs| vec![None; subrs.len()])
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let mut char_strings = Vec::with_capacity(self.font_tables.len());
+        for (font_index, font) in self.font_tables.iter().enumerate() {
+            let mut parsed = Vec::with_capacity(font.char_strings.len());
+            for (gid, charstring) in font.char_strings.iter().enumerate() {
+                let fd = font.fd_index(gid);
+                let subrs = local_subrs[font_index]
+                    .get(fd)
+                    .ok_or(CffError::FdIndexOutOfBounds(fd))?;
+                let mut parser = Parser::new(&[]).with_subrs(&global_subrs, subrs);
+                let tokens = parser.parse(charstring)?;
+                for call in parser.subr_calls() {
+                    let first_call = match call.global {
+                        true => &mut global_calls[call.index],
+                        false => &mut local_calls[font_index][fd][call.index],
+                    };
+                    first_call.get_or_insert((call.stem_count, subrs.as_slice()));
+                }
+                parsed.push((fd, tokens));
+            }
+            char_strings.push(parsed);
+        }
+        let global_subrs_parsed = parse_subrs(&global_subrs, &global_calls, &global_subrs)?;
+        let global_calls_local = global_subrs_parsed
+            .iter()
+            .flatten()
+            .any(|tokens| tokens.contains(&Token::Operator(charstring::Operator::CallSubr)));
+        let local_subrs = local_subrs
+            .iter()
+            .zip(&local_calls)
+            .map(|(font, calls)| {
+                font.iter()
+                    .zip(calls)
+                    .map(|(subrs, calls)| parse_subrs(subrs, calls, &global_subrs))
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(ParsedCharstrings {
+            global_subrs: global_subrs_parsed,
+            char_strings,
+            local_subrs,
+            global_calls_local,
+        })
+    }
+
+    fn compile(&self) -> Vec<u8> {
+        self.compile_with_layout().0
+    }
+
+    /// Compiles the table, returning the data and the location of each
+    /// structure within it.
+    fn compile_with_layout(&self) -> (Vec<u8>, CffLayout) {
+        // Offsets in DICTs are always encoded with the same width, so the
+        // size of each DICT doesn't depend on the values of the offsets.
+        // This lets us do a first pass with placeholder offsets to find the
+        // start of each font's subtables.
+        let (_, layout) = self.compile_with_starts(0, &vec![0; self.font_tables.len()]);
+        let starts = layout
+            .fonts
+            .iter()
+            .map(|font| font.start)
+            .collect::<Vec<_>>();
+        let (mut data, final_layout) =
+            self.compile_with_starts(layout.variation_store.start, &starts);
+        debug_assert_eq!(layout, final_layout);
+        // the header's offset size covers every offset from the start of
+        // the table
+        data[3] = self.header.off_size.max(min_offset_size(data.len()));
+        if self.pad_to_four_bytes {
+            data.resize(data.len().next_multiple_of(4), 0);
+        }
+        (data, final_layout)
+    }
+
+    /// Compiles the table, assuming that the variation store and each
+    /// font's subtables start at the given positions.
+    ///
+    /// Returns the table data and the actual location of each structure,
+    /// including the subtables of each font.
+    fn compile_with_starts(&self, store_start: usize, starts: &[usize]) -> (Vec<u8>, CffLayout) {
+        let store_update = (
+            DictKey::VariationStoreOffset,
+            self.variation_store
+                .as_ref()
+                .map(|_| encoded_offset(store_start)),
+        );
+        let mut top_dicts = Vec::with_capacity(self.top_dicts.len());
+        let mut subtables = Vec::with_capacity(self.font_tables.len());
+        for (i, top_dict) in self.top_dicts.iter().enumerate() {
+            match self.font_tables.get(i) {
+                Some(font) => {
+                    let (mut updates, data) =
+                        font.compile(top_dict, starts[i], self.preserve_offset_sizes);
+                    updates.push(store_update.clone());
+                    top_dicts.push(dict::rewrite(top_dict, &updates));
+                    subtables.push(data);
+                }
+                None => {
+                    top_dicts.push(dict::rewrite(top_dict, std::slice::from_ref(&store_update)))
+                }
+            }
+        }
+        let mut top_dicts = Index1::from_bytes(top_dicts);
+        if self.preserve_offset_sizes {
+            top_dicts.widen_offsets(self.top_dicts.off_size());
+        }
+        let (major, minor) = self.format_version();
+        let mut data = vec![major, minor, self.header.hdr_size, self.header.off_size];
+        data.extend_from_slice(&self.header._padding);
+        let mut extend = |bytes: Vec<u8>| {
+            let start = data.len();
+            data.extend(bytes);
+            start..data.len()
+        };
+        let layout = CffLayout {
+            header: 0..4 + self.header._padding.len(),
+            names: extend(write_to_vec(&self.names)),
+            top_dicts: extend(write_to_vec(&top_dicts)),
+            strings: extend(write_to_vec(&self.strings)),
+            global_subrs: extend(write_to_vec(&self.global_subrs)),
+            variation_store: extend(self.encoded_variation_store()),
+            fonts: subtables.into_iter().map(extend).collect(),
+        };
+        (data, layout)
+    }
+}
+
+impl<I> Cff<I> {
+    /// Returns the variation store preceded by its length, or no data if
+    /// there is no variation store.
+    fn encoded_variation_store(&self) -> Vec<u8> {
+        let Some(store) = &self.variation_store else {
+            return Vec::new();
+        };
+        let mut data = (store.len() as u16).to_be_bytes().to_vec();
+        data.extend_from_slice(store);
+        data
+    }
+}
+
+impl<I> FontTables<I> {
+    /// Returns the index of the Font DICT selected for the given glyph.
+    ///
+    /// Glyphs not covered by the FDSelect use the first Font DICT.
+    fn fd_index(&self, gid: usize) -> usize {
+        self.fd_select
+            .as_ref()
+            .and_then(|fd_select| fd_select.font_index(gid as u32))
+            .unwrap_or_default()
+    }
+
+    /// Returns the data of the custom encoding, if any.
+    fn custom_encoding(&self) -> Option<&[u8]> {
+        self.encoding
+            .as_deref()
+            .filter(|encoding| !encoding.is_empty())
+    }
+
+    /// Returns the Private DICT of a name-keyed font, or the Private DICT of
+    /// each Font DICT of a CID-keyed font.
+    fn private_dicts(&self) -> Vec<Option<&PrivateDict<I>>> {
+        if self.fd_array.is_empty() {
+            return vec![self.private_dict.as_ref()];
+        }
+        self.fd_array
+            .iter()
+            .map(|font_dict| font_dict.private_dict.as_ref())
+            .collect()
+    }
+
+    /// Mutable access to the Private DICTs, in the order of
+    /// [`private_dicts`](Self::private_dicts).
+    fn private_dicts_mut(&mut self) -> Vec<Option<&mut PrivateDict<I>>> {
+        if self.fd_array.is_empty() {
+            return vec![self.private_dict.as_mut()];
+        }
+        self.fd_array
+            .iter_mut()
+            .map(|font_dict| font_dict.private_dict.as_mut())
+            .collect()
+    }
+
+    /// Reads the subtables referenced by the given Top DICT.
+    fn read<'a>(data: FontData<'a>, top_dict: &[Entry]) -> Result<Self, ReadError>
+    where
+        I: FontRead<'a> + CffIndex + Default,
+    {
+        let mut font = FontTables::default();
+        if let Some(offset) = offset_operand(top_dict, DictKey::CharstringsOffset) {
+            font.char_strings = I::read(split_off(data, offset)?)?;
+        }
+        let num_glyphs = font.char_strings.len();
+        // values 0-2 select predefined charsets
+        if let Some(offset) = offset_operand(top_dict, DictKey::Charset).filter(|x| *x > 2) {
+            let charset: CustomCharset =
+                read_fonts::tables::postscript::CustomCharset::read(split_off(data, offset)?)?
+                    .to_owned_table();
+            font.charset = Some(trim_charset(charset, num_glyphs));
+        }
+        // values 0-1 select predefined encodings
+        if let Some(offset) = offset_operand(top_dict, DictKey::Encoding).filter(|x| *x > 1) {
+            let encoding = split_off(data, offset)?.as_bytes();
+            let len = encoding_len(encoding).ok_or(ReadError::OutOfBounds)?;
+            font.encoding = Some(encoding[..len].to_vec());
+        }
+        if let Some(private) = private_dict_range(top_dict) {
+            font.private_dict = Some(PrivateDict::read(data, private)?);
+        }
+        if let Some(offset) = offset_operand(top_dict, DictKey::FdArrayOffset) {
+            font.fd_array = FontDict::read_fd_array(data, offset, false)?;
+            font.fd_array_off_size =
+                read_fonts::tables::postscript::Index1::read(split_off(data, offset)?)?.off_size();
+        }
+        if let Some(offset) = offset_operand(top_dict, DictKey::FdSelectOffset) {
+            let mut fd_select: FdSelect =
+                read_fonts::tables::postscript::FdSelect::read(split_off(data, offset)?)?
+                    .to_owned_table();
+            // format 0 has no explicit length and extends to the end of the table
+            if let FdSelect::Format0(format0) = &mut fd_select {
+                format0.fds.truncate(num_glyphs);
+            }
+            font.fd_select = Some(fd_select);
+        }
+        Ok(font)
+    }
+
+    /// Compiles the subtables, assuming they begin at `start`.
+    ///
+    /// Returns the updates to apply to the Top DICT and the subtable data.
+    ///
+    /// If `preserve_offset_sizes` is `true`, the FDArray INDEX is written
+    /// with at least the offset size of the source table.
+    fn compile(
+        &self,
+        top_dict: &[u8],
+        start: usize,
+        preserve_offset_sizes: bool,
+    ) -> (Vec<dict::Update>, Vec<u8>)
+    where
+        I: CffIndex,
+    {
+        let mut updates = Vec::new();
+        let mut data = Vec::new();
+        let entries = dict::parse(top_dict).unwrap_or_default();
+        let offset = |data: &Vec<u8>| Some(encoded_offset(start + data.len()));
+        let empty_private = PrivateDict {
+            dict: Vec::new(),
+            subrs: None,
+        };
+
+        match &self.charset {
+            Some(charset) => {
+                updates.push((DictKey::Charset, offset(&data)));
+                data.extend(write_to_vec(charset));
+            }
+            // remove any stale offset to a custom charset
+            None if offset_operand(&entries, DictKey::Charset).is_some_and(|x| x > 2) => {
+                updates.push((DictKey::Charset, None))
+            }
+            None => (),
+        }
+        match self.custom_encoding() {
+            Some(encoding) => {
+                updates.push((DictKey::Encoding, offset(&data)));
+                data.extend_from_slice(encoding);
+            }
+            // remove any stale offset to a custom encoding, and the default
+            // operand of the Standard encoding
+            None if offset_operand(&entries, DictKey::Encoding).is_some_and(|x| x != 1) => {
+                updates.push((DictKey::Encoding, None))
+            }
+            None => (),
+        }
+        match &self.fd_select {
+            Some(fd_select) => {
+                updates.push((DictKey::FdSelectOffset, offset(&data)));
+                let fd_select = fd_select.compact(self.char_strings.len());
+                data.extend(write_to_vec(&fd_select));
+            }
+            None => updates.push((DictKey::FdSelectOffset, None)),
+        }
+        updates.push((DictKey::CharstringsOffset, offset(&data)));
+        data.extend(write_to_vec(&self.char_strings));
+
+        if self.fd_array.is_empty() {
+            updates.push((DictKey::FdArrayOffset, None));
+        } else {
+            // the Private DICTs precede the FDArray so that their offsets
+            // are known when the Font DICTs are encoded
+            let mut font_dicts = Vec::with_capacity(self.fd_array.len());
+            for font_dict in &self.fd_array {
+                // every Font DICT references a Private DICT, even if empty
+                let private = font_dict.private_dict.as_ref().unwrap_or(&empty_private);
+                let private_start = start + data.len();
+                let (private_data, dict_len) = private.compile();
+                data.extend(private_data);
+                let range = encoded_private_range(dict_len, private_start);
+                font_dicts.push(dict::rewrite(
+                    &font_dict.dict,
+                    &[(DictKey::PrivateDictRange, Some(range))],
+                ));
+            }
+            updates.push((DictKey::FdArrayOffset, offset(&data)));
+            let mut font_dicts = Index1::from_bytes(font_dicts);
+            if preserve_offset_sizes {
+                font_dicts.widen_offsets(self.fd_array_off_size);
+            }
+            data.extend(write_to_vec(&font_dicts));
+        }
+
+        // a name-keyed font always references a Private DICT, even if empty
+        let private = match &self.private_dict {
+            Some(private) => Some(private),
+            None if self.fd_array.is_empty() => Some(&empty_private),
+            None => None,
+        };
+        match private {
+            Some(private) => {
+                let private_start = start + data.len();
+                let (private_data, dict_len) = private.compile();
+                data.extend(private_data);
+                updates.push((
+                    DictKey::PrivateDictRange,
+                    Some(encoded_private_range(dict_len, private_start)),
+                ));
+            }
+            None => updates.push((DictKey::PrivateDictRange, None)),
+        }
+        (updates, data)
+    }
+
+    /// Returns the updates to apply to the Top DICT and the length of the
+    /// data returned by [`compile`](Self::compile), without encoding the
+    /// INDEXes.
+    ///
+    /// Offsets in the updates are placeholders, which have the same
+    /// encoded size as the actual offsets.
+    fn compiled_len(
+        &self,
+        top_dict: &[u8],
+        preserve_offset_sizes: bool,
+    ) -> (Vec<dict::Update>, usize)
+    where
+        I: CffIndex,
+    {
+        let mut updates = Vec::new();
+        let mut len = 0;
+        let entries = dict::parse(top_dict).unwrap_or_default();
+        let offset = || Some(encoded_offset(0));
+
+        match &self.charset {
+            Some(charset) => {
+                updates.push((DictKey::Charset, offset()));
+                len += write_to_vec(charset).len();
+            }
+            None if offset_operand(&entries, DictKey::Charset).is_some_and(|x| x > 2) => {
+                updates.push((DictKey::Charset, None))
+            }
+            None => (),
+        }
+        match self.custom_encoding() {
+            Some(encoding) => {
+                updates.push((DictKey::Encoding, offset()));
+                len += encoding.len();
+            }
+            None if offset_operand(&entries, DictKey::Encoding).is_some_and(|x| x != 1) => {
+                updates.push((DictKey::Encoding, None))
+            }
+            None => (),
+        }
+        match &self.fd_select {
+            Some(fd_select) => {
+                updates.push((DictKey::FdSelectOffset, offset()));
+                len += write_to_vec(&fd_select.compact(self.char_strings.len())).len();
+            }
+            None => updates.push((DictKey::FdSelectOffset, None)),
+        }
+        updates.push((DictKey::CharstringsOffset, offset()));
+        len += self.char_strings.byte_len();
+
+        if self.fd_array.is_empty() {
+            updates.push((DictKey::FdArrayOffset, None));
+        } else {
+            let mut font_dicts = Vec::with_capacity(self.fd_array.len());
+            for font_dict in &self.fd_array {
+                let (private_len, dict_len) = match &font_dict.private_dict {
+                    Some(private) => (private.compiled_len(), private_dict_len(private)),
+                    None => (0, 0),
+                };
+                len += private_len;
+                font_dicts.push(dict::rewrite(
+                    &font_dict.dict,
+                    &[(
+                        DictKey::PrivateDictRange,
+                        Some(encoded_private_range(dict_len, 0)),
+                    )],
+                ));
+            }
+            updates.push((DictKey::FdArrayOffset, offset()));
+            let mut font_dicts = Index1::from_bytes(font_dicts);
+            if preserve_offset_sizes {
+                font_dicts.widen_offsets(self.fd_array_off_size);
+            }
+            len += font_dicts.byte_len();
+        }
+
+        match &self.private_dict {
+            Some(private) => {
+                len += private.compiled_len();
+                updates.push((
+                    DictKey::PrivateDictRange,
+                    Some(encoded_private_range(private_dict_len(private), 0)),
+                ));
+            }
+            None if self.fd_array.is_empty() => {
+                updates.push((DictKey::PrivateDictRange, Some(encoded_private_range(0, 0))))
+            }
+            None => updates.push((DictKey::PrivateDictRange, None)),
+        }
+        (updates, len)
+    }
+}
+
+/// Returns the size of a Private DICT, excluding its subroutines.
+fn private_dict_len<I: CffIndex>(private: &PrivateDict<I>) -> usize {
+    private.compiled_len() - private.subrs.as_ref().map_or(0, CffIndex::byte_len)
+}
+
+/// The DICT operators with a single string identifier operand.
+const STRING_KEYS: [DictKey; 9] = [
+    DictKey::Version,
+    DictKey::Notice,
+    DictKey::Copyright,
+    DictKey::FullName,
+    DictKey::FamilyName,
+    DictKey::Weight,
+    DictKey::PostScript,
+    DictKey::BaseFontName,
+    DictKey::FontName,
+];
+
+/// The data needed to interpret the charstrings of a font, which is
+/// prepared once for any number of glyphs.
+struct OutlineData<'a, I> {
+    cff: &'a Cff<I>,
+    font: &'a FontTables<I>,
+    global_subrs: Vec<u8>,
+    /// The local subroutines of each Private DICT, in the order of
+    /// [`FontTables::private_dicts`].
+    local_subrs: Vec<Option<Vec<u8>>>,
+    /// The subroutines as slices, for parsing charstrings.
+    global_subr_slices: Vec<&'a [u8]>,
+    local_subr_slices: Vec<Vec<&'a [u8]>>,
+    /// The charstrings INDEX and the compiled table, which are only needed
+    /// to find the components of a `seac`, so are built on first use.
+    seac_data: OnceCell<(Vec<u8>, Vec<u8>)>,
+}
+
+impl<'a, I: CffIndex> OutlineData<'a, I> {
+    /// Prepares the font at the given index, which must have Type 2
+    /// charstrings.
+    fn new(cff: &'a Cff<I>, font_index: usize) -> Result<Self, CffError> {
+        match cff.charstring_type(font_index)? {
+            2 => (),
+            other => return Err(CffError::UnsupportedCharstringType(other)),
+        }
+        let font = cff
+            .font_tables
+            .get(font_index)
+            .ok_or(CffError::FontIndexOutOfBounds(font_index))?;
+        let local_subrs = font
+            .private_dicts()
+            .into_iter()
+            .map(|private| private.and_then(|private| private.subrs.as_ref()))
+            .collect::<Vec<_>>();
+        Ok(OutlineData {
+            cff,
+            font,
+            global_subrs: write_to_vec(&cff.global_subrs),
+            local_subrs: local_subrs
+                .iter()
+                .map(|subrs| subrs.map(write_to_vec))
+                .collect(),
+            global_subr_slices: cff.global_subrs.iter().collect(),
+            local_subr_slices: local_subrs
+                .iter()
+                .map(|subrs| {
+                    subrs
+                        .map(|subrs| subrs.iter().collect())
+                        .unwrap_or_default()
+                })
+                .collect(),
+            seac_data: OnceCell::new(),
+        })
+    }
+
+    /// Interprets the charstring of a glyph, sending the path to `pen`.
+    fn draw(&self, gid: u16, pen: &mut impl OutlinePen) -> Result<(), CffError> {
+        use read_fonts::tables::postscript::{charstring::evaluate, Index};
+        // the INDEX of a font without a seac, which has no count
+        const EMPTY_INDEX: &[u8] = &[0, 0];
+        let charstring = self
+            .font
+            .char_strings
+            .get(gid as usize)
+            .ok_or(CffError::GlyphIndexOutOfBounds(gid))?;
+        let fd = self.font.fd_index(gid as usize);
+        let uses_seac = Parser::new(&[])
+            .with_subrs(
+                &self.global_subr_slices,
+                self.local_subr_slices
+                    .get(fd)
+                    .map_or(&[][..], Vec::as_slice),
+            )
+            .with_inlined_subrs()
+            .parse(charstring)
+            .is_ok_and(|tokens| charstring::seac_codes(&tokens).is_some());
+        // the whole table is needed to look up the components of a seac
+        let (char_strings, data) = match uses_seac {
+            true => {
+                let (char_strings, data) = self
+                    .seac_data
+                    .get_or_init(|| (write_to_vec(&self.font.char_strings), self.cff.compile()));
+                (char_strings.as_slice(), data.as_slice())
+            }
+            false => (EMPTY_INDEX, &[][..]),
+        };
+        let subrs = self.local_subrs.get(fd).and_then(Option::as_deref);
+        let index = |data| Index::new(data, false).map_err(|_| CffError::InvalidOffsetRange);
+        evaluate(
+            data,
+            index(char_strings)?,
+            index(&self.global_subrs)?,
+            subrs.map(index).transpose()?,
+            None,
+            charstring,
+            &mut PenSink(pen),
+        )
+        .map_err(|_| CffError::InvalidCharstring)
+    }
+}
+
+/// A pen that records the bounds of the points of an outline.
+#[derive(Default)]
+struct BoundsPen(Option<[f64; 4]>);
+
+impl BoundsPen {
+    fn add(&mut self, x: f64, y: f64) {
+        let bounds = self.0.get_or_insert([x, y, x, y]);
+        *bounds = [
+            bounds[0].min(x),
+            bounds[1].min(y),
+            bounds[2].max(x),
+            bounds[3].max(y),
+        ];
+    }
+}
+
+impl OutlinePen for BoundsPen {
+    fn move_to(&mut self, x: f64, y: f64) {
+        self.add(x, y);
+    }
+
+    fn line_to(&mut self, x: f64, y: f64) {
+        self.add(x, y);
+    }
+
+    fn curve_to(&mut self, cx0: f64, cy0: f64, cx1: f64, cy1: f64, x: f64, y: f64) {
+        self.add(cx0, cy0);
+        self.add(cx1, cy1);
+        self.add(x, y);
+    }
+
+    fn close(&mut self) {}
+}
+
+/// The stem count and local subroutines at the first call to a subroutine.
+type SubrContext<'a> = (usize, &'a [&'a [u8]]);
+
+/// The charstrings and subroutines of a font set, as parsed by
+/// [`Cff::parse_charstrings`].
+///
+/// Subroutines that are never called are `None`, since the number of stem
+/// hints needed to parse them is unknown.
+struct ParsedCharstrings {
+    global_subrs: Vec<Option<Vec<Token>>>,
+    /// The selected Font DICT and the tokens of each charstring, for each
+    /// font.
+    char_strings: Vec<Vec<(usize, Vec<Token>)>>,
+    /// The local subroutines of each Private DICT, in the order of
+    /// [`FontTables::private_dicts`], for each font.
+    local_subrs: Vec<Vec<Vec<Option<Vec<Token>>>>>,
+    /// `true` if any global subroutine calls a local subroutine.
+    global_calls_local: bool,
+}
+
+impl ParsedCharstrings {
+    /// Calls `f` for every parsed charstring and subroutine.
+    fn for_each_mut(&mut self, mut f: impl FnMut(&mut Vec<Token>)) {
+        self.global_subrs.iter_mut().flatten().for_each(&mut f);
+        for (char_strings, local_subrs) in self.char_strings.iter_mut().zip(&mut self.local_subrs) {
+            char_strings.iter_mut().for_each(|(_, tokens)| f(tokens));
+            local_subrs.iter_mut().flatten().flatten().for_each(&mut f);
+        }
+    }
+}
+
+/// The new numbering of a set of subroutines.
+pub(crate) struct SubrMap {
+    old_bias: i32,
+    new_bias: i32,
+    /// The new index of each subroutine, or `None` if it is removed.
+    new_indices: Vec<Option<usize>>,
+}
+
+impl SubrMap {
+    /// Creates a mapping that removes the subroutines that aren't used.
+    fn new(subrs: &[Option<Vec<Token>>]) -> Self {
+        let mut count = 0;
+        let new_indices = subrs
+            .iter()
+            .map(|subr| {
+                subr.as_ref()?;
+                count += 1;
+                Some(count - 1)
+            })
+            .collect();
+        SubrMap {
+            old_bias: charstring::subr_bias(subrs.len()),
+            new_bias: charstring::subr_bias(count),
+            new_indices,
+        }
+    }
+
+    /// Creates a mapping that retains all subroutines.
+    fn identity(count: usize) -> Self {
+        SubrMap {
+            old_bias: charstring::subr_bias(count),
+            new_bias: charstring::subr_bias(count),
+            new_indices: (0..count).map(Some).collect(),
+        }
+    }
+
+    /// Creates a mapping that keeps the number of each subroutine when the
+    /// count changes, so that only the bias is updated.
+    pub(crate) fn rebias(old_count: usize, new_count: usize) -> Self {
+        SubrMap {
+            old_bias: charstring::subr_bias(old_count),
+            new_bias: charstring::subr_bias(new_count),
+            new_indices: (0..new_count).map(Some).collect(),
+        }
+    }
+
+    /// Creates a mapping that moves all subroutines to start at `offset` in a
+    /// set of `new_count` subroutines.
+    fn shift(old_count: usize, offset: usize, new_count: usize) -> Self {
+        SubrMap {
+            old_bias: charstring::subr_bias(old_count),
+            new_bias: charstring::subr_bias(new_count),
+            new_indices: (offset..offset + old_count).map(Some).collect(),
+        }
+    }
+
+    /// Returns the new biased number for a biased subroutine number.
+    fn renumber(&self, number: i32) -> Option<i32> {
+        let old_index = usize::try_from(number + self.old_bias).ok()?;
+        let new_index = (*self.new_indices.get(old_index)?)?;
+        Some(new_index as i32 - self.new_bias)
+    }
+
+    /// Removes the data of the subroutines that aren't retained.
+    fn retain(&self, subrs: Vec<Vec<u8>>) -> Vec<Vec<u8>> {
+        subrs
+            .into_iter()
+            .zip(&self.new_indices)
+            .filter_map(|(subr, new_index)| new_index.map(|_| subr))
+            .collect()
+    }
+}
+
+/// Updates the operands of the subroutine calls in a charstring.
+///
+/// The subroutine number must immediately precede each call. Calls to
+/// local subroutines are unchanged if `local_subrs` is `None`.
+pub(crate) fn renumber_subr_calls(
+    tokens: &mut [Token],
+    global_subrs: &SubrMap,
+    local_subrs: Option<&SubrMap>,
+) -> Result<(), CffError> {
+    for i in 1..tokens.len() {
+        let map = match tokens[i] {
+            Token::Operator(charstring::Operator::CallSubr) => match local_subrs {
+                Some(map) => map,
+                None => continue,
+            },
+            Token::Operator(charstring::Operator::CallGsubr) => global_subrs,
+            _ => continue,
+        };
+        let Token::Operand(charstring::Operand::Integer(number)) = &mut tokens[i - 1] else {
+            return Err(CffError::InvalidCharstring);
+        };
+        *number = map.renumber(*number).ok_or(CffError::InvalidCharstring)?;
+    }
+    Ok(())
+}
+
+/// Parses each subroutine given the stem count and local subroutines at its
+/// first call.
+///
+/// Subroutines that are never called are `None`.
+fn parse_subrs(
+    subrs: &[&[u8]],
+    calls: &[Option<SubrContext>],
+    global_subrs: &[&[u8]],
+) -> Result<Vec<Option<Vec<Token>>>, CffError> {
+    subrs
+        .iter()
+        .zip(calls)
+        .map(|(subr, call)| {
+            let Some((stem_count, local_subrs)) = call else {
+                return Ok(None);
+            };
+            Parser::new(&[])
+                .with_stem_count(*stem_count)
+                .with_subrs(global_subrs, local_subrs)
+                .parse(subr)
+                .map(Some)
+        })
+        .collect()
+}
+
+/// Encodes parsed subroutines, copying those that weren't parsed from
+/// `source`.
+fn encode_subrs(subrs: &[Option<Vec<Token>>], source: &Index1) -> Vec<Vec<u8>> {
+    subrs
+        .iter()
+        .zip(source.iter())
+        .map(|(subr, source)| match subr {
+            Some(tokens) => charstring::encode(tokens),
+            None => source.to_vec(),
+        })
+        .collect()
+}
+
+/// Encodes DICT entries, using the integer form for all integral operands.
+///
+/// Entries with the same values as an entry in the `source` DICT data are
+/// copied from it unchanged, so that existing encodings are preserved.
+fn encode_entries(entries: &[Entry], source: &[u8]) -> Vec<u8> {
+    let source_entries = dict::parse_with_ranges(source).unwrap_or_default();
+    let mut out = Vec::new();
+    for entry in entries {
+        let same_values = |source: &Entry| {
+            source.key == entry.key
+                && source.operands.len() == entry.operands.len()
+                && (source.operands.iter())
+                    .zip(&entry.operands)
+                    .all(|(a, b)| a.to_f64() == b.to_f64())
+        };
+        if let Some((_, range)) = source_entries
+            .iter()
+            .find(|(source, _)| same_values(source))
+        {
+            out.extend_from_slice(&source[range.clone()]);
+            continue;
+        }
+        for operand in &entry.operands {
+            match operand {
+                Operand::Integer(value) => dict::encode_int(*value, &mut out),
+                Operand::Real(value) => dict::encode_number(*value, &mut out),
+            }
+        }
+        dict::encode_operator(entry.key, &mut out);
+    }
+    out
+}
+
+/// Returns `true` if the FontMatrix operands are six values with a non-zero
+/// determinant.
+fn is_valid_font_matrix(values: &[f64]) -> bool {
+    matches!(values, [a, b, c, d, _, _] if a * d - b * c != 0.0)
+}
+
+/// Returns `true` if the given Top DICT entries belong to a CID-keyed font.
+fn is_cid_keyed(entries: &[Entry]) -> bool {
+    entries.iter().any(|entry| entry.key == DictKey::Ros)
+}
+
+/// Returns the string identifiers referenced by the given DICT entries.
+fn dict_string_ids(entries: &[Entry]) -> impl Iterator<Item = StringId> + '_ {
+    entries.iter().flat_map(|entry| {
+        let count = match entry.key {
+            key if STRING_KEYS.contains(&key) => 1,
+            // registry and ordering
+            DictKey::Ros => 2,
+            _ => 0,
+        };
+        entry
+            .operands
+            .iter()
+            .take(count)
+            .filter_map(|operand| match operand {
+                Operand::Integer(sid) => u16::try_from(*sid).ok().map(StringId::new),
+                Operand::Real(_) => None,
+            })
+    })
+}
+
+/// Returns a copy of the DICT data with each string identifier replaced.
+fn remap_dict_strings(
+    data: &[u8],
+    remap: impl Fn(StringId) -> StringId,
+) -> Result<Vec<u8>, CffError> {
+    let mut updates = Vec::new();
+    for entry in dict::parse(data)? {
+        if dict_string_ids(std::slice::from_ref(&entry))
+            .next()
+            .is_none()
+        {
+            continue;
+        }
+        let count = if entry.key == DictKey::Ros { 2 } else { 1 };
+        let mut operands = Vec::new();
+        for (i, operand) in entry.operands.iter().enumerate() {
+            let operand = match operand {
+                Operand::Integer(sid) if i < count => {
+                    let sid = remap(StringId::new(*sid as u16));
+                    Operand::Integer(sid.to_u16() as i32)
+                }
+                other => *other,
+            };
+            dict::encode_operand(operand, &mut operands);
+        }
+        updates.push((entry.key, Some(operands)));
+    }
+    Ok(dict::rewrite(data, &updates))
+}
+
+/// Returns a copy of the Top DICT data with each string identifier
+/// replaced, and replaces those in the Font DICTs and, for a name-keyed
+/// font, the charset of the font's subtables.
+fn remap_font_strings(
+    top_dict: &[u8],
+    font: Option<&mut FontTables>,
+    remap: impl Fn(StringId) -> StringId,
+) -> Result<Vec<u8>, CffError> {
+    let remapped = remap_dict_strings(top_dict, &remap)?;
+    let Some(font) = font else {
+        return Ok(remapped);
+    };
+    for font_dict in &mut font.fd_array {
+        font_dict.dict = remap_dict_strings(&font_dict.dict, &remap)?;
+    }
+    if !is_cid_keyed(&dict::parse(top_dict)?) {
+        if let Some(charset) = &mut font.charset {
+            let sids = charset_sids(charset)
+                .into_iter()
+                .map(|sid| remap(StringId::new(sid)).to_u16())
+                .collect::<Vec<_>>();
+            *charset = CustomCharset::from_sids(&sids);
+        }
+    }
+    Ok(remapped)
+}
+
+/// Returns the string identifier (or CID) of each glyph in the charset,
+/// excluding .notdef.
+fn charset_sids(charset: &CustomCharset) -> Vec<u16> {
+    let expand =
+        |first: u16, n_left: usize| (0..=n_left).map(move |i| first.wrapping_add(i as u16));
+    match charset {
+        CustomCharset::Format0(format0) => format0.glyph.clone(),
+        CustomCharset::Format1(format1) => format1
+            .ranges
+            .iter()
+            .flat_map(|range| expand(range.first, range.n_left as usize))
+            .collect(),
+        CustomCharset::Format2(format2) => format2
+            .ranges
+            .iter()
+            .flat_map(|range| expand(range.first, range.n_left as usize))
+            .collect(),
+    }
+}
+
+fn string_operand(entry: &Entry) -> Option<StringId> {
+    match entry.operands.as_slice() {
+        [Operand::Integer(sid)] => u16::try_from(*sid).ok().map(StringId::new),
+        _ => None,
+    }
+}
+
+/// Removes any ranges that extend past the number of glyphs in the font.
+///
+/// The charset has no explicit length, so the source data extends to the
+/// end of the table.
+fn trim_charset(charset: CustomCharset, num_glyphs: usize) -> CustomCharset {
+    // the charset omits .notdef
+    let num_glyphs = num_glyphs.saturating_sub(1);
+    match charset {
+        CustomCharset::Format0(mut format0) => {
+            format0.glyph.truncate(num_glyphs);
+            CustomCharset::Format0(format0)
+        }
+        CustomCharset::Format1(mut format1) => {
+            let len = ranges_needed(format1.ranges.iter().map(|r| r.n_left as usize), num_glyphs);
+            format1.ranges.truncate(len);
+            CustomCharset::Format1(format1)
+        }
+        CustomCharset::Format2(mut format2) => {
+            let len = ranges_needed(format2.ranges.iter().map(|r| r.n_left as usize), num_glyphs);
+            format2.ranges.truncate(len);
+            CustomCharset::Format2(format2)
+        }
+    }
+}
+
+/// Returns the number of charset ranges required to cover `num_glyphs`.
+fn ranges_needed(n_left: impl Iterator<Item = usize>, num_glyphs: usize) -> usize {
+    let mut covered = 0;
+    let mut count = 0;
+    for n_left in n_left {
+        if covered >= num_glyphs {
+            break;
+        }
+        covered += n_left + 1;
+        count += 1;
+    }
+    count
+}
+
+/// Describes how an FDSelect fails to map exactly the glyphs
+/// `0..num_glyphs`, or returns `None` if it maps them all.
+fn fd_select_coverage_error(fd_select: &FdSelect, num_glyphs: usize) -> Option<String> {
+    match fd_select {
+        FdSelect::Format0(format0) => (format0.fds.len() != num_glyphs).then(|| {
+            format!(
+                "has an FDSelect covering {} glyphs rather than {num_glyphs}",
+                format0.fds.len()
+            )
+        }),
+        FdSelect::Format3(format3) => {
+            if format3.sentinel as usize != num_glyphs {
+                return Some(format!(
+                    "has an FDSelect sentinel of {} rather than {num_glyphs}",
+                    format3.sentinel
+                ));
+            }
+            if format3.ranges.is_empty() {
+                return (num_glyphs > 0).then(|| "has an FDSelect with no ranges".into());
+            }
+            // the first range must start at glyph 0, and every range must be
+            // non-empty
+            let firsts = format3.ranges.iter().map(|range| range.first);
+            let mut previous = None;
+            for first in firsts.chain([format3.sentinel]) {
+                let valid = match previous {
+                    None => first == 0,
+                    Some(previous) => first > previous,
+                };
+                if !valid {
+                    return Some(format!("has an FDSelect range starting at glyph {first}"));
+                }
+                previous = Some(first);
+            }
+            None
+        }
+        FdSelect::Format4(_) => Some("has a format 4 FDSelect, which is only valid in CFF2".into()),
+    }
+}
+
+/// Returns the size of the encoding at the start of `data`.
+fn encoding_len(data: &[u8]) -> Option<usize> {
+    let format = *data.first()?;
+    let count = *data.get(1)? as usize;
+    let mut len = match format & 0x7F {
+        0 => 2 + count,
+        1 => 2 + count * 2,
+        _ => return None,
+    };
+    // the high bit indicates the presence of supplemental encodings
+    if format & 0x80 != 0 {
+        len += 1 + *data.get(len)? as usize * 3;
+    }
+    (len <= data.len()).then_some(len)
+}
+
+fn latin1_to_string(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| *b as char).collect()
+}
+
+fn string_to_latin1(string: &str) -> Vec<u8> {
+    string
+        .chars()
+        .map(|c| u8::try_from(c).unwrap_or(b'?'))
+        .collect()
+}
+
+impl<I: CffIndex> TopLevelTable for Cff<I> {
+    const TAG: Tag = Tag::new(b"CFF ");
+}
+
+impl<I: CffIndex> FontWrite for Cff<I> {
+    fn write_into(&self, writer: &mut TableWriter) {
+        writer.write_slice(&self.compile())
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::TopLevel(Self::TAG)
+    }
+}
+
+impl<I: CffIndex> Validate for Cff<I> {
+    fn validate_impl(&self, ctx: &mut ValidationCtx) {
+        ctx.in_table("Cff", |ctx| {
+            ctx.in_field("header", |ctx| {
+                if self.header.major != 1 {
+                    ctx.report(format!(
+                        "major version {} isn't supported by CFF, which is version 1",
+                        self.header.major
+                    ));
+                }
+                if self.header.hdr_size as usize != 4 + self.header._padding.len() {
+                    ctx.report("hdr_size must cover the four header fields and the padding");
+                }
+            });
+            if self.font_tables.len() != self.top_dicts.len() {
+                ctx.report("font_tables must contain one entry for each Top DICT");
+            }
+            if self.names.len() != self.top_dicts.len() {
+                ctx.report(format!(
+                    "the Name INDEX has {} entries but the Top DICT INDEX has {}",
+                    self.names.len(),
+                    self.top_dicts.len()
+                ));
+            }
+            ctx.in_field("top_dicts", |ctx| {
+                let Ok(top_dicts) = self
+                    .top_dicts
+                    .iter()
+                    .map(dict::parse)
+                    .collect::<Result<Vec<_>, _>>()
+                else {
+                    ctx.report("malformed Top DICT");
+                    return;
+                };
+                let invalid_matrix = top_dicts.iter().flatten().any(|entry| {
+                    let values = entry.operands.iter().map(|operand| operand.to_f64());
+                    entry.key == DictKey::FontMatrix
+                        && !is_valid_font_matrix(&values.collect::<Vec<_>>())
+                });
+                if invalid_matrix {
+                    ctx.report("FontMatrix must have six values and a non-zero determinant");
+                }
+            });
+            ctx.in_field("strings", |ctx| {
+                let long_string = self
+                    .strings
+                    .iter()
+                    .position(|string| string.len() > MAX_STRING_LEN);
+                if let Some(index) = long_string {
+                    ctx.report(format!(
+                        "string {} is longer than {MAX_STRING_LEN} bytes",
+                        index + STANDARD_STRING_COUNT
+                    ));
+                }
+            });
+            ctx.in_field("variation_store", |ctx| {
+                if self
+                    .variation_store
+                    .as_ref()
+                    .is_some_and(|store| store.len() > u16::MAX as usize)
+                {
+                    ctx.report("the variation store is longer than 65535 bytes");
+                }
+            });
+            ctx.in_field("font_tables", |ctx| {
+                let private_dicts = self.font_tables.iter().flat_map(|font| {
+                    font.private_dict.iter().chain(
+                        font.fd_array
+                            .iter()
+                            .filter_map(|font_dict| font_dict.private_dict.as_ref()),
+                    )
+                });
+                if private_dicts
+                    .map(|private| &private.dict)
+                    .chain(
+                        self.font_tables
+                            .iter()
+                            .flat_map(|font| font.fd_array.iter().map(|fd| &fd.dict)),
+                    )
+                    .any(|data| dict::parse(data).is_err())
+                {
+                    ctx.report("malformed Private or Font DICT");
+                }
+                for (font_index, font) in self.font_tables.iter().enumerate() {
+                    let is_cid = self
+                        .top_dicts
+                        .get(font_index)
+                        .and_then(|top_dict| dict::parse(top_dict).ok())
+                        .is_some_and(|entries| is_cid_keyed(&entries));
+                    // .notdef has the identifier 0
+                    let mut seen = BTreeSet::from([0]);
+                    let duplicate = font
+                        .charset
+                        .as_ref()
+                        .map(charset_sids)
+                        .unwrap_or_default()
+                        .into_iter()
+                        .find(|sid| !seen.insert(*sid));
+                    if let Some(duplicate) = duplicate {
+                        let kind = if is_cid { "CID" } else { "string id" };
+                        ctx.report(format!(
+                            "the charset of font {font_index} has the duplicate {kind} {duplicate}"
+                        ));
+                    }
+                    if !is_cid {
+                        continue;
+                    }
+                    let num_glyphs = font.char_strings.len();
+                    let error = match &font.fd_select {
+                        Some(fd_select) => fd_select_coverage_error(fd_select, num_glyphs),
+                        None => Some("has no FDSelect".into()),
+                    };
+                    if let Some(error) = error {
+                        ctx.report(format!("CID-keyed font {font_index} {error}"));
+                    }
+                }
+            });
+        })
+    }
+}
+
+/// The checks of [`Cff::validate_charstrings`], which interpret every
+/// charstring.
+struct CharstringChecks<'a, I>(&'a Cff<I>);
+
+impl<I: CffIndex> Validate for CharstringChecks<'_, I> {
+    fn validate_impl(&self, ctx: &mut ValidationCtx) {
+        let cff = self.0;
+        ctx.in_table("Cff", |ctx| {
+            // other charstring types can't be interpreted
+            if cff.check_charstring_types().is_err() {
+                return;
+            }
+            ctx.in_field("font_tables", |ctx| {
+                let global_subrs = cff.global_subrs.iter().collect::<Vec<_>>();
+                for (font_index, font) in cff.font_tables.iter().enumerate() {
+                    let local_subrs = font
+                        .private_dicts()
+                        .into_iter()
+                        .map(|private| match private.and_then(|p| p.subrs.as_ref()) {
+                            Some(subrs) => subrs.iter().collect(),
+                            None => Vec::new(),
+                        })
+                        .collect::<Vec<Vec<_>>>();
+                    for (gid, charstring) in font.char_strings.iter().enumerate() {
+                        let subrs = local_subrs
+                            .get(font.fd_index(gid))
+                            .map_or(&[][..], Vec::as_slice);
+                        // malformed charstrings are reported with the subroutines
+                        let Ok(tokens) = Parser::new(&[])
+                            .with_subrs(&global_subrs, subrs)
+                            .with_inlined_subrs()
+                            .parse(charstring)
+                        else {
+                            continue;
+                        };
+                        if let Some((operator, count)) = charstring::invalid_operand_count(&tokens)
+                        {
+                            ctx.report(format!(
+                                "glyph {gid} of font {font_index} has {count} operands for {operator:?}"
+                            ));
+                        }
+                    }
+                }
+            });
+            ctx.in_field("global_subrs", |ctx| {
+                if let Err(err) = cff.validate_subrs() {
+                    ctx.report(format!("invalid subroutines: {err}"));
+                }
+            });
+        })
+    }
+}
+
+impl PassthroughCff<'_> {
+    /// The data of the table.
+    pub fn data(&self) -> &[u8] {
+        self.data
+    }
+}
+
+impl TopLevelTable for PassthroughCff<'_> {
+    const TAG: Tag = <Cff>::TAG;
+}
+
+impl FontWrite for PassthroughCff<'_> {
+    fn write_into(&self, writer: &mut TableWriter) {
+        writer.write_slice(self.data)
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::TopLevel(<Cff>::TAG)
+    }
+}
+
+impl Validate for PassthroughCff<'_> {
+    fn validate_impl(&self, _ctx: &mut ValidationCtx) {}
+}
+
+impl<I> Cff<I> {
+    /// Reads the table, with INDEXes of type `I`.
+    ///
+    /// If `strict` is `true`, an error is returned if an INDEX, a Top DICT
+    /// or a subtable referenced by a Top DICT can't be read. Otherwise these
+    /// are left empty, for the conversions that can't fail.
+    fn read_source<'a>(
+        obj: &read_fonts::tables::cff::Cff<'a>,
+        strict: bool,
+    ) -> Result<Self, ReadError>
+    where
+        I: FontRead<'a> + CffIndex + Default,
+    {
+        // offsets in the DICTs are relative to the start of the table, so
+        // they are resolved against the table's own data rather than the
+        // (empty) parent data passed by `FromTableRef`
+        let data = obj.offset_data();
+        let index = |index: read_fonts::tables::postscript::Index1<'a>| {
+            recover(I::read(index.offset_data()), strict)
+        };
+        let mut header: CffHeader = obj.header().to_owned_table();
+        header.trailing_data.clear();
+        let top_dicts = index(obj.top_dicts())?;
+        // names and strings are looked up by their offsets, so overlapping
+        // offsets give garbled data
+        let indexes = [
+            ("name", obj.names()),
+            ("Top DICT", obj.top_dicts()),
+            ("string", obj.strings()),
+            ("global subroutine", obj.global_subrs()),
+        ];
+        for (name, index) in indexes {
+            let wellformed = check_wellformed_offsets(
+                index.count() as usize,
+                index.off_size(),
+                index.offsets(),
+                index.data().len(),
+            );
+            match wellformed {
+                Err(_) if strict => {
+                    return Err(ReadError::MalformedData("malformed INDEX offsets"))
+                }
+                Err(_) => log::warn!("the {name} INDEX has malformed offsets"),
+                Ok(()) => (),
+            }
+        }
+        let font_tables = top_dicts
+            .iter()
+            .map(|top_dict| {
+                let font = dict::parse(top_dict)
+                    .map_err(|_| ReadError::MalformedData("invalid Top DICT"))
+                    .and_then(|entries| FontTables::read(data, &entries));
+                recover(font, strict)
+            })
+            .collect::<Result<_, _>>()?;
+        // the store is preceded by its length
+        let variation_store = top_dicts
+            .iter()
+            .filter_map(|top_dict| {
+                offset_operand(&dict::parse(top_dict).ok()?, DictKey::VariationStoreOffset)
+            })
+            .find_map(|offset| {
+                let len = data.read_at::<u16>(offset).ok()? as usize;
+                data.slice(offset + 2..offset + 2 + len)
+                    .map(|store| store.as_bytes().to_vec())
+            });
+        Ok(Cff {
+            header,
+            names: index(obj.names())?,
+            top_dicts,
+            strings: index(obj.strings())?,
+            global_subrs: index(obj.global_subrs())?,
+            font_tables,
+            variation_store,
+            preserve_offset_sizes: false,
+            pad_to_four_bytes: false,
+            string_index_limit: None,
+        })
+    }
+}
+
+/// Returns the result of reading part of a table, or the default value if
+/// the read failed and `strict` is `false`.
+fn recover<T: Default>(result: Result<T, ReadError>, strict: bool) -> Result<T, ReadError> {
+    match result {
+        Err(_) if !strict => Ok(T::default()),
+        result => result,
+    }
+}
+
+impl<'a> Cff<CowIndex1<'a>> {
+    /// Creates a table that borrows the INDEXes of the given source table.
+    ///
+    /// Unchanged INDEXes are written directly from the source data, so only
+    /// the INDEXes modified through [`CowIndex1::to_mut`] are copied. The
+    /// output is identical to that of the owned table created with
+    /// [`FromTableRef`].
+    ///
+    /// Parts of the source that can't be read are left empty; reading the
+    /// owned table with [`FontRead`] reports them instead.
+    pub fn borrow_from(source: &read_fonts::tables::cff::Cff<'a>) -> Self {
+        Self::read_source(source, false).unwrap_or_default()
+    }
+
+    /// Wraps the data of a CFF table that is written without changes.
+    ///
+    /// The returned table writes the data verbatim, which avoids parsing
+    /// and re-encoding a table that isn't modified. The data is not
+    /// validated.
+    pub fn passthrough(data: &[u8]) -> PassthroughCff<'_> {
+        PassthroughCff { data }
+    }
+
+    /// Serializes the table.
+    ///
+    /// This is equivalent to [`dump_table`](crate::dump_table).
+    pub fn to_bytes(&self) -> Result<Vec<u8>, CffError> {
+        Ok(crate::dump_table(self)?)
+    }
+}
+
+impl<'a> FromObjRef<read_fonts::tables::cff::Cff<'a>> for Cff {
+    // this can't fail, so parts of the source that can't be read are left
+    // empty
+    fn from_obj_ref(obj: &read_fonts::tables::cff::Cff<'a>, _: FontData) -> Self {
+        Cff::read_source(obj, false).unwrap_or_default()
+    }
+}
+
+impl<'a> FromTableRef<read_fonts::tables::cff::Cff<'a>> for Cff {}
+
+impl<'a> FontRead<'a> for Cff {
+    fn read(data: FontData<'a>) -> Result<Self, ReadError> {
+        let source = <read_fonts::tables::cff::Cff as FontRead>::read(data)?;
+        Cff::read_source(&source, true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tables::postscript::{
+        charstring::{Operand as CharstringOperand, Operator},
+        CharsetRange1, CharsetRange2, FdSelectRange3,
+    };
+    use read_fonts::{tables::postscript::charstring, FontRef, TableProvider};
+
+    fn owned(font_data: &[u8]) -> Cff {
+        FontRef::new(font_data)
+            .unwrap()
+            .cff()
+            .unwrap()
+            .to_owned_table()
+    }
+
+    /// Serializes the table, checking that the output is unchanged when it
+    /// is parsed and serialized again and that the parsed table has the same
+    /// contents as the source.
+    ///
+    /// Offsets in DICTs are ignored since they are computed when writing.
+    fn assert_cff_roundtrip(cff: &Cff) -> Vec<u8> {
+        fn objects(index: &Index1) -> Vec<&[u8]> {
+            index.iter().collect()
+        }
+        fn entries(data: &[u8]) -> Vec<Entry> {
+            let mut entries = dict::parse(data).unwrap();
+            entries.retain(|entry| {
+                !matches!(
+                    entry.key,
+                    DictKey::Charset
+                        | DictKey::Encoding
+                        | DictKey::CharstringsOffset
+                        | DictKey::PrivateDictRange
+                        | DictKey::FdArrayOffset
+                        | DictKey::FdSelectOffset
+                        | DictKey::SubrsOffset
+                        | DictKey::VariationStoreOffset
+                )
+            });
+            entries
+        }
+        fn subrs(private: Option<&PrivateDict<Index1>>) -> Option<Vec<&[u8]>> {
+            private
+                .and_then(|private| private.subrs.as_ref())
+                .map(objects)
+        }
+
+        let bytes = crate::dump_table(cff).unwrap();
+        let reparsed = Cff::read(FontData::new(&bytes)).unwrap();
+        assert_eq!(crate::dump_table(&reparsed).unwrap(), bytes);
+
+        assert_eq!(objects(&reparsed.names), objects(&cff.names));
+        assert_eq!(objects(&reparsed.strings), objects(&cff.strings));
+        assert_eq!(objects(&reparsed.global_subrs), objects(&cff.global_subrs));
+        assert_eq!(reparsed.variation_store, cff.variation_store);
+        assert_eq!(reparsed.top_dicts.len(), cff.top_dicts.len());
+        for (reparsed, source) in reparsed.top_dicts.iter().zip(cff.top_dicts.iter()) {
+            assert_eq!(entries(reparsed), entries(source));
+        }
+        assert_eq!(reparsed.font_tables.len(), cff.font_tables.len());
+        for (reparsed, source) in reparsed.font_tables.iter().zip(&cff.font_tables) {
+            assert_eq!(
+                objects(&reparsed.char_strings),
+                objects(&source.char_strings)
+            );
+            assert_eq!(reparsed.charset, source.charset);
+            assert_eq!(
+                reparsed.fd_select,
+                source
+                    .fd_select
+                    .as_ref()
+                    .map(|fd_select| fd_select.compact(source.char_strings.len()))
+            );
+            assert_eq!(
+                subrs(reparsed.private_dict.as_ref()),
+                subrs(source.private_dict.as_ref())
+            );
+            assert_eq!(reparsed.fd_array.len(), source.fd_array.len());
+            for (reparsed, source) in reparsed.fd_array.iter().zip(&source.fd_array) {
+                assert_eq!(entries(&reparsed.dict), entries(&source.dict));
+                assert_eq!(
+                    subrs(reparsed.private_dict.as_ref()),
+                    subrs(source.private_dict.as_ref())
+                );
+            }
+        }
+        bytes
+    }
+
+    /// Records the path commands produced by a charstring.
+    #[derive(Default)]
+    struct Recorder(Vec<String>);
+
+    impl charstring::CommandSink for Recorder {
+        fn move_to(&mut self, x: Fixed, y: Fixed) {
+            self.0.push(format!("M{x} {y}"));
+        }
+        fn line_to(&mut self, x: Fixed, y: Fixed) {
+            self.0.push(format!("L{x} {y}"));
+        }
+        fn curve_to(&mut self, cx0: Fixed, cy0: Fixed, cx1: Fixed, cy1: Fixed, x: Fixed, y: Fixed) {
+            self.0.push(format!("C{cx0} {cy0} {cx1} {cy1} {x} {y}"));
+        }
+        fn close(&mut self) {
+            self.0.push("Z".into());
+        }
+    }
+
+    /// Records the bounds of the points produced by a charstring.
+    #[derive(Debug, Default, PartialEq)]
+    struct Bounds(Option<[f64; 4]>);
+
+    impl Bounds {
+        fn add(&mut self, x: Fixed, y: Fixed) {
+            let (x, y) = (x.to_f64(), y.to_f64());
+            let bounds = self.0.get_or_insert([x, y, x, y]);
+            *bounds = [
+                bounds[0].min(x),
+                bounds[1].min(y),
+                bounds[2].max(x),
+                bounds[3].max(y),
+            ];
+        }
+    }
+
+    impl charstring::CommandSink for Bounds {
+        fn move_to(&mut self, x: Fixed, y: Fixed) {
+            self.add(x, y);
+        }
+        fn line_to(&mut self, x: Fixed, y: Fixed) {
+            self.add(x, y);
+        }
+        fn curve_to(&mut self, cx0: Fixed, cy0: Fixed, cx1: Fixed, cy1: Fixed, x: Fixed, y: Fixed) {
+            self.add(cx0, cy0);
+            self.add(cx1, cy1);
+            self.add(x, y);
+        }
+        fn close(&mut self) {}
+    }
+
+    /// Evaluates every charstring in the first font of the given table.
+    fn outlines(data: &[u8]) -> Vec<Vec<String>> {
+        evaluate::<Recorder>(data)
+            .into_iter()
+            .map(|recorder| recorder.0)
+            .collect()
+    }
+
+    /// Evaluates every charstring in the first font of the given table with
+    /// a new sink for each glyph.
+    fn evaluate<S: charstring::CommandSink + Default>(data: &[u8]) -> Vec<S> {
+        use read_fonts::tables::{
+            cff::Cff,
+            postscript::{dict, Index},
+        };
+        let cff = Cff::read(FontData::new(data)).unwrap();
+        let top_dict = cff.top_dicts().get(0).unwrap();
+        let mut charstrings = None;
+        let mut private = None;
+        for entry in dict::entries(top_dict, None) {
+            match entry.unwrap() {
+                dict::Entry::CharstringsOffset(offset) => charstrings = Some(offset),
+                dict::Entry::PrivateDictRange(range) => private = Some(range),
+                _ => (),
+            }
+        }
+        let charstrings = Index::new(&data[charstrings.unwrap()..], false).unwrap();
+        let private = private.unwrap();
+        let subrs = dict::entries(&data[private.clone()], None)
+            .find_map(|entry| match entry {
+                Ok(dict::Entry::SubrsOffset(offset)) => Some(offset),
+                _ => None,
+            })
+            .map(|offset| Index::new(&data[private.start + offset..], false).unwrap());
+        (0..charstrings.count() as usize)
+            .map(|gid| {
+                let mut sink = S::default();
+                charstring::evaluate(
+                    data,
+                    charstrings.clone(),
+                    cff.global_subrs().into(),
+                    subrs.clone(),
+                    None,
+                    charstrings.get(gid).unwrap(),
+                    &mut sink,
+                )
+                .unwrap();
+                sink
+            })
+            .collect()
+    }
+
+    #[test]
+    fn round_trip_name_keyed() {
+        let font = FontRef::new(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED).unwrap();
+        let source = font.cff().unwrap();
+        let cff: Cff = source.to_owned_table();
+        assert_eq!(cff.font_tables.len(), 1);
+        assert_eq!(cff.font_tables[0].char_strings.len(), 5);
+        assert_eq!(cff.global_subrs.len(), 17);
+        assert_eq!(
+            cff.font_tables[0]
+                .private_dict
+                .as_ref()
+                .and_then(|private| private.subrs.as_ref())
+                .map(Index1::len),
+            Some(2)
+        );
+
+        let bytes = assert_cff_roundtrip(&cff);
+        let reparsed = read_fonts::tables::cff::Cff::read(FontData::new(&bytes)).unwrap();
+        let names = |cff: &read_fonts::tables::cff::Cff| {
+            let charset = cff.charset(0).unwrap().unwrap();
+            charset
+                .iter()
+                .map(|(_, sid)| cff.string(sid).unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(&reparsed), names(&source));
+        assert_eq!(outlines(&bytes), outlines(source.offset_data().as_bytes()));
+    }
+
+    #[test]
+    fn round_trip_cid_keyed() {
+        let cff = owned(font_test_data::NOTO_SANS_JP_CFF);
+        let font = &cff.font_tables[0];
+        assert_eq!(font.fd_array.len(), 2);
+        assert!(font.fd_select.is_some());
+        assert!(font.private_dict.is_none());
+
+        let bytes = assert_cff_roundtrip(&cff);
+        let reparsed = Cff::read(FontData::new(&bytes)).unwrap();
+        assert_eq!(reparsed.font_tables[0].char_strings, font.char_strings);
+        assert_eq!(reparsed.font_tables[0].fd_select, font.fd_select);
+        assert_eq!(reparsed.font_tables[0].charset, font.charset);
+        let private_dicts = |font: &FontTables| {
+            font.fd_array
+                .iter()
+                .map(|fd| fd.private_dict.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(private_dicts(&reparsed.font_tables[0]), private_dicts(font));
+    }
+
+    #[test]
+    fn round_trip_empty_private_dict() {
+        let cff = owned(font_test_data::MATERIAL_ICONS_SUBSET);
+        assert_eq!(
+            cff.font_tables[0].private_dict,
+            Some(PrivateDict::default())
+        );
+        let bytes = assert_cff_roundtrip(&cff);
+        let reparsed = Cff::read(FontData::new(&bytes)).unwrap();
+        assert_eq!(reparsed.font_tables, cff.font_tables);
+    }
+
+    #[test]
+    fn cid_keyed() {
+        assert!(!owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED).is_cid_keyed());
+        assert!(owned(font_test_data::NOTO_SANS_JP_CFF).is_cid_keyed());
+        assert!(!<Cff>::default().is_cid_keyed());
+    }
+
+    #[test]
+    fn edit_top_dict_strings() {
+        let mut cff = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        let mut top_dict = cff.get_top_dict_data(0).unwrap();
+        assert_eq!(top_dict.version.as_deref(), Some("2.9"));
+        assert_eq!(top_dict.family_name.as_deref(), Some("Noto Serif Display"));
+        assert_eq!(top_dict.weight, None);
+
+        top_dict.version = Some("3.0".into());
+        // a standard string
+        top_dict.weight = Some("Bold".into());
+        cff.set_top_dict_data(0, &top_dict).unwrap();
+        assert_eq!(cff.strings.len(), 6);
+
+        let bytes = assert_cff_roundtrip(&cff);
+        let reparsed = Cff::read(FontData::new(&bytes)).unwrap();
+        let top_dict = reparsed.get_top_dict_data(0).unwrap();
+        assert_eq!(top_dict.version.as_deref(), Some("3.0"));
+        assert_eq!(top_dict.weight.as_deref(), Some("Bold"));
+        assert_eq!(top_dict.family_name.as_deref(), Some("Noto Serif Display"));
+        assert_eq!(outlines(&bytes).len(), 5);
+    }
+
+    #[test]
+    fn borrowed_indexes() {
+        for font_data in [
+            font_test_data::NOTO_SERIF_DISPLAY_TRIMMED,
+            font_test_data::NOTO_SANS_JP_CFF,
+        ] {
+            let source = FontRef::new(font_data).unwrap().cff().unwrap();
+            let mut owned = Cff::from_table_ref(&source);
+            let mut borrowed = Cff::borrow_from(&source);
+            assert!(borrowed.strings.is_borrowed());
+            assert!(borrowed.font_tables[0].char_strings.is_borrowed());
+            assert_eq!(borrowed.to_bytes().unwrap(), owned.to_bytes().unwrap());
+
+            // only the edited INDEX is copied
+            owned.strings.push(b"Edited");
+            borrowed.strings.to_mut().push(b"Edited");
+            assert!(!borrowed.strings.is_borrowed());
+            assert!(borrowed.global_subrs.is_borrowed());
+            assert_eq!(borrowed.to_bytes().unwrap(), owned.to_bytes().unwrap());
+            assert_eq!(borrowed.strings.into_owned(), owned.strings);
+        }
+    }
+
+    #[test]
+    fn subset_name_keyed() {
+        let mut cff = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        let source = outlines(&crate::dump_table(&cff).unwrap());
+        let names = |cff: &Cff| {
+            charset_sids(cff.font_tables[0].charset.as_ref().unwrap())
+                .into_iter()
+                .map(|sid| cff.resolve_string(StringId::new(sid)).unwrap())
+                .collect::<Vec<_>>()
+        };
+        let source_names = names(&cff);
+        let remap = cff.subset(0, &[4, 2]).unwrap();
+        assert_eq!(remap, [0, REMOVED_GLYPH, 1, REMOVED_GLYPH, 2]);
+
+        let bytes = assert_cff_roundtrip(&cff);
+        let subset = outlines(&bytes);
+        assert_eq!(
+            subset,
+            [source[0].clone(), source[2].clone(), source[4].clone()]
+        );
+        let subset_names = names(&Cff::read(FontData::new(&bytes)).unwrap());
+        // the charset omits .notdef
+        assert_eq!(
+            subset_names,
+            [source_names[1].clone(), source_names[3].clone()]
+        );
+    }
+
+    #[test]
+    fn subset_cid_keyed() {
+        let mut cff = owned(font_test_data::NOTO_SANS_JP_CFF);
+        let source = cff.clone();
+        let remap = cff.subset(0, &[5, 2, 0]).unwrap();
+        assert_eq!(remap.len(), 60);
+        assert_eq!(remap[0], 0);
+        assert_eq!(remap[2], 1);
+        assert_eq!(remap[5], 2);
+        let removed = (0..60).filter(|gid| ![0, 2, 5].contains(gid));
+        assert!(removed.into_iter().all(|gid| remap[gid] == REMOVED_GLYPH));
+
+        let bytes = assert_cff_roundtrip(&cff);
+        let reparsed = Cff::read(FontData::new(&bytes)).unwrap();
+        let font = &reparsed.font_tables[0];
+        assert_eq!(font.char_strings.len(), 3);
+        let cids = charset_sids(font.charset.as_ref().unwrap());
+        let source_cids = charset_sids(source.font_tables[0].charset.as_ref().unwrap());
+        assert_eq!(cids, [source_cids[1], source_cids[4]]);
+        let source_fd_select = source.font_tables[0].fd_select.as_ref().unwrap();
+        let fd_select = font.fd_select.as_ref().unwrap();
+        for (old_gid, new_gid) in [(0, 0), (2, 1), (5, 2)] {
+            assert_eq!(
+                fd_select.font_index(new_gid),
+                source_fd_select.font_index(old_gid)
+            );
+        }
+        assert!(reparsed.validate_subrs().is_ok());
+        assert!(matches!(
+            cff.subset(0, &[3]),
+            Err(CffError::GlyphIndexOutOfBounds(3))
+        ));
+    }
+
+    #[test]
+    fn fd_index() {
+        use read_fonts::tables::postscript::{dict, FdSelect};
+        let font = FontRef::new(font_test_data::NOTO_SANS_JP_CFF).unwrap();
+        let source = font.cff().unwrap();
+        let top_dict = source.top_dicts().get(0).unwrap();
+        let fd_select_offset = dict::entries(top_dict, None)
+            .find_map(|entry| match entry {
+                Ok(dict::Entry::FdSelectOffset(offset)) => Some(offset),
+                _ => None,
+            })
+            .unwrap();
+        let fd_select =
+            FdSelect::read(source.offset_data().split_off(fd_select_offset).unwrap()).unwrap();
+
+        let cff = owned(font_test_data::NOTO_SANS_JP_CFF);
+        let fds = (0..60)
+            .map(|gid| cff.fd_index(0, gid).unwrap() as u16)
+            .collect::<Vec<_>>();
+        assert!(fds.iter().any(|fd| *fd != 0));
+        for (gid, fd) in fds.iter().enumerate() {
+            assert_eq!(fd_select.font_index(GlyphId::new(gid as u32)), Some(*fd));
+        }
+        assert!(matches!(
+            cff.fd_index(0, 60),
+            Err(CffError::GlyphIndexOutOfBounds(60))
+        ));
+        assert!(matches!(
+            cff.fd_index(1, 0),
+            Err(CffError::FontIndexOutOfBounds(1))
+        ));
+        // name-keyed fonts have no FDSelect
+        let cff = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        assert_eq!(cff.fd_index(0, 1).unwrap(), 0);
+    }
+
+    #[test]
+    fn empty_table_accessors() {
+        let mut cff = Cff::default();
+        assert_eq!(cff.fonts().count(), 0);
+        assert_eq!(cff.custom_strings().unwrap(), []);
+        assert_eq!(cff.resolve_string(StringId::new(0)).unwrap(), ".notdef");
+        assert_eq!(cff.resolve_string(StringId::new(391)), None);
+        assert!(matches!(
+            cff.get_string_bytes(StringId::new(391)),
+            Err(CffError::InvalidStringId(_))
+        ));
+        assert!(matches!(
+            cff.get_top_dict_data(0),
+            Err(CffError::FontIndexOutOfBounds(0))
+        ));
+        assert!(matches!(
+            cff.set_top_dict_data(0, &TopDictData::default()),
+            Err(CffError::FontIndexOutOfBounds(0))
+        ));
+        assert!(matches!(
+            cff.private_dict_bytes(0),
+            Err(CffError::FontIndexOutOfBounds(0))
+        ));
+        assert!(matches!(
+            cff.charstring_bytes(0, 0),
+            Err(CffError::FontIndexOutOfBounds(0))
+        ));
+        assert!(matches!(
+            cff.glyph_name(0, 0),
+            Err(CffError::FontIndexOutOfBounds(0))
+        ));
+        assert!(matches!(
+            cff.charstring_type(0),
+            Err(CffError::FontIndexOutOfBounds(0))
+        ));
+        assert!(!cff.summary().is_empty());
+        cff.validate_subrs().unwrap();
+        cff.strip_hints().unwrap();
+        cff.remove_unused_subrs().unwrap();
+        cff.subroutinize().unwrap();
+        cff.scale_outlines(2.0).unwrap();
+        cff.repack().unwrap();
+        assert!(cff.layout().fonts.is_empty());
+        // the header and four INDEXes with a count of 0
+        assert_eq!(
+            cff.to_bytes().unwrap(),
+            [1, 0, 4, 1, 0, 0, 0, 0, 0, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn widths_for_glyph() {
+        let cff = owned(font_test_data::NOTO_SANS_JP_CFF);
+        let mut seen = Vec::new();
+        for gid in 0..60 {
+            let fd_index = cff.fd_index(0, gid).unwrap() as usize;
+            let private = cff.font_tables[0].fd_array[fd_index]
+                .private_dict
+                .as_ref()
+                .unwrap();
+            let data = PrivateDictData::parse(&private.dict, &[]).unwrap();
+            let expected = (
+                data.default_width_x.unwrap().default,
+                data.nominal_width_x.unwrap().default,
+            );
+            let widths = cff.widths_for_glyph(0, gid).unwrap();
+            assert_eq!(widths, expected);
+            seen.push(widths);
+        }
+        seen.dedup();
+        assert!(seen.len() > 1);
+        assert!(matches!(
+            cff.widths_for_glyph(0, 60),
+            Err(CffError::GlyphIndexOutOfBounds(60))
+        ));
+
+        // missing values default to 0
+        let mut cff = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        cff.set_private_dict_data(0, &PrivateDictData::default())
+            .unwrap();
+        assert_eq!(cff.widths_for_glyph(0, 1).unwrap(), (0.0, 0.0));
+    }
+
+    #[test]
+    fn disassemble_charstring() {
+        let cff = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        let notdef = cff.disassemble_charstring(0, 0).unwrap();
+        assert!(!notdef.is_empty());
+        assert!(notdef.ends_with("endchar"));
+        for line in notdef.lines() {
+            let operator = line.split(' ').next_back().unwrap();
+            assert!(operator
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit()));
+        }
+        assert!(matches!(
+            cff.disassemble_charstring(0, 100),
+            Err(CffError::GlyphIndexOutOfBounds(100))
+        ));
+    }
+
+    #[test]
+    fn reorder_glyphs() {
+        let mut cff = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        let draw = |cff: &Cff, gid: u16| {
+            let mut pen = PenRecorder::default();
+            cff.outline(0, gid, &mut pen).unwrap();
+            pen.0
+        };
+        let old = (0..5)
+            .map(|gid| (draw(&cff, gid), cff.glyph_name(0, gid).unwrap()))
+            .collect::<Vec<_>>();
+        let new_order = [0, 3, 1, 4, 2];
+        let remap = cff.reorder_glyphs(0, &new_order).unwrap();
+        assert_eq!(remap, [0, 2, 4, 1, 3]);
+        let bytes = assert_cff_roundtrip(&cff);
+        let reparsed = Cff::read(FontData::new(&bytes)).unwrap();
+        for (old_gid, new_gid) in remap.iter().enumerate() {
+            let new = (
+                draw(&reparsed, *new_gid),
+                reparsed.glyph_name(0, *new_gid).unwrap(),
+            );
+            assert_eq!(new, old[old_gid]);
+        }
+
+        for invalid in [&[0, 1, 2, 3][..], &[1, 0, 2, 3, 4], &[0, 1, 1, 3, 4]] {
+            assert!(matches!(
+                cff.reorder_glyphs(0, invalid),
+                Err(CffError::InvalidGlyphOrder)
+            ));
+        }
+        assert!(matches!(
+            cff.reorder_glyphs(0, &[0, 1, 2, 3, 5]),
+            Err(CffError::GlyphIndexOutOfBounds(5))
+        ));
+    }
+
+    #[test]
+    fn reorder_glyphs_cid_keyed() {
+        let mut cff = owned(font_test_data::NOTO_SANS_JP_CFF);
+        let source = cff.clone();
+        let new_order = std::iter::once(0).chain((1..60).rev()).collect::<Vec<_>>();
+        let remap = cff.reorder_glyphs(0, &new_order).unwrap();
+        let bytes = assert_cff_roundtrip(&cff);
+        let reparsed = Cff::read(FontData::new(&bytes)).unwrap();
+        let source_cids = source.charset_sids(0).unwrap();
+        let cids = reparsed.charset_sids(0).unwrap();
+        for (old_gid, new_gid) in remap.iter().enumerate() {
+            assert_eq!(new_gid + old_gid as u16, if old_gid == 0 { 0 } else { 60 });
+            assert_eq!(
+                reparsed.charstring_bytes(0, *new_gid).unwrap(),
+                source.charstring_bytes(0, old_gid as u16).unwrap()
+            );
+            assert_eq!(
+                reparsed.fd_index(0, *new_gid).unwrap(),
+                source.fd_index(0, old_gid as u16).unwrap()
+            );
+            if old_gid > 0 {
+                assert_eq!(cids[*new_gid as usize - 1], source_cids[old_gid - 1]);
+            }
+        }
+    }
+
+    #[test]
+    fn subset_keeps_seac_components() {
+        let mut cff = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        cff.rename_glyph(0, 1, "A").unwrap();
+        cff.rename_glyph(0, 3, "acute").unwrap();
+        // glyph 4 is "A" with "acute" offset by (100, 200)
+        let seac = crate::tables::postscript::charstring::encode(
+            &[100, 200, 65, 194]
+                .map(|value| Token::Operand(CharstringOperand::Integer(value)))
+                .into_iter()
+                .chain([Token::Operator(Operator::EndChar)])
+                .collect::<Vec<_>>(),
+        );
+        let char_strings = &cff.font_tables[0].char_strings;
+        let char_strings = (0..char_strings.len())
+            .map(|gid| match gid {
+                4 => seac.clone(),
+                _ => char_strings.get(gid).unwrap().to_vec(),
+            })
+            .collect::<Vec<_>>();
+        cff.font_tables[0].char_strings = Index1::from_bytes(char_strings);
+        let composed = outlines(&crate::dump_table(&cff).unwrap())[4].clone();
+        assert!(!composed.is_empty());
+
+        let remap = cff.subset(0, &[4]).unwrap();
+        assert_eq!(remap, [0, 1, REMOVED_GLYPH, 2, 3]);
+        let bytes = assert_cff_roundtrip(&cff);
+        assert_eq!(outlines(&bytes)[3], composed);
+        assert_eq!(cff.glyph_name(0, 1).unwrap(), "A");
+        assert_eq!(cff.glyph_name(0, 2).unwrap(), "acute");
+    }
+
+    #[test]
+    fn outline_with_seac() {
+        let mut cff = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        cff.rename_glyph(0, 1, "A").unwrap();
+        cff.rename_glyph(0, 3, "acute").unwrap();
+        // glyph 4 is "A" with "acute" offset by (100, 200)
+        let seac = crate::tables::postscript::charstring::encode(
+            &[100, 200, 65, 194]
+                .map(|value| Token::Operand(CharstringOperand::Integer(value)))
+                .into_iter()
+                .chain([Token::Operator(Operator::EndChar)])
+                .collect::<Vec<_>>(),
+        );
+        let mut char_strings = cff.font_tables[0]
+            .char_strings
+            .iter()
+            .map(<[u8]>::to_vec)
+            .collect::<Vec<_>>();
+        char_strings[4] = seac;
+        cff.font_tables[0].char_strings = Index1::from_bytes(char_strings);
+        let draw = |gid| {
+            let mut pen = PenRecorder::default();
+            cff.outline(0, gid, &mut pen).unwrap();
+            pen.0
+        };
+        let (base, accent, composed) = (draw(1), draw(3), draw(4));
+        // the accent is drawn first, at its offset, then the base
+        assert_eq!(composed.len(), accent.len() + base.len());
+        assert_ne!(composed[..accent.len()], accent);
+        assert!(cff.recompute_font_bbox(0).is_ok());
+    }
+
+    #[test]
+    fn long_string_fails_validation() {
+        let mut cff = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        let mut top_dict = cff.get_top_dict_data(0).unwrap();
+        top_dict.notice = Some("n".repeat(70_000));
+        cff.set_top_dict_data(0, &top_dict).unwrap();
+        let err = cff.validate().unwrap_err().to_string();
+        assert!(err.contains("longer than 65535 bytes"), "{err}");
+
+        top_dict.notice = Some("n".repeat(MAX_STRING_LEN));
+        cff.set_top_dict_data(0, &top_dict).unwrap();
+        cff.repack().unwrap();
+        assert!(cff.validate().is_ok());
+    }
+
+    #[test]
+    fn local_subrs() {
+        let mut cff = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        assert_eq!(cff.local_subrs(0, 0).unwrap().map(Index1::len), Some(2));
+        assert!(matches!(
+            cff.local_subrs(0, 1),
+            Err(CffError::FdIndexOutOfBounds(1))
+        ));
+        assert!(matches!(
+            cff.local_subrs(1, 0),
+            Err(CffError::FontIndexOutOfBounds(1))
+        ));
+
+        let subrs = cff.local_subrs(0, 0).unwrap().unwrap().clone();
+        // an unused subroutine appended to the end
+        let extended = Index1::from_bytes(subrs.iter().chain([[11].as_slice()]));
+        cff.set_local_subrs(0, 0, Some(extended.clone())).unwrap();
+        let bytes = assert_cff_roundtrip(&cff);
+        let reparsed = Cff::read(FontData::new(&bytes)).unwrap();
+        assert_eq!(reparsed.local_subrs(0, 0).unwrap(), Some(&extended));
+
+        let cid = owned(font_test_data::NOTO_SANS_JP_CFF);
+        let fd_count = cid.font_tables[0].fd_array.len();
+        for fd_index in 0..fd_count {
+            assert!(cid.local_subrs(0, fd_index).is_ok());
+        }
+        assert!(cid.local_subrs(0, fd_count).is_err());
+    }
+
+    #[test]
+    fn custom_strings() {
+        let cff = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        let strings = cff.custom_strings().unwrap();
+        assert_eq!(strings.len(), cff.strings.len());
+        let (sid, string) = &strings[0];
+        assert_eq!(sid.to_u16(), 391);
+        assert_eq!(Some(string.clone()), cff.resolve_string(*sid));
+        assert_eq!(string, "2.9");
+    }
+
+    #[test]
+    fn string_map() {
+        let mut cff = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        let family_name = dict::parse(cff.get_top_dict_bytes(0).unwrap())
+            .unwrap()
+            .into_iter()
+            .find(|entry| entry.key == DictKey::FamilyName)
+            .unwrap();
+        let [Operand::Integer(sid)] = family_name.operands[..] else {
+            panic!("unexpected operands {:?}", family_name.operands);
+        };
+        let map = cff.string_map();
+        assert_eq!(map["Noto Serif Display"], StringId::new(sid as u16));
+        assert_eq!(map[".notdef"], StringId::new(0));
+        assert_eq!(map["2.9"], StringId::new(391));
+        assert_eq!(map.len(), STANDARD_STRING_COUNT + cff.strings.len());
+
+        // the first of duplicate strings is used
+        cff.strings.push(b"2.9");
+        cff.strings.push(b"A");
+        let map = cff.string_map();
+        assert_eq!(map["2.9"], StringId::new(391));
+        assert_eq!(map["A"], StringId::new(34));
+    }
+
+    #[test]
+    fn add_glyph() {
+        let operands = |values: &[i32]| {
+            values
+                .iter()
+                .map(|value| Token::Operand(CharstringOperand::Integer(*value)))
+                .collect::<Vec<_>>()
+        };
+        let mut tokens = operands(&[100, 0]);
+        tokens.push(Token::Operator(Operator::RMoveTo));
+        tokens.extend(operands(&[200, 0, -100, 300]));
+        tokens.push(Token::Operator(Operator::RLineTo));
+        tokens.push(Token::Operator(Operator::EndChar));
+        let triangle = crate::tables::postscript::charstring::encode(&tokens);
+        let mut cff = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        let num_glyphs = cff.font_tables[0].char_strings.len();
+        let gid = cff.add_glyph(0, "triangle", triangle.clone()).unwrap();
+        assert_eq!(gid as usize, num_glyphs);
+        assert_eq!(cff.font_tables[0].char_strings.len(), num_glyphs + 1);
+
+        let bytes = assert_cff_roundtrip(&cff);
+        let reparsed = Cff::read(FontData::new(&bytes)).unwrap();
+        assert_eq!(reparsed.glyph_name(0, gid).unwrap(), "triangle");
+        assert_eq!(
+            reparsed.glyph_name(0, 1).unwrap(),
+            cff.glyph_name(0, 1).unwrap()
+        );
+        let mut pen = PenRecorder::default();
+        reparsed.outline(0, gid, &mut pen).unwrap();
+        assert_eq!(pen.0, ["M100 0", "L300 0", "L200 300", "Z"]);
+
+        let mut cid = owned(font_test_data::NOTO_SANS_JP_CFF);
+        assert!(matches!(
+            cid.add_glyph(0, "triangle", triangle.clone()),
+            Err(CffError::CidFontUnsupported)
+        ));
+        assert!(matches!(
+            cff.add_cid_glyph(0, 1000, 0, triangle.clone()),
+            Err(CffError::NameKeyedFontUnsupported)
+        ));
+        assert!(matches!(
+            cid.add_cid_glyph(0, 1000, 2, triangle.clone()),
+            Err(CffError::FdIndexOutOfBounds(2))
+        ));
+        let mut no_tables = cid.clone();
+        no_tables.font_tables.clear();
+        assert!(matches!(
+            no_tables.add_cid_glyph(0, 1000, 0, triangle.clone()),
+            Err(CffError::FontIndexOutOfBounds(0))
+        ));
+        let num_glyphs = cid.font_tables[0].char_strings.len();
+        let fds = (0..num_glyphs as u16)
+            .map(|gid| cid.fd_index(0, gid).unwrap())
+            .collect::<Vec<_>>();
+        let gid = cid.add_cid_glyph(0, 1000, 1, triangle).unwrap();
+        assert_eq!(gid as usize, num_glyphs);
+        let bytes = assert_cff_roundtrip(&cid);
+        let reparsed = Cff::read(FontData::new(&bytes)).unwrap();
+        assert_eq!(reparsed.charset_sids(0).unwrap().last(), Some(&1000));
+        assert_eq!(reparsed.fd_index(0, gid).unwrap(), 1);
+        for (old_gid, fd) in fds.iter().enumerate() {
+            assert_eq!(reparsed.fd_index(0, old_gid as u16).unwrap(), *fd);
+        }
+        let mut pen = PenRecorder::default();
+        reparsed.outline(0, gid, &mut pen).unwrap();
+        assert_eq!(pen.0.len(), 4);
+    }
+
+    #[test]
+    fn rename_glyph() {
+        let mut cff = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        assert_eq!(cff.glyph_name(0, 0).unwrap(), ".notdef");
+        let old_name = cff.glyph_name(0, 1).unwrap();
+        cff.rename_glyph(0, 1, "renamed").unwrap();
+        assert_eq!(cff.glyph_name(0, 1).unwrap(), "renamed");
+        // a standard string needs no new custom string
+        let string_count = cff.strings.len();
+        cff.rename_glyph(0, 2, "A").unwrap();
+        assert_eq!(cff.strings.len(), string_count);
+
+        let bytes = assert_cff_roundtrip(&cff);
+        let reparsed = Cff::read(FontData::new(&bytes)).unwrap();
+        assert_eq!(reparsed.glyph_name(0, 1).unwrap(), "renamed");
+        assert_eq!(reparsed.glyph_name(0, 2).unwrap(), "A");
+        assert_ne!(old_name, "renamed");
+        assert!(matches!(
+            cff.rename_glyph(0, 0, "notdef"),
+            Err(CffError::GlyphIndexOutOfBounds(0))
+        ));
+        assert!(matches!(
+            cff.glyph_name(0, 5),
+            Err(CffError::GlyphIndexOutOfBounds(5))
+        ));
+
+        let mut cid = owned(font_test_data::NOTO_SANS_JP_CFF);
+        assert!(matches!(
+            cid.rename_glyph(0, 1, "A"),
+            Err(CffError::CidFontUnsupported)
+        ));
+        assert!(matches!(
+            cid.glyph_name(0, 1),
+            Err(CffError::CidFontUnsupported)
+        ));
+    }
+
+    #[test]
+    fn convert_between_name_keyed_and_cid_keyed() {
+        fn glyph_outlines(cff: &Cff) -> Vec<Vec<String>> {
+            (0..cff.font_tables[0].char_strings.len() as u16)
+                .map(|gid| {
+                    let mut pen = PenRecorder::default();
+                    cff.outline(0, gid, &mut pen).unwrap();
+                    pen.0
+                })
+                .collect()
+        }
+
+        let mut cff = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        let names = (0..5)
+            .map(|gid| cff.glyph_name(0, gid).unwrap())
+            .collect::<Vec<_>>();
+        let private = cff.get_private_dict_data(0).unwrap();
+        let expected = glyph_outlines(&cff);
+        assert!(matches!(
+            cff.to_name_keyed(&names),
+            Err(CffError::NameKeyedFontUnsupported)
+        ));
+        for cid_map in [&[0, 10, 11][..], &[1, 10, 11, 12, 20], &[0, 10, 11, 10, 20]] {
+            assert!(matches!(
+                cff.to_cid_keyed(cid_map),
+                Err(CffError::InvalidCharset)
+            ));
+        }
+
+        cff.to_cid_keyed(&[0, 10, 11, 12, 20]).unwrap();
+        assert!(cff.is_cid_keyed());
+        assert!(cff.validate().is_ok());
+        let bytes = assert_cff_roundtrip(&cff);
+        let cid = Cff::read(FontData::new(&bytes)).unwrap();
+        assert!(cid.is_cid_keyed());
+        assert_eq!(cid.private_dict_bytes(0).unwrap(), b"");
+        assert_eq!(
+            cid.local_subrs(0, 0).unwrap(),
+            cff.local_subrs(0, 0).unwrap()
+        );
+        assert_eq!(cid.charset_sids(0).unwrap(), [10, 11, 12, 20]);
+        assert_eq!(cid.fd_index(0, 4).unwrap(), 0);
+        let top_dict = dict::parse(cid.get_top_dict_bytes(0).unwrap()).unwrap();
+        assert_eq!(top_dict[0].key, DictKey::Ros);
+        let cid_count = top_dict
+            .iter()
+            .find(|entry| entry.key == DictKey::CidCount)
+            .unwrap();
+        assert_eq!(cid_count.operands, [Operand::Integer(21)]);
+        assert_eq!(glyph_outlines(&cid), expected);
+        assert!(matches!(
+            cff.to_cid_keyed(&[0, 1, 2, 3, 4]),
+            Err(CffError::CidFontUnsupported)
+        ));
+
+        // and back again
+        let mut cff = cid;
+        cff.to_name_keyed(&names).unwrap();
+        assert!(!cff.is_cid_keyed());
+        assert!(cff.validate().is_ok());
+        let bytes = assert_cff_roundtrip(&cff);
+        let mut name_keyed = Cff::read(FontData::new(&bytes)).unwrap();
+        assert_eq!(name_keyed.get_private_dict_data(0).unwrap(), private);
+        assert!(name_keyed.font_tables[0].fd_array.is_empty());
+        for (gid, name) in names.iter().enumerate() {
+            assert_eq!(name_keyed.glyph_name(0, gid as u16).unwrap(), *name);
+        }
+        assert_eq!(glyph_outlines(&name_keyed), expected);
+        name_keyed.repack().unwrap();
+        let mut source = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        source.repack().unwrap();
+        assert_eq!(
+            name_keyed.custom_strings().unwrap(),
+            source.custom_strings().unwrap()
+        );
+
+        // a font with several Font DICTs has no single Private DICT
+        let mut cff = owned(font_test_data::NOTO_SANS_JP_CFF);
+        assert!(matches!(
+            cff.to_name_keyed(&names),
+            Err(CffError::MultipleFontDicts)
+        ));
+
+        // a Top DICT without the tables of its font
+        cff.font_tables.clear();
+        assert!(matches!(
+            cff.to_name_keyed(&names),
+            Err(CffError::FontIndexOutOfBounds(0))
+        ));
+        let mut cff = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        cff.font_tables.clear();
+        assert!(matches!(
+            cff.to_cid_keyed(&[0, 10, 11, 12, 20]),
+            Err(CffError::FontIndexOutOfBounds(0))
+        ));
+    }
+
+    #[test]
+    fn predefined_encodings() {
+        let encoding_operand = |bytes: &[u8]| {
+            let reparsed = Cff::read(FontData::new(bytes)).unwrap();
+            assert_eq!(reparsed.font_tables[0].encoding, None);
+            dict::parse(reparsed.get_top_dict_bytes(0).unwrap())
+                .unwrap()
+                .iter()
+                .find(|entry| entry.key == DictKey::Encoding)
+                .map(|entry| entry.operands.clone())
+        };
+        let mut cff = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        // the Standard encoding is the default
+        cff.set_top_dict_operator(0, DictKey::Encoding, vec![Operand::Integer(0)])
+            .unwrap();
+        let bytes = assert_cff_roundtrip(&cff);
+        assert_eq!(encoding_operand(&bytes), None);
+        assert_eq!(bytes.len(), cff.serialized_len());
+        // empty data isn't written as a custom encoding
+        cff.font_tables[0].encoding = Some(Vec::new());
+        assert_eq!(crate::dump_table(&cff).unwrap(), bytes);
+
+        cff.set_top_dict_operator(0, DictKey::Encoding, vec![Operand::Integer(1)])
+            .unwrap();
+        let bytes = assert_cff_roundtrip(&cff);
+        assert_eq!(encoding_operand(&bytes), Some(vec![Operand::Integer(1)]));
+    }
+
+    #[test]
+    fn set_top_dict_operator() {
+        let mut cff = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        let sid = cff.add_string("/OrigFontType /TrueType def").unwrap();
+        let operand = Operand::Integer(sid.to_u16() as i32);
+        cff.set_top_dict_operator(0, DictKey::PostScript, vec![operand])
+            .unwrap();
+        // replacing an existing operator keeps its position
+        let family_name = dict::parse(cff.get_top_dict_bytes(0).unwrap())
+            .unwrap()
+            .iter()
+            .position(|entry| entry.key == DictKey::FamilyName);
+        let family = cff.add_string("Renamed").unwrap();
+        let family = Operand::Integer(family.to_u16() as i32);
+        cff.set_top_dict_operator(0, DictKey::FamilyName, vec![family])
+            .unwrap();
+
+        let bytes = assert_cff_roundtrip(&cff);
+        let reparsed = Cff::read(FontData::new(&bytes)).unwrap();
+        let entries = dict::parse(reparsed.get_top_dict_bytes(0).unwrap()).unwrap();
+        let post_script = entries
+            .iter()
+            .find(|entry| entry.key == DictKey::PostScript)
+            .unwrap();
+        assert_eq!(post_script.operands, [operand]);
+        let sid = string_operand(post_script).unwrap();
+        assert_eq!(
+            reparsed.resolve_string(sid).as_deref(),
+            Some("/OrigFontType /TrueType def")
+        );
+        assert_eq!(
+            entries
+                .iter()
+                .position(|entry| entry.key == DictKey::FamilyName),
+            family_name
+        );
+        let top_dict = reparsed.get_top_dict_data(0).unwrap();
+        assert_eq!(top_dict.family_name.as_deref(), Some("Renamed"));
+        assert!(matches!(
+            cff.set_top_dict_operator(1, DictKey::PostScript, Vec::new()),
+            Err(CffError::FontIndexOutOfBounds(1))
+        ));
+    }
+
+    #[test]
+    fn post_script_and_base_font_name() {
+        let mut cff = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        let sid = cff.add_string("/OrigFontType /TrueType def").unwrap();
+        let operand = Operand::Integer(sid.to_u16() as i32);
+        cff.set_top_dict_operator(0, DictKey::PostScript, vec![operand])
+            .unwrap();
+        let bytes = assert_cff_roundtrip(&cff);
+        let mut cff = Cff::read(FontData::new(&bytes)).unwrap();
+        let mut top_dict = cff.get_top_dict_data(0).unwrap();
+        assert_eq!(
+            top_dict.post_script.as_deref(),
+            Some("/OrigFontType /TrueType def")
+        );
+        assert_eq!(top_dict.base_font_name, None);
+        assert!(top_dict
+            .raw_entries
+            .iter()
+            .all(|entry| entry.key != DictKey::PostScript));
+
+        top_dict.post_script = Some("/OrigFontType /Type1 def".into());
+        top_dict.base_font_name = Some("NotoSerifDisplay-Base".into());
+        cff.set_top_dict_data(0, &top_dict).unwrap();
+        let bytes = assert_cff_roundtrip(&cff);
+        let reparsed = Cff::read(FontData::new(&bytes)).unwrap();
+        let top_dict = reparsed.get_top_dict_data(0).unwrap();
+        assert_eq!(
+            top_dict.post_script.as_deref(),
+            Some("/OrigFontType /Type1 def")
+        );
+        assert_eq!(
+            top_dict.base_font_name.as_deref(),
+            Some("NotoSerifDisplay-Base")
+        );
+        // the PostScript entry keeps its position
+        let keys = dict::parse(reparsed.get_top_dict_bytes(0).unwrap())
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.key)
+            .collect::<Vec<_>>();
+        assert!(
+            keys.iter().position(|key| *key == DictKey::PostScript)
+                < keys.iter().position(|key| *key == DictKey::BaseFontName)
+        );
+    }
+
+    #[test]
+    fn charstring_type() {
+        let mut cff = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        assert_eq!(cff.charstring_type(0).unwrap(), 2);
+        assert_eq!(
+            owned(font_test_data::NOTO_SANS_JP_CFF)
+                .charstring_type(0)
+                .unwrap(),
+            2
+        );
+        assert!(matches!(
+            cff.charstring_type(1),
+            Err(CffError::FontIndexOutOfBounds(1))
+        ));
+
+        cff.set_top_dict_operator(0, DictKey::CharstringType, vec![Operand::Integer(1)])
+            .unwrap();
+        assert_eq!(cff.charstring_type(0).unwrap(), 1);
+        let unsupported = |result: Result<(), CffError>| {
+            matches!(result, Err(CffError::UnsupportedCharstringType(1)))
+        };
+        assert!(unsupported(cff.outline(0, 1, &mut PenRecorder::default())));
+        assert!(unsupported(cff.clone().scale_outlines(2.0)));
+        assert!(unsupported(cff.clone().strip_hints()));
+        assert!(unsupported(cff.clone().subroutinize()));
+        assert!(unsupported(cff.clone().subset(0, &[1]).map(|_| ())));
+        // the charstrings can't be interpreted, but the table can be written
+        assert!(unsupported(cff.validate_subrs()));
+        assert_cff_roundtrip(&cff);
+    }
+
+    #[test]
+    fn passthrough() {
+        let font = FontRef::new(font_test_data::NOTO_SANS_JP_CFF).unwrap();
+        let source = font.table_data(PassthroughCff::TAG).unwrap();
+        let passthrough = Cff::passthrough(source.as_bytes());
+        assert_eq!(passthrough.data(), source.as_bytes());
+        let bytes = crate::dump_table(&passthrough).unwrap();
+        assert_eq!(bytes, source.as_bytes());
+
+        let mut builder = crate::FontBuilder::new();
+        builder.add_table(&passthrough).unwrap();
+        let font_data = builder.build();
+        let font = FontRef::new(&font_data).unwrap();
+        assert_eq!(
+            font.table_data(PassthroughCff::TAG).unwrap().as_bytes(),
+            source.as_bytes()
+        );
+    }
+
+    #[test]
+    fn format_version() {
+        let font = FontRef::new(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED).unwrap();
+        let header = font.cff().unwrap().header();
+        assert_eq!((header.major(), header.minor()), (1, 0));
+        let cff = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        assert_eq!(cff.format_version(), (1, 0));
+        let bytes = crate::dump_table(&cff).unwrap();
+        assert_eq!(bytes[..2], [1, 0]);
+
+        // the source version is kept, and only major version 1 is valid
+        let mut patched = bytes.clone();
+        patched[0] = 2;
+        let mut cff = Cff::read(FontData::new(&patched)).unwrap();
+        assert_eq!(cff.format_version(), (2, 0));
+        let report = cff.validate().unwrap_err().to_string();
+        assert!(report.contains("major version 2"), "{report}");
+        assert!(crate::dump_table(&cff).is_err());
+        cff.header.major = 1;
+        cff.header.minor = 1;
+        assert_eq!(cff.format_version(), (1, 1));
+        assert_eq!(crate::dump_table(&cff).unwrap()[..2], [1, 1]);
+    }
+
+    #[test]
+    fn header_extra_bytes() {
+        let mut cff = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        let source_bytes = crate::dump_table(&cff).unwrap();
+        assert!(cff.header_extra_bytes().is_empty());
+        cff.header.hdr_size = 8;
+        cff.header._padding = vec![0xCA, 0xFE, 0, 1];
+
+        let bytes = assert_cff_roundtrip(&cff);
+        assert_eq!(bytes[..3], [1, 0, 8]);
+        assert_eq!(bytes[4..8], [0xCA, 0xFE, 0, 1]);
+        let reparsed = Cff::read(FontData::new(&bytes)).unwrap();
+        assert_eq!(reparsed.header_extra_bytes(), [0xCA, 0xFE, 0, 1]);
+        assert_eq!(outlines(&bytes), outlines(&source_bytes));
+
+        // the header size must match the padding
+        cff.header.hdr_size = 4;
+        assert!(crate::dump_table(&cff).is_err());
+    }
+
+    #[test]
+    fn header_offset_size_grows() {
+        let mut cff = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        let off_size = cff.header.off_size;
+        assert!(off_size < 3);
+        let bytes = crate::dump_table(&cff).unwrap();
+        assert_eq!(bytes[3], off_size);
+
+        let mut strings = cff.strings.iter().map(<[u8]>::to_vec).collect::<Vec<_>>();
+        strings.extend([vec![b'x'; 0x8000], vec![b'y'; 0x8000]]);
+        cff.strings = Index1::from_bytes(strings);
+        let bytes = crate::dump_table(&cff).unwrap();
+        let reparsed = Cff::read(FontData::new(&bytes)).unwrap();
+        assert_eq!(reparsed.header.off_size, 3);
+        assert_eq!(reparsed.strings, cff.strings);
+    }
+
+    #[test]
+    fn preserve_offset_sizes() {
+        use read_fonts::tables::{cff::Cff as ReadCff, postscript::Index1 as ReadIndex1};
+        let encoding = |index: ReadIndex1| {
+            let objects = (0..index.count() as usize)
+                .map(|i| index.get(i).unwrap().to_vec())
+                .collect::<Vec<_>>();
+            (index.off_size(), index.offsets().to_vec(), objects)
+        };
+        // the INDEXes of the source already use the smallest offset sizes
+        let font = FontRef::new(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED).unwrap();
+        let source = font.cff().unwrap();
+        let mut cff = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        cff.preserve_offset_sizes = true;
+        let bytes = crate::dump_table(&cff).unwrap();
+        let dumped = ReadCff::read(FontData::new(&bytes)).unwrap();
+        assert_eq!(encoding(dumped.names()), encoding(source.names()));
+        assert_eq!(encoding(dumped.strings()), encoding(source.strings()));
+        assert_eq!(
+            encoding(dumped.global_subrs()),
+            encoding(source.global_subrs())
+        );
+        // the Top DICT is rewritten with five byte offsets
+        let top_dicts = |cff: &ReadCff| (cff.top_dicts().count(), cff.top_dicts().off_size());
+        assert_eq!(top_dicts(&dumped), top_dicts(&source));
+
+        // a source with wider offsets than necessary
+        let mut cff = owned(font_test_data::NOTO_SANS_JP_CFF);
+        cff.preserve_offset_sizes = true;
+        cff.top_dicts.widen_offsets(2);
+        cff.font_tables[0].fd_array_off_size = 3;
+        let source_bytes = crate::dump_table(&cff).unwrap();
+        let mut cff = Cff::read(FontData::new(&source_bytes)).unwrap();
+        assert_eq!(cff.top_dicts.off_size, 2);
+        assert_eq!(cff.font_tables[0].fd_array_off_size, 3);
+        cff.preserve_offset_sizes = true;
+        assert_eq!(crate::dump_table(&cff).unwrap(), source_bytes);
+        // by default the smallest offset sizes are used
+        cff.preserve_offset_sizes = false;
+        let bytes = crate::dump_table(&cff).unwrap();
+        let reparsed = Cff::read(FontData::new(&bytes)).unwrap();
+        assert_eq!(reparsed.top_dicts.off_size, 1);
+        assert_eq!(reparsed.font_tables[0].fd_array_off_size, 1);
+    }
+
+    #[test]
+    fn long_strings_grow_offset_size() {
+        let mut cff = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        assert_eq!(cff.strings.off_size, 1);
+        let mut top_dict = cff.get_top_dict_data(0).unwrap();
+        top_dict.notice = Some("n".repeat(300));
+        cff.set_top_dict_data(0, &top_dict).unwrap();
+        assert_eq!(cff.strings.off_size, 2);
+        // past the range of a two byte offset
+        top_dict.copyright = Some("c".repeat(MAX_STRING_LEN));
+        cff.set_top_dict_data(0, &top_dict).unwrap();
+        assert_eq!(cff.strings.off_size, 3);
+
+        let bytes = assert_cff_roundtrip(&cff);
+        let reparsed = Cff::read(FontData::new(&bytes)).unwrap();
+        assert_eq!(reparsed.strings.off_size, 3);
+        let reparsed_top_dict = reparsed.get_top_dict_data(0).unwrap();
+        assert!(reparsed_top_dict.notice == top_dict.notice);
+        assert!(reparsed_top_dict.copyright == top_dict.copyright);
+    }
+
+    #[test]
+    fn private_dict_bytes() {
+        let cff = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        let top_dict = dict::parse(cff.get_top_dict_bytes(0).unwrap()).unwrap();
+        let (size, _) = private_dict_range(&top_dict).unwrap();
+        let private = cff.private_dict_bytes(0).unwrap();
+        assert_eq!(private.len(), size);
+        assert_eq!(
+            PrivateDictData::parse(private, &[]).unwrap(),
+            cff.get_private_dict_data(0).unwrap()
+        );
+        assert!(matches!(
+            cff.private_dict_bytes(1),
+            Err(CffError::FontIndexOutOfBounds(1))
+        ));
+        // the Private DICTs of a CID-keyed font belong to its Font DICTs
+        let cff = owned(font_test_data::NOTO_SANS_JP_CFF);
+        assert!(cff.private_dict_bytes(0).unwrap().is_empty());
+    }
+
+    #[test]
+    fn edit_private_dict() {
+        let mut cff = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        let mut private = cff.get_private_dict_data(0).unwrap();
+        assert!(!private.blue_values.is_empty());
+        assert!(private
+            .blue_values
+            .iter()
+            .all(|value| value.deltas.is_empty()));
+        private.std_vw = Some(100.0.into());
+        cff.set_private_dict_data(0, &private).unwrap();
+
+        let bytes = assert_cff_roundtrip(&cff);
+        let reparsed = Cff::read(FontData::new(&bytes)).unwrap();
+        assert_eq!(reparsed.get_private_dict_data(0).unwrap(), private);
+        // local subroutines are still reachable
+        let source = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        assert_eq!(
+            outlines(&bytes),
+            outlines(&crate::dump_table(&source).unwrap())
+        );
+    }
+
+    #[test]
+    fn multiple_fonts() {
+        let mut cff = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        // duplicate the font under a new name
+        cff.names.push(b"NotoSerifDisplay-Copy");
+        cff.top_dicts
+            .push(cff.get_top_dict_bytes(0).unwrap().to_vec().as_slice());
+        cff.font_tables.push(cff.font_tables[0].clone());
+        let mut top_dict = cff.get_top_dict_data(1).unwrap();
+        top_dict.version = Some("4.0".into());
+        cff.set_top_dict_data(1, &top_dict).unwrap();
+        assert!(cff.get_top_dict_data(2).is_err());
+
+        let bytes = assert_cff_roundtrip(&cff);
+        let reparsed = Cff::read(FontData::new(&bytes)).unwrap();
+        let fonts = reparsed
+            .fonts()
+            .map(|font| {
+                let version = font.top_dict_data().unwrap().version;
+                (
+                    font.name().unwrap(),
+                    version.unwrap(),
+                    font.charset().is_some(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            fonts,
+            [
+                (
+                    "NotoSerifDisplay-Regular".to_string(),
+                    "2.9".to_string(),
+                    true
+                ),
+                ("NotoSerifDisplay-Copy".to_string(), "4.0".to_string(), true),
+            ]
+        );
+    }
+
+    #[test]
+    fn edit_second_font_shares_strings() {
+        let mut cff = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        cff.names.push(b"NotoSerifDisplay-Copy");
+        cff.top_dicts
+            .push(cff.get_top_dict_bytes(0).unwrap().to_vec().as_slice());
+        cff.font_tables.push(cff.font_tables[0].clone());
+        let string_count = cff.strings.len();
+        for font_index in [1, 0] {
+            let mut top_dict = cff.get_top_dict_data(font_index).unwrap();
+            top_dict.version = Some("5.0".into());
+            cff.set_top_dict_data(font_index, &top_dict).unwrap();
+        }
+        // both fonts reference the same new string
+        assert_eq!(cff.strings.len(), string_count + 1);
+
+        let bytes = assert_cff_roundtrip(&cff);
+        let reparsed = Cff::read(FontData::new(&bytes)).unwrap();
+        for font_index in [0, 1] {
+            let top_dict = reparsed.get_top_dict_data(font_index).unwrap();
+            assert_eq!(top_dict.version.as_deref(), Some("5.0"));
+        }
+        let version_sid = |font_index| {
+            dict::parse(reparsed.get_top_dict_bytes(font_index).unwrap())
+                .unwrap()
+                .into_iter()
+                .find(|entry| entry.key == DictKey::Version)
+                .unwrap()
+                .operands
+        };
+        assert_eq!(version_sid(0), version_sid(1));
+    }
+
+    #[test]
+    fn merge_fonts() {
+        let serif = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        let mut sans = owned(font_test_data::NOTO_SANS_JP_CFF);
+        // the custom string identifiers of both fonts overlap
+        let version = sans.add_string("2.004").unwrap();
+        sans.set_top_dict_operator(
+            0,
+            DictKey::Version,
+            vec![Operand::Integer(version.to_u16() as i32)],
+        )
+        .unwrap();
+        let outlines = |cff: &Cff, font_index: usize| {
+            (0..cff.font_tables[font_index].char_strings.len() as u16)
+                .map(|gid| {
+                    let mut pen = PenRecorder::default();
+                    cff.outline(font_index, gid, &mut pen).unwrap();
+                    pen.0
+                })
+                .collect::<Vec<_>>()
+        };
+        let expected = [outlines(&serif, 0), outlines(&sans, 0)];
+        // the Top DICT values, with the registry and ordering resolved and
+        // excluding offsets
+        let top_dict = |cff: &Cff, font_index: usize| {
+            let mut data = cff.get_top_dict_data(font_index).unwrap();
+            let ros = data
+                .raw_entries
+                .iter()
+                .find(|entry| entry.key == DictKey::Ros);
+            let ros = ros.map(|entry| {
+                entry.operands[..2]
+                    .iter()
+                    .map(|sid| {
+                        let sid = StringId::new(sid.to_i32().unwrap() as u16);
+                        cff.resolve_string(sid).unwrap()
+                    })
+                    .collect::<Vec<_>>()
+            });
+            data.raw_entries.retain(|entry| {
+                ![
+                    DictKey::Ros,
+                    DictKey::Charset,
+                    DictKey::CharstringsOffset,
+                    DictKey::PrivateDictRange,
+                    DictKey::FdArrayOffset,
+                    DictKey::FdSelectOffset,
+                ]
+                .contains(&entry.key)
+            });
+            (data, ros)
+        };
+        let top_dicts = [top_dict(&serif, 0), top_dict(&sans, 0)];
+        let global_subr_count = serif.global_subrs.len() + sans.global_subrs.len();
+
+        let merged = Cff::merge_fonts(serif, sans).unwrap();
+        assert_eq!(merged.global_subrs.len(), global_subr_count);
+        let bytes = assert_cff_roundtrip(&merged);
+        let reparsed = Cff::read(FontData::new(&bytes)).unwrap();
+        let names = reparsed
+            .fonts()
+            .map(|font| font.name().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["NotoSerifDisplay-Regular", "NotoSansCJKjp-Regular"]);
+        assert_eq!(top_dicts[0].0.version.as_deref(), Some("2.9"));
+        assert_eq!(top_dicts[1].0.version.as_deref(), Some("2.004"));
+        assert_eq!(
+            top_dicts[1].1.as_deref(),
+            Some(&["Adobe".into(), "Identity".into()][..])
+        );
+        for (font_index, expected) in top_dicts.iter().enumerate() {
+            assert_eq!(&top_dict(&reparsed, font_index), expected);
+        }
+        for (font_index, expected) in expected.iter().enumerate() {
+            assert_eq!(&outlines(&reparsed, font_index), expected);
+        }
+    }
+
+    #[test]
+    fn merge_fonts_with_different_variation_stores() {
+        let serif = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        let mut other = serif.clone();
+        other.variation_store = Some(vec![0; 8]);
+        assert!(matches!(
+            Cff::merge_fonts(serif, other),
+            Err(CffError::IncompatibleVariationStores)
+        ));
+    }
+
+    #[test]
+    fn large_top_dict_values() {
+        let mut cff = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        let mut top_dict = cff.get_top_dict_data(0).unwrap();
+        let unique_id = Entry::new(DictKey::UniqueId, vec![Operand::Integer(0x01FF_FFFF)]);
+        top_dict.raw_entries.push(unique_id);
+        cff.set_top_dict_data(0, &top_dict).unwrap();
+
+        let bytes = assert_cff_roundtrip(&cff);
+        let reparsed = Cff::read(FontData::new(&bytes)).unwrap();
+        let top_dict = reparsed.get_top_dict_data(0).unwrap();
+        assert_eq!(top_dict.unique_id, Some(0x01FF_FFFF));
+    }
+
+    #[test]
+    fn synthesize_empty_private_dict() {
+        let mut cff = Cff {
+            header: CffHeader {
+                off_size: 4,
+                ..Default::default()
+            },
+            names: Index1::from_bytes(["Empty"]),
+            top_dicts: Index1::from_bytes([[]]),
+            font_tables: vec![FontTables {
+                // a single endchar
+                char_strings: Index1::from_bytes([[14]]),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let top_dict = TopDictData {
+            full_name: Some("Empty".into()),
+            ..Default::default()
+        };
+        cff.set_top_dict_data(0, &top_dict).unwrap();
+
+        let bytes = assert_cff_roundtrip(&cff);
+        let read = read_fonts::tables::cff::Cff::read(FontData::new(&bytes)).unwrap();
+        let top_dict = read.top_dicts().get(0).unwrap();
+        let range = read_fonts::tables::postscript::dict::entries(top_dict, None)
+            .find_map(|entry| match entry.unwrap() {
+                read_fonts::tables::postscript::dict::Entry::PrivateDictRange(range) => Some(range),
+                _ => None,
+            })
+            .unwrap();
+        assert!(range.is_empty());
+        assert!(range.end <= bytes.len());
+        assert_eq!(outlines(&bytes), [vec![] as Vec<String>]);
+    }
+
+    #[test]
+    fn charstrings_offset_is_recomputed() {
+        let source = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        let source_bytes = crate::dump_table(&source).unwrap();
+        let mut cff = source.clone();
+        let wrong_offset = dict::rewrite(
+            cff.get_top_dict_bytes(0).unwrap(),
+            &[(DictKey::CharstringsOffset, Some(encoded_offset(12345)))],
+        );
+        cff.top_dicts = Index1::from_bytes([wrong_offset]);
+
+        let bytes = assert_cff_roundtrip(&cff);
+        assert_eq!(bytes, source_bytes);
+        assert_eq!(outlines(&bytes), outlines(&source_bytes));
+    }
+
+    #[test]
+    fn subtables_after_global_subrs() {
+        let charstrings_offset = |top_dict: &[u8]| {
+            offset_operand(&dict::parse(top_dict).unwrap(), DictKey::CharstringsOffset).unwrap()
+        };
+        for font_data in [
+            font_test_data::NOTO_SERIF_DISPLAY_TRIMMED,
+            font_test_data::NOTO_SANS_JP_CFF,
+        ] {
+            let font = FontRef::new(font_data).unwrap();
+            let source = font.cff().unwrap();
+            let global_subrs_end = source.header().hdr_size() as usize
+                + source.names().size_in_bytes().unwrap()
+                + source.top_dicts().size_in_bytes().unwrap()
+                + source.strings().size_in_bytes().unwrap()
+                + source.global_subrs().size_in_bytes().unwrap();
+            // the charstrings are only reachable through the Top DICT
+            let offset = charstrings_offset(source.top_dicts().get(0).unwrap());
+            assert!(offset >= global_subrs_end);
+            let source_charstrings = read_fonts::tables::postscript::Index::new(
+                &source.offset_data().as_bytes()[offset..],
+                false,
+            )
+            .unwrap();
+
+            let cff: Cff = source.to_owned_table();
+            let bytes = assert_cff_roundtrip(&cff);
+            let layout = cff.layout();
+            let reparsed = Cff::read(FontData::new(&bytes)).unwrap();
+            let offset = charstrings_offset(reparsed.get_top_dict_bytes(0).unwrap());
+            assert!(offset >= layout.global_subrs.end);
+            assert!(layout.fonts[0].contains(&offset));
+            assert_eq!(
+                reparsed.font_tables[0].char_strings.len(),
+                source_charstrings.count() as usize
+            );
+            for gid in 0..source_charstrings.count() as usize {
+                assert_eq!(
+                    reparsed.charstring_bytes(0, gid as u16).unwrap(),
+                    source_charstrings.get(gid).unwrap()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn read_reports_subtable_errors() {
+        let cff = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        let bytes = crate::dump_table(&cff).unwrap();
+        // point the charstrings past the end of the table, keeping the length
+        // of the Top DICT
+        let layout = cff.layout();
+        let top_dict_start = layout.top_dicts.end - cff.top_dicts.get(0).unwrap().len();
+        let top_dict = &bytes[top_dict_start..layout.top_dicts.end];
+        let patched = dict::rewrite(
+            top_dict,
+            &[(
+                DictKey::CharstringsOffset,
+                Some(encoded_offset(bytes.len())),
+            )],
+        );
+        assert_eq!(patched.len(), top_dict.len());
+        let mut corrupt = bytes.clone();
+        corrupt.splice(top_dict_start..layout.top_dicts.end, patched);
+
+        assert!(Cff::read(FontData::new(&bytes)).is_ok());
+        assert!(Cff::read(FontData::new(&corrupt)).is_err());
+        // the conversions that can't fail leave the font empty
+        let source = read_fonts::tables::cff::Cff::read(FontData::new(&corrupt)).unwrap();
+        let lossy: Cff = source.to_owned_table();
+        assert!(lossy.font_tables[0].char_strings.is_empty());
+        assert!(Cff::borrow_from(&source).font_tables[0]
+            .char_strings
+            .is_empty());
+    }
+
+    #[test]
+    fn read_rejects_overlapping_strings() {
+        let cff = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        assert!(cff.strings.len() >= 2);
+        assert_eq!(cff.strings.off_size, 1);
+        let mut corrupt = crate::dump_table(&cff).unwrap();
+        // the count is followed by the offset size and the offsets; make the
+        // third offset precede the second, so that the strings overlap
+        let offsets = cff.layout().strings.start + 3;
+        corrupt[offsets + 2] = corrupt[offsets + 1] - 1;
+
+        assert!(Cff::read(FontData::new(&corrupt)).is_err());
+        let source = read_fonts::tables::cff::Cff::read(FontData::new(&corrupt)).unwrap();
+        let lossy: Cff = source.to_owned_table();
+        assert!(lossy.strings.check_wellformed().is_err());
+    }
+
+    #[test]
+    fn duplicate_charset_ids_fail_validation() {
+        let mut cff = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        assert!(cff.validate().is_ok());
+        let mut sids = cff.charset_sids(0).unwrap();
+        sids[2] = sids[0];
+        cff.font_tables[0].charset = Some(CustomCharset::from_sids(&sids));
+        let error = cff.validate().unwrap_err().to_string();
+        assert!(error.contains("duplicate string id"), "{error}");
+
+        // .notdef is always CID 0
+        let mut cff = owned(font_test_data::NOTO_SANS_JP_CFF);
+        let mut cids = cff.charset_sids(0).unwrap();
+        cids[1] = 0;
+        cff.font_tables[0].charset = Some(CustomCharset::from_sids(&cids));
+        let error = cff.validate().unwrap_err().to_string();
+        assert!(error.contains("duplicate CID 0"), "{error}");
+    }
+
+    #[test]
+    fn top_dict_operator_span() {
+        let cff = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        let bytes = crate::dump_table(&cff).unwrap();
+        let cff = Cff::read(FontData::new(&bytes)).unwrap();
+        let span = cff
+            .top_dict_operator_span(0, DictKey::CharstringsOffset)
+            .unwrap()
+            .unwrap();
+        // a five byte integer and the operator
+        assert_eq!(span.len(), 6);
+        let top_dict = cff.get_top_dict_bytes(0).unwrap();
+        let entries = dict::parse(&top_dict[span.clone()]).unwrap();
+        let offset = offset_operand(&entries, DictKey::CharstringsOffset).unwrap();
+        assert_eq!(entries.len(), 1);
+        let charstrings = Index1::read(FontData::new(&bytes[offset..])).unwrap();
+        assert_eq!(charstrings, cff.font_tables[0].char_strings);
+
+        // patching the operand in place keeps the rest of the DICT
+        let mut patched = top_dict.to_vec();
+        patched.splice(
+            span.clone(),
+            dict::rewrite(
+                &top_dict[span],
+                &[(DictKey::CharstringsOffset, Some(encoded_offset(offset + 1)))],
+            ),
+        );
+        assert_eq!(patched.len(), top_dict.len());
+        assert_eq!(
+            offset_operand(&dict::parse(&patched).unwrap(), DictKey::CharstringsOffset),
+            Some(offset + 1)
+        );
+
+        assert_eq!(cff.top_dict_operator_span(0, DictKey::Xuid).unwrap(), None);
+        assert!(cff
+            .top_dict_operator_span(1, DictKey::CharstringsOffset)
+            .is_err());
+    }
+
+    #[test]
+    fn zero_index_offset() {
+        // offsets are one-based, so a zero offset is invalid
+        let malformed = Index1 {
+            count: 1,
+            off_size: 4,
+            offsets: vec![0, 0, 0, 0, 0, 0, 0, 2],
+            data: vec![1, 2],
+        };
+        let cff = Cff {
+            top_dicts: malformed.clone(),
+            strings: malformed,
+            ..Default::default()
+        };
+        assert!(matches!(
+            cff.get_top_dict_bytes(0),
+            Err(CffError::InvalidOffsetRange)
+        ));
+        assert!(matches!(
+            cff.get_top_dict_bytes(1),
+            Err(CffError::FontIndexOutOfBounds(1))
+        ));
+        assert!(matches!(
+            cff.get_string_bytes(StringId::new(391)),
+            Err(CffError::InvalidOffsetRange)
+        ));
+        assert!(matches!(
+            cff.get_string_bytes(StringId::new(392)),
+            Err(CffError::InvalidStringId(_))
+        ));
+        assert!(cff.get_top_dict_data(0).is_err());
+    }
+
+    #[test]
+    fn repack_removes_orphaned_strings() {
+        let mut cff = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        let source_bytes = crate::dump_table(&cff).unwrap();
+        for version in ["3.0", "3.1"] {
+            let mut top_dict = cff.get_top_dict_data(0).unwrap();
+            top_dict.version = Some(version.into());
+            cff.set_top_dict_data(0, &top_dict).unwrap();
+        }
+        // a duplicate of a standard string
+        cff.strings.push(b"space");
+        assert_eq!(cff.strings.len(), 8);
+
+        cff.repack().unwrap();
+        // "2.9" and "3.0" are no longer referenced
+        assert_eq!(cff.strings.len(), 5);
+        let bytes = assert_cff_roundtrip(&cff);
+        let reparsed = Cff::read(FontData::new(&bytes)).unwrap();
+        let top_dict = reparsed.get_top_dict_data(0).unwrap();
+        assert_eq!(top_dict.version.as_deref(), Some("3.1"));
+        assert_eq!(top_dict.family_name.as_deref(), Some("Noto Serif Display"));
+        assert_eq!(outlines(&bytes), outlines(&source_bytes));
+        assert_eq!(reparsed.font_tables[0].charset, cff.font_tables[0].charset);
+    }
+
+    #[test]
+    fn clear_metadata_strings() {
+        let mut cff = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        let string_count = cff.strings.len();
+        cff.clear_metadata_strings().unwrap();
+        assert!(cff.strings.len() < string_count);
+
+        let bytes = assert_cff_roundtrip(&cff);
+        let reparsed = Cff::read(FontData::new(&bytes)).unwrap();
+        let top_dict = reparsed.get_top_dict_data(0).unwrap();
+        assert_eq!(top_dict.version, None);
+        assert_eq!(top_dict.notice, None);
+        assert_eq!(top_dict.copyright, None);
+        assert_eq!(top_dict.full_name, None);
+        assert_eq!(top_dict.family_name, None);
+        assert_eq!(top_dict.weight, None);
+        assert!(top_dict.font_bbox.is_some());
+        assert_eq!(
+            reparsed.fonts().next().unwrap().name().as_deref(),
+            Some("NotoSerifDisplay-Regular")
+        );
+    }
+
+    #[test]
+    fn edit_font_bbox() {
+        let mut cff = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        let mut top_dict = cff.get_top_dict_data(0).unwrap();
+        assert!(top_dict.font_bbox.is_some());
+        assert!(top_dict
+            .raw_entries
+            .iter()
+            .all(|entry| entry.key != DictKey::FontBbox));
+        top_dict.font_bbox = Some([-120.0, -250.5, 1000.0, 1100.0]);
+        cff.set_top_dict_data(0, &top_dict).unwrap();
+
+        let bytes = assert_cff_roundtrip(&cff);
+        let reparsed = Cff::read(FontData::new(&bytes)).unwrap();
+        assert_eq!(
+            reparsed.get_top_dict_data(0).unwrap().font_bbox,
+            Some([-120.0, -250.5, 1000.0, 1100.0])
+        );
+    }
+
+    #[test]
+    fn summary() {
+        let summary = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED).summary();
+        assert!(
+            summary.contains("family name: Noto Serif Display"),
+            "{summary}"
+        );
+        assert!(summary.contains("version: 2.9"), "{summary}");
+        assert!(summary.contains("glyphs: 5"), "{summary}");
+        assert!(summary.contains("local subrs: 2"), "{summary}");
+        assert!(summary.contains("CID-keyed: false"), "{summary}");
+
+        let summary = owned(font_test_data::NOTO_SANS_JP_CFF).summary();
+        assert!(summary.contains("CID-keyed: true"), "{summary}");
+        assert!(summary.contains("font dicts: 2"), "{summary}");
+    }
+
+    #[test]
+    fn real_top_dict_operands() {
+        let mut cff = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        let mut top_dict = cff.get_top_dict_data(0).unwrap();
+        let matrix = [0.0005, 0.0, 0.0, 0.0005, 0.0, 0.0];
+        top_dict.font_matrix = Some(matrix);
+        cff.set_top_dict_data(0, &top_dict).unwrap();
+        let top_dict_bytes = cff.get_top_dict_bytes(0).unwrap();
+        // 0.0005 is encoded as 5E-4 and zero as an integer
+        let encoded = [
+            0x1e, 0x5c, 0x4f, 139, 139, 0x1e, 0x5c, 0x4f, 139, 139, 12, 7,
+        ];
+        assert!(top_dict_bytes
+            .windows(encoded.len())
+            .any(|window| window == encoded));
+
+        let bytes = assert_cff_roundtrip(&cff);
+        let reparsed = Cff::read(FontData::new(&bytes)).unwrap();
+        let top_dict = reparsed.get_top_dict_data(0).unwrap();
+        assert_eq!(top_dict.font_matrix, Some(matrix));
+    }
+
+    #[test]
+    fn set_units_per_em() {
+        let mut top_dict = TopDictData::default();
+        top_dict.set_units_per_em(1000.0);
+        let default = DictKey::FontMatrix.top_dict_default().unwrap();
+        assert_eq!(top_dict.font_matrix.unwrap(), default);
+
+        let mut cff = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        let mut top_dict = cff.get_top_dict_data(0).unwrap();
+        top_dict.set_units_per_em(2048.0);
+        cff.set_top_dict_data(0, &top_dict).unwrap();
+        let bytes = assert_cff_roundtrip(&cff);
+        let reparsed = Cff::read(FontData::new(&bytes)).unwrap();
+        let matrix = reparsed.get_top_dict_data(0).unwrap().font_matrix.unwrap();
+        assert!((matrix[0] * 2048.0 - 1.0).abs() < 1e-6);
+        assert_eq!(matrix[0], matrix[3]);
+    }
+
+    #[test]
+    fn reject_degenerate_font_matrix() {
+        let mut cff = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        let mut top_dict = cff.get_top_dict_data(0).unwrap();
+        top_dict.font_matrix = Some([0.001, 0.002, 0.0005, 0.001, 0.0, 0.0]);
+        assert!(matches!(
+            cff.set_top_dict_data(0, &top_dict),
+            Err(CffError::DegenerateFontMatrix)
+        ));
+    }
+
+    #[test]
+    fn invalid_operand_count_fails_charstring_validation() {
+        let mut cff = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        assert!(cff.validate().is_ok());
+        // rmoveto, then an rrcurveto with 5 operands
+        let mut charstring = vec![139, 139, 21];
+        charstring.extend([139; 5]);
+        charstring.extend([8, 14]);
+        let mut char_strings = cff.font_tables[0]
+            .char_strings
+            .iter()
+            .map(<[u8]>::to_vec)
+            .collect::<Vec<_>>();
+        char_strings[2] = charstring;
+        cff.font_tables[0].char_strings = Index1::from_bytes(char_strings);
+        let report = cff.validate_charstrings().unwrap_err().to_string();
+        assert!(
+            report.contains("glyph 2 of font 0 has 5 operands for RrCurveTo"),
+            "{report}"
+        );
+        // charstrings aren't interpreted when the table is written
+        assert_cff_roundtrip(&cff);
+    }
+
+    #[test]
+    fn fd_select_coverage_fails_validation() {
+        let mut cff = owned(font_test_data::NOTO_SANS_JP_CFF);
+        assert!(cff.validate().is_ok());
+        let num_glyphs = cff.font_tables[0].char_strings.len();
+        let fds = (0..num_glyphs)
+            .map(|gid| cff.fd_index(0, gid as u16).unwrap())
+            .collect::<Vec<_>>();
+
+        // a truncated FDSelect leaves the last glyphs unmapped
+        cff.font_tables[0].fd_select = Some(FdSelect::format_0(fds[..10].to_vec()));
+        let report = cff.validate().unwrap_err().to_string();
+        assert!(
+            report.contains(&format!(
+                "CID-keyed font 0 has an FDSelect covering 10 glyphs rather than {num_glyphs}"
+            )),
+            "{report}"
+        );
+        cff.font_tables[0].fd_select = Some(FdSelect::format_3(
+            vec![FdSelectRange3::new(0, fds[0])],
+            num_glyphs as u16 + 1,
+        ));
+        let report = cff.validate().unwrap_err().to_string();
+        assert!(report.contains("FDSelect sentinel"), "{report}");
+        // a gap before the first range
+        cff.font_tables[0].fd_select = Some(FdSelect::format_3(
+            vec![FdSelectRange3::new(1, fds[0])],
+            num_glyphs as u16,
+        ));
+        let report = cff.validate().unwrap_err().to_string();
+        assert!(
+            report.contains("FDSelect range starting at glyph 1"),
+            "{report}"
+        );
+        cff.font_tables[0].fd_select = None;
+        let report = cff.validate().unwrap_err().to_string();
+        assert!(report.contains("has no FDSelect"), "{report}");
+
+        cff.font_tables[0].fd_select = Some(FdSelect::from_fds(&fds));
+        assert!(cff.validate().is_ok());
+    }
+
+    #[test]
+    fn private_dict_out_of_bounds() {
+        let read_error = |bytes: &[u8]| match Cff::read(FontData::new(bytes)) {
+            Err(ReadError::MalformedData(message)) => message,
+            other => panic!("expected malformed data, got {other:?}"),
+        };
+        let cff = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        let bytes = crate::dump_table(&cff).unwrap();
+
+        // move the Private DICT past the end of the table, keeping the
+        // length of the Top DICT
+        let layout = cff.layout();
+        let top_dict_start = layout.top_dicts.end - cff.top_dicts.get(0).unwrap().len();
+        let top_dict = &bytes[top_dict_start..layout.top_dicts.end];
+        let (size, _) = private_dict_range(&dict::parse(top_dict).unwrap()).unwrap();
+        let patched = dict::rewrite(
+            top_dict,
+            &[(
+                DictKey::PrivateDictRange,
+                Some(encoded_private_range(size, bytes.len())),
+            )],
+        );
+        assert_eq!(patched.len(), top_dict.len());
+        let mut corrupt = bytes.clone();
+        corrupt.splice(top_dict_start..layout.top_dicts.end, patched);
+        assert!(read_error(&corrupt).contains("Private DICT"));
+        // the lossy conversion drops the subtables of the font
+        let source = read_fonts::tables::cff::Cff::read(FontData::new(&corrupt)).unwrap();
+        let lossy: Cff = source.to_owned_table();
+        assert_eq!(lossy.font_tables[0].private_dict, None);
+
+        // and the local subroutines of a CID-keyed font
+        let mut cff = owned(font_test_data::NOTO_SANS_JP_CFF);
+        cff.set_local_subrs(0, 0, Some(Index1::from_bytes(vec![vec![11]])))
+            .unwrap();
+        let bytes = crate::dump_table(&cff).unwrap();
+        let reparsed = Cff::read(FontData::new(&bytes)).unwrap();
+        let font_dict = dict::parse(&reparsed.font_tables[0].fd_array[0].dict).unwrap();
+        let (size, offset) = private_dict_range(&font_dict).unwrap();
+        let private = &bytes[offset..offset + size];
+        let patched = dict::rewrite(
+            private,
+            &[(DictKey::SubrsOffset, Some(encoded_offset(bytes.len())))],
+        );
+        assert_eq!(patched.len(), size);
+        let mut corrupt = bytes.clone();
+        corrupt.splice(offset..offset + size, patched);
+        assert!(read_error(&corrupt).contains("local subroutines"));
+    }
+
+    #[test]
+    fn name_count_mismatch_fails_validation() {
+        let mut cff = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        assert!(cff.validate().is_ok());
+        cff.names.push(b"Extra");
+        let report = cff.validate().unwrap_err().to_string();
+        assert!(report.contains("Name INDEX has 2 entries"), "{report}");
+        assert!(crate::dump_table(&cff).is_err());
+        cff.names = Index1::default();
+        assert!(cff.validate().is_err());
+    }
+
+    #[test]
+    fn degenerate_font_matrix_fails_validation() {
+        let mut cff = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        let mut entries = dict::parse(cff.get_top_dict_bytes(0).unwrap()).unwrap();
+        entries.retain(|entry| entry.key != DictKey::FontMatrix);
+        for values in [vec![0; 6], vec![1, 0, 0, 1]] {
+            let operands = values.into_iter().map(Operand::Integer).collect();
+            let mut with_matrix = entries.clone();
+            with_matrix.push(Entry::new(DictKey::FontMatrix, operands));
+            cff.top_dicts = Index1::from_bytes([dict::encode(&with_matrix)]);
+            assert!(crate::dump_table(&cff).is_err());
+        }
+        cff.top_dicts = Index1::from_bytes([dict::encode(&entries)]);
+        assert!(crate::dump_table(&cff).is_ok());
+    }
+
+    #[test]
+    fn preserve_top_dict_order() {
+        let mut cff = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        let source_bytes = crate::dump_table(&cff).unwrap();
+        let top_dict = cff.get_top_dict_bytes(0).unwrap();
+        let mut entries = dict::parse(top_dict).unwrap();
+        entries.reverse();
+        let reversed = entries.iter().map(|entry| entry.key).collect::<Vec<_>>();
+        assert!(reversed.contains(&DictKey::FamilyName));
+        cff.top_dicts = Index1::from_bytes([dict::encode(&entries)]);
+
+        let mut top_dict = cff.get_top_dict_data(0).unwrap();
+        assert_eq!(top_dict.operator_order, reversed);
+        top_dict.family_name = Some("Noto Serif Reversed".into());
+        top_dict.raw_entries.push(Entry::new(
+            DictKey::ItalicAngle,
+            vec![Operand::Integer(-12)],
+        ));
+        cff.set_top_dict_data(0, &top_dict).unwrap();
+
+        let bytes = assert_cff_roundtrip(&cff);
+        let reparsed = Cff::read(FontData::new(&bytes)).unwrap();
+        let keys = dict::parse(reparsed.get_top_dict_bytes(0).unwrap())
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.key)
+            .collect::<Vec<_>>();
+        assert_eq!(keys[..reversed.len()], reversed);
+        assert_eq!(keys[reversed.len()..], [DictKey::ItalicAngle]);
+        let top_dict = reparsed.get_top_dict_data(0).unwrap();
+        assert_eq!(top_dict.family_name.as_deref(), Some("Noto Serif Reversed"));
+        assert_eq!(outlines(&bytes), outlines(&source_bytes));
+    }
+
+    #[test]
+    fn scale_outlines() {
+        let mut cff = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        let source_bytes = crate::dump_table(&cff).unwrap();
+        let source_private = cff.get_private_dict_data(0).unwrap();
+        cff.scale_outlines(2.0).unwrap();
+
+        let bytes = assert_cff_roundtrip(&cff);
+        let doubled = evaluate::<Bounds>(&source_bytes)
+            .into_iter()
+            .map(|bounds| Bounds(bounds.0.map(|b| b.map(|value| value * 2.0))))
+            .collect::<Vec<_>>();
+        assert!(doubled.iter().any(|bounds| bounds.0.is_some()));
+        assert_eq!(evaluate::<Bounds>(&bytes), doubled);
+
+        let reparsed = Cff::read(FontData::new(&bytes)).unwrap();
+        let source_bbox = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED)
+            .get_top_dict_data(0)
+            .unwrap()
+            .font_bbox
+            .unwrap();
+        let top_dict = reparsed.get_top_dict_data(0).unwrap();
+        assert_eq!(
+            top_dict.font_bbox,
+            Some(source_bbox.map(|value| value * 2.0))
+        );
+        let private = reparsed.get_private_dict_data(0).unwrap();
+        assert!(!private.blue_values.is_empty());
+        let doubled_blues = source_private
+            .blue_values
+            .iter()
+            .map(|value| value.default * 2.0)
+            .collect::<Vec<_>>();
+        let blues = private
+            .blue_values
+            .iter()
+            .map(|value| value.default)
+            .collect::<Vec<_>>();
+        assert_eq!(blues, doubled_blues);
+        assert_eq!(private.blue_scale, source_private.blue_scale);
+    }
+
+    #[test]
+    fn strip_hints() {
+        let is_hint = |token: &Token| {
+            matches!(
+                token,
+                Token::HintMask(_)
+                    | Token::CntrMask(_)
+                    | Token::Operator(
+                        Operator::HStem | Operator::VStem | Operator::HStemHm | Operator::VStemHm
+                    )
+            )
+        };
+        let has_hints = |cff: &Cff| {
+            let mut found = false;
+            cff.parse_charstrings()
+                .unwrap()
+                .for_each_mut(|tokens| found |= tokens.iter().any(is_hint));
+            found
+        };
+
+        // the trimmed font has no hints, so add some after each width
+        let mut cff = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        let source_bytes = crate::dump_table(&cff).unwrap();
+        let mut parsed = cff.parse_charstrings().unwrap();
+        for (_, tokens) in &mut parsed.char_strings[0] {
+            let mut operand_count = tokens
+                .iter()
+                .position(|token| !matches!(token, Token::Operand(_)))
+                .unwrap();
+            // the subroutine number isn't part of a width
+            if matches!(
+                tokens[operand_count],
+                Token::Operator(Operator::CallSubr | Operator::CallGsubr)
+            ) {
+                operand_count -= 1;
+            }
+            let hints = [10, 20]
+                .map(|value| Token::Operand(CharstringOperand::Integer(value)))
+                .into_iter()
+                .chain([
+                    Token::Operator(Operator::HStemHm),
+                    Token::HintMask(vec![0b1000_0000]),
+                    Token::CntrMask(vec![0b1000_0000]),
+                ]);
+            let at = operand_count % 2;
+            tokens.splice(at..at, hints);
+        }
+        cff.set_parsed_charstrings(parsed);
+        assert!(has_hints(&cff));
+        assert_eq!(
+            outlines(&crate::dump_table(&cff).unwrap()),
+            outlines(&source_bytes)
+        );
+        cff.strip_hints().unwrap();
+
+        let bytes = assert_cff_roundtrip(&cff);
+        let reparsed = Cff::read(FontData::new(&bytes)).unwrap();
+        assert!(!has_hints(&reparsed));
+        assert_eq!(outlines(&bytes), outlines(&source_bytes));
+        let private = reparsed.get_private_dict_data(0).unwrap();
+        assert!(private.blue_values.is_empty());
+        assert!(private.std_hw.is_none() && private.stem_snap_h.is_empty());
+
+        // hints declared in subroutines
+        let mut cff = owned(font_test_data::NOTO_SANS_JP_CFF);
+        assert!(has_hints(&cff));
+        cff.strip_hints().unwrap();
+        let bytes = assert_cff_roundtrip(&cff);
+        let reparsed = Cff::read(FontData::new(&bytes)).unwrap();
+        assert!(!has_hints(&reparsed));
+        let private = reparsed.font_tables[0].fd_array[0].private_dict.as_ref();
+        let private = PrivateDictData::parse(&private.unwrap().dict, &[]).unwrap();
+        assert!(private.blue_values.is_empty());
+    }
+
+    #[test]
+    fn set_top_dict_data_cid_keyed() {
+        let mut cff = owned(font_test_data::NOTO_SANS_JP_CFF);
+        let source_top_dict = cff.get_top_dict_bytes(0).unwrap().to_vec();
+        let mut top_dict = cff.get_top_dict_data(0).unwrap();
+        cff.set_top_dict_data(0, &top_dict).unwrap();
+        assert_eq!(cff.get_top_dict_bytes(0).unwrap(), source_top_dict);
+
+        // the ROS is kept, and stays first, when it isn't in the raw entries
+        top_dict.version = Some("2.0".into());
+        top_dict
+            .raw_entries
+            .retain(|entry| entry.key != DictKey::Ros);
+        top_dict.operator_order.clear();
+        cff.set_top_dict_data(0, &top_dict).unwrap();
+        let entries = dict::parse(cff.get_top_dict_bytes(0).unwrap()).unwrap();
+        assert_eq!(entries[0].key, DictKey::Ros);
+        let bytes = assert_cff_roundtrip(&cff);
+        let reparsed = Cff::read(FontData::new(&bytes)).unwrap();
+        assert!(reparsed.is_cid_keyed());
+        let reparsed = reparsed.get_top_dict_data(0).unwrap();
+        assert_eq!(reparsed.version.as_deref(), Some("2.0"));
+    }
+
+    #[test]
+    fn charstring_bytes() {
+        use read_fonts::tables::postscript::{dict, Index};
+        let font = FontRef::new(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED).unwrap();
+        let source = font.cff().unwrap();
+        let offset = dict::entries(source.top_dicts().get(0).unwrap(), None)
+            .find_map(|entry| match entry {
+                Ok(dict::Entry::CharstringsOffset(offset)) => Some(offset),
+                _ => None,
+            })
+            .unwrap();
+        let data = source.offset_data();
+        let charstrings = Index::new(&data.as_bytes()[offset..], false).unwrap();
+
+        let cff: Cff = source.to_owned_table();
+        let notdef = cff.charstring_bytes(0, 0).unwrap();
+        assert!(!notdef.is_empty());
+        assert_eq!(notdef, charstrings.get(0).unwrap());
+        assert_eq!(
+            cff.fonts().next().unwrap().charstring_bytes(0).unwrap(),
+            notdef
+        );
+        assert!(matches!(
+            cff.charstring_bytes(0, 5),
+            Err(CffError::GlyphIndexOutOfBounds(5))
+        ));
+        assert!(matches!(
+            cff.charstring_bytes(1, 0),
+            Err(CffError::FontIndexOutOfBounds(1))
+        ));
+    }
+
+    #[test]
+    fn remove_unused_subrs_after_hintmask() {
+        use crate::tables::postscript::charstring::{encode, Operand, Operator};
+        let int = |value| Token::Operand(Operand::Integer(value));
+        let mut cff = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        let font = &mut cff.font_tables[0];
+        let subrs = font.private_dict.as_mut().unwrap().subrs.as_mut().unwrap();
+        assert_eq!(subrs.len(), 2);
+        // 2: declares nine stems, so a following hintmask has two bytes
+        let mut stems = vec![int(10); 18];
+        stems.extend([
+            Token::Operator(Operator::HStemHm),
+            Token::Operator(Operator::Return),
+        ]);
+        subrs.push(&encode(&stems));
+        // 3: never called
+        subrs.push(&encode(&[Token::Operator(Operator::Return)]));
+        // 4: draws three sides of a square
+        subrs.push(&encode(&[
+            int(100),
+            int(0),
+            Token::Operator(Operator::RLineTo),
+            int(0),
+            int(100),
+            Token::Operator(Operator::RLineTo),
+            int(-100),
+            int(0),
+            Token::Operator(Operator::RLineTo),
+            Token::Operator(Operator::Return),
+        ]));
+        // the second mask byte is the callsubr opcode
+        let glyph = encode(&[
+            int(-105),
+            Token::Operator(Operator::CallSubr),
+            Token::HintMask(vec![0xFF, 10]),
+            int(0),
+            int(0),
+            Token::Operator(Operator::RMoveTo),
+            int(-103),
+            Token::Operator(Operator::CallSubr),
+            Token::Operator(Operator::EndChar),
+        ]);
+        font.char_strings =
+            Index1::from_bytes(font.char_strings.iter().enumerate().map(|(gid, data)| {
+                if gid == 1 {
+                    glyph.as_slice()
+                } else {
+                    data
+                }
+            }));
+        let source_outlines = outlines(&crate::dump_table(&cff).unwrap());
+
+        cff.remove_unused_subrs().unwrap();
+        let subrs = cff.font_tables[0]
+            .private_dict
+            .as_ref()
+            .and_then(|private| private.subrs.as_ref())
+            .unwrap();
+        assert!(subrs.len() < 5);
+        assert!(!subrs.iter().any(|subr| subr == [11]));
+        assert!(cff.global_subrs.len() <= 17);
+        let bytes = assert_cff_roundtrip(&cff);
+        assert_eq!(outlines(&bytes), source_outlines);
+    }
+
+    #[test]
+    fn set_top_dict_data_preserves_entries() {
+        let mut cff = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        // an integer in the five byte form, which wouldn't be re-encoded
+        // the same way
+        let source = dict::rewrite(
+            cff.get_top_dict_bytes(0).unwrap(),
+            &[(DictKey::UnderlineThickness, Some(vec![29, 0, 0, 0, 60]))],
+        );
+        cff.top_dicts = Index1::from_bytes([source.as_slice()]);
+        let mut top_dict = cff.get_top_dict_data(0).unwrap();
+        top_dict.version = Some("3.0".into());
+        cff.set_top_dict_data(0, &top_dict).unwrap();
+
+        let source_entries = dict::parse_with_ranges(&source).unwrap();
+        let data = cff.get_top_dict_bytes(0).unwrap();
+        let entries = dict::parse_with_ranges(data).unwrap();
+        assert_eq!(entries.len(), source_entries.len());
+        for ((entry, range), (source_entry, source_range)) in entries.iter().zip(&source_entries) {
+            assert_eq!(entry.key, source_entry.key);
+            if entry.key != DictKey::Version {
+                assert_eq!(data[range.clone()], source[source_range.clone()]);
+            }
+        }
+        let version = &entries[0].0;
+        assert_eq!(version.key, DictKey::Version);
+        assert_ne!(version, &source_entries[0].0);
+    }
+
+    #[test]
+    fn set_top_dict_data_omits_defaults() {
+        let mut cff = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        let mut top_dict = cff.get_top_dict_data(0).unwrap();
+        top_dict.font_matrix = Some([0.001, 0.0, 0.0, 0.001, 0.0, 0.0]);
+        top_dict.raw_entries.extend([
+            Entry::new(DictKey::IsFixedPitch, vec![Operand::Integer(0)]),
+            Entry::new(DictKey::PaintType, vec![Operand::Integer(0)]),
+            Entry::new(DictKey::UnderlinePosition, vec![Operand::Real(-100.0)]),
+            Entry::new(DictKey::ItalicAngle, vec![Operand::Integer(-12)]),
+        ]);
+        cff.set_top_dict_data(0, &top_dict).unwrap();
+
+        let bytes = assert_cff_roundtrip(&cff);
+        let reparsed = Cff::read(FontData::new(&bytes)).unwrap();
+        let entries = dict::parse(reparsed.get_top_dict_bytes(0).unwrap()).unwrap();
+        let keys = entries.iter().map(|entry| entry.key).collect::<Vec<_>>();
+        for key in [
+            DictKey::IsFixedPitch,
+            DictKey::PaintType,
+            DictKey::UnderlinePosition,
+            DictKey::FontMatrix,
+        ] {
+            assert!(!keys.contains(&key), "{key:?}");
+        }
+        assert!(entries.contains(&Entry::new(
+            DictKey::ItalicAngle,
+            vec![Operand::Integer(-12)]
+        )));
+        let fixed_pitch = Entry::new(DictKey::IsFixedPitch, vec![Operand::Integer(1)]);
+        assert!(!fixed_pitch.is_top_dict_default());
+    }
+
+    #[test]
+    fn get_top_dict_data_strict() {
+        let mut cff = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        assert_eq!(
+            cff.get_top_dict_data_strict(0).unwrap(),
+            cff.get_top_dict_data(0).unwrap()
+        );
+        cff.set_top_dict_operator(0, DictKey::Unknown(0x0c30), vec![Operand::Integer(1)])
+            .unwrap();
+        let top_dict = cff.get_top_dict_data(0).unwrap();
+        assert!(top_dict.raw_entries.contains(&Entry::new(
+            DictKey::Unknown(0x0c30),
+            vec![Operand::Integer(1)]
+        )));
+        assert!(matches!(
+            cff.get_top_dict_data_strict(0),
+            Err(CffError::UnknownOperator(0x0c30))
+        ));
+    }
+
+    #[test]
+    fn cid_top_dict_values() {
+        let cff = owned(font_test_data::NOTO_SANS_JP_CFF);
+        let top_dict = cff.get_top_dict_data(0).unwrap();
+        assert_eq!(top_dict.cid_count, Some(65535));
+        assert!((top_dict.cid_font_version.unwrap() - 2.004).abs() < 1e-6);
+        assert_eq!(top_dict.cid_font_revision, None);
+        assert!(top_dict
+            .raw_entries
+            .iter()
+            .all(|entry| !matches!(entry.key, DictKey::CidCount | DictKey::CidFontVersion)));
+        let bytes = assert_cff_roundtrip(&cff);
+        let reparsed = Cff::read(FontData::new(&bytes)).unwrap();
+        let reparsed = reparsed.get_top_dict_data(0).unwrap();
+        assert_eq!(reparsed.cid_count, top_dict.cid_count);
+        assert_eq!(reparsed.cid_font_version, top_dict.cid_font_version);
+
+        // the values are written as numbers, omitting the defaults
+        let mut cff = owned(font_test_data::NOTO_SANS_JP_CFF);
+        let mut top_dict = cff.get_top_dict_data(0).unwrap();
+        top_dict.cid_count = Some(100);
+        top_dict.cid_font_version = Some(1.5);
+        top_dict.cid_font_revision = Some(0.0);
+        cff.set_top_dict_data(0, &top_dict).unwrap();
+        let entries = dict::parse(cff.get_top_dict_bytes(0).unwrap()).unwrap();
+        assert_eq!(entries[0].key, DictKey::Ros);
+        assert!(entries.contains(&Entry::new(DictKey::CidCount, vec![Operand::Integer(100)])));
+        assert!(entries.contains(&Entry::new(
+            DictKey::CidFontVersion,
+            vec![Operand::Real(1.5)]
+        )));
+        assert!(entries
+            .iter()
+            .all(|entry| entry.key != DictKey::CidFontRevision));
+        let bytes = assert_cff_roundtrip(&cff);
+        let reparsed = Cff::read(FontData::new(&bytes)).unwrap();
+        let reparsed = reparsed.get_top_dict_data(0).unwrap();
+        assert_eq!(reparsed.cid_count, Some(100));
+        assert_eq!(reparsed.cid_font_version, Some(1.5));
+        assert_eq!(reparsed.cid_font_revision, None);
+        top_dict.cid_count = Some(u32::MAX);
+        assert!(matches!(
+            cff.set_top_dict_data(0, &top_dict),
+            Err(CffError::InvalidDict)
+        ));
+
+        // the CID fields can't be set for a name-keyed font
+        let mut cff = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        let source_top_dict = cff.get_top_dict_bytes(0).unwrap().to_vec();
+        let mut top_dict = cff.get_top_dict_data(0).unwrap();
+        top_dict.cid_count = Some(100);
+        assert!(matches!(
+            cff.set_top_dict_data(0, &top_dict),
+            Err(CffError::NameKeyedFontUnsupported)
+        ));
+        assert_eq!(cff.get_top_dict_bytes(0).unwrap(), source_top_dict);
+    }
+
+    #[test]
+    fn set_is_fixed_pitch() {
+        let mut cff = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        let mut top_dict = cff.get_top_dict_data(0).unwrap();
+        assert_eq!(top_dict.is_fixed_pitch, None);
+        top_dict.is_fixed_pitch = Some(true);
+        cff.set_top_dict_data(0, &top_dict).unwrap();
+
+        let bytes = assert_cff_roundtrip(&cff);
+        let reparsed = Cff::read(FontData::new(&bytes)).unwrap();
+        let entries = dict::parse(reparsed.get_top_dict_bytes(0).unwrap()).unwrap();
+        assert!(entries.contains(&Entry::new(
+            DictKey::IsFixedPitch,
+            vec![Operand::Integer(1)]
+        )));
+        let mut top_dict = reparsed.get_top_dict_data(0).unwrap();
+        assert_eq!(top_dict.is_fixed_pitch, Some(true));
+        assert!(top_dict
+            .raw_entries
+            .iter()
+            .all(|entry| entry.key != DictKey::IsFixedPitch));
+
+        // false is the default, so the operator is omitted
+        top_dict.is_fixed_pitch = Some(false);
+        cff.set_top_dict_data(0, &top_dict).unwrap();
+        let entries = dict::parse(cff.get_top_dict_bytes(0).unwrap()).unwrap();
+        assert!(entries
+            .iter()
+            .all(|entry| entry.key != DictKey::IsFixedPitch));
+        assert_eq!(cff.get_top_dict_data(0).unwrap().is_fixed_pitch, None);
+    }
+
+    #[test]
+    fn set_and_clear_unique_id() {
+        let mut cff = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        let mut top_dict = cff.get_top_dict_data(0).unwrap();
+        assert_eq!(top_dict.unique_id, None);
+        assert_eq!(top_dict.xuid, None);
+        top_dict.unique_id = Some(4_000_123);
+        top_dict.xuid = Some(vec![1000, 4_000_123]);
+        cff.set_top_dict_data(0, &top_dict).unwrap();
+
+        let bytes = assert_cff_roundtrip(&cff);
+        let mut reparsed = Cff::read(FontData::new(&bytes)).unwrap();
+        let mut top_dict = reparsed.get_top_dict_data(0).unwrap();
+        assert_eq!(top_dict.unique_id, Some(4_000_123));
+        assert_eq!(top_dict.xuid, Some(vec![1000, 4_000_123]));
+        assert!(top_dict
+            .raw_entries
+            .iter()
+            .all(|entry| !matches!(entry.key, DictKey::UniqueId | DictKey::Xuid)));
+
+        top_dict.xuid = Some(vec![0; MAX_XUID_LEN + 1]);
+        assert!(matches!(
+            reparsed.set_top_dict_data(0, &top_dict),
+            Err(CffError::InvalidDict)
+        ));
+        top_dict.unique_id = None;
+        top_dict.xuid = None;
+        reparsed.set_top_dict_data(0, &top_dict).unwrap();
+        let entries = dict::parse(reparsed.get_top_dict_bytes(0).unwrap()).unwrap();
+        assert!(entries
+            .iter()
+            .all(|entry| !matches!(entry.key, DictKey::UniqueId | DictKey::Xuid)));
+    }
+
+    #[test]
+    fn top_dict_data_from_entries() {
+        let sid = |value| vec![Operand::Integer(value)];
+        let entries = [
+            Entry::new(DictKey::Version, sid(391)),
+            Entry::new(DictKey::FamilyName, sid(392)),
+            Entry::new(DictKey::Weight, sid(500)),
+            Entry::new(DictKey::ItalicAngle, sid(-12)),
+        ];
+        let top_dict = TopDictData::from_entries(&entries, |sid| match sid.to_u16() {
+            391 => Some("1.0".into()),
+            392 => Some("Stub".into()),
+            _ => None,
+        });
+        assert_eq!(top_dict.version.as_deref(), Some("1.0"));
+        assert_eq!(top_dict.family_name.as_deref(), Some("Stub"));
+        // unresolved strings are kept as they are
+        assert_eq!(top_dict.weight, None);
+        assert_eq!(top_dict.raw_entries, entries[2..]);
+        assert_eq!(
+            top_dict.operator_order,
+            entries.iter().map(|entry| entry.key).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn validate_truncated_subr() {
+        let mut cff = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        assert!(cff.validate_subrs().is_ok());
+        let subrs = cff.font_tables[0]
+            .private_dict
+            .as_mut()
+            .and_then(|private| private.subrs.as_mut())
+            .unwrap();
+        let truncated = subrs.get(0).unwrap().split_last().unwrap().1.to_vec();
+        *subrs = Index1::from_bytes(subrs.iter().enumerate().map(|(i, subr)| match i {
+            0 => truncated.as_slice(),
+            _ => subr,
+        }));
+        assert!(matches!(
+            cff.validate_subrs(),
+            Err(CffError::InvalidCharstring)
+        ));
+        assert!(cff.validate_charstrings().is_err());
+        // the table is still written, as the checks are opt-in
+        assert!(cff.validate().is_ok());
+    }
+
+    #[test]
+    fn validate_subr_tail_calls() {
+        let mut cff = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        let num_glyphs = cff.font_tables[0].char_strings.len();
+        let set_local_subrs = |cff: &mut Cff, subrs: Vec<Vec<u8>>| {
+            cff.set_local_subrs(0, 0, Some(Index1::from_bytes(subrs)))
+                .unwrap();
+        };
+        // glyph 1 calls local subr 0, which calls local subr 1; the number
+        // -107 is encoded as 32
+        cff.font_tables[0].char_strings =
+            Index1::from_bytes((0..num_glyphs).map(|gid| match gid {
+                1 => vec![32, 10, 14],
+                _ => vec![14],
+            }));
+        set_local_subrs(&mut cff, vec![vec![33, 10], vec![139, 139, 21, 11]]);
+        assert!(cff.validate_subrs().is_ok());
+        // the tail call is to a subroutine without a terminator
+        set_local_subrs(&mut cff, vec![vec![33, 10], vec![139, 139, 21]]);
+        assert!(matches!(
+            cff.validate_subrs(),
+            Err(CffError::InvalidCharstring)
+        ));
+        assert!(cff.validate_charstrings().is_err());
+        // the table is still written, as the checks are opt-in
+        assert!(cff.validate().is_ok());
+    }
+
+    #[test]
+    fn validate_stack_underflow() {
+        let mut cff = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        assert!(cff.validate_subrs().is_ok());
+        let num_glyphs = cff.font_tables[0].char_strings.len();
+        // glyph 1 calls local subr 0, which pushes a single operand for the
+        // rmoveto of the caller
+        cff.font_tables[0].char_strings =
+            Index1::from_bytes((0..num_glyphs).map(|gid| match gid {
+                1 => vec![32, 10, 21, 14],
+                _ => vec![14],
+            }));
+        cff.set_local_subrs(0, 0, Some(Index1::from_bytes(vec![vec![139, 11]])))
+            .unwrap();
+        assert!(matches!(
+            cff.validate_subrs(),
+            Err(CffError::InvalidCharstring)
+        ));
+        assert!(cff.validate_charstrings().is_err());
+        // the table is still written, as the checks are opt-in
+        assert!(cff.validate().is_ok());
+        // and passes with both operands
+        cff.set_local_subrs(0, 0, Some(Index1::from_bytes(vec![vec![139, 139, 11]])))
+            .unwrap();
+        assert!(cff.validate_subrs().is_ok());
+    }
+
+    #[test]
+    fn fd_select_format_3() {
+        let mut cff = owned(font_test_data::NOTO_SANS_JP_CFF);
+        let num_glyphs = cff.font_tables[0].char_strings.len();
+        assert_eq!(num_glyphs, 60);
+        let ranges = vec![
+            FdSelectRange3::new(0, 0),
+            FdSelectRange3::new(1, 1),
+            FdSelectRange3::new(30, 0),
+        ];
+        // an overrun fails validation, but if written anyway the sentinel is
+        // replaced by the number of glyphs
+        cff.font_tables[0].fd_select = Some(FdSelect::format_3(ranges.clone(), 1000));
+        assert!(cff.validate().is_err());
+        let bytes = write_to_vec(&cff);
+        let reparsed = Cff::read(FontData::new(&bytes)).unwrap();
+        assert_cff_roundtrip(&reparsed);
+        assert_eq!(
+            reparsed.font_tables[0].fd_select,
+            Some(FdSelect::format_3(ranges, num_glyphs as u16))
+        );
+        let data = write_to_vec(reparsed.font_tables[0].fd_select.as_ref().unwrap());
+        assert_eq!(data[data.len() - 2..], 60u16.to_be_bytes());
+        let fd_select =
+            read_fonts::tables::postscript::FdSelect::read(FontData::new(&data)).unwrap();
+        assert_eq!(fd_select.font_index(GlyphId::new(29)), Some(1));
+        assert_eq!(fd_select.font_index(GlyphId::new(30)), Some(0));
+
+        // format 0 is smaller when the selection alternates
+        let fds = (0..num_glyphs)
+            .map(|gid| (gid % 2) as u8)
+            .collect::<Vec<_>>();
+        assert_eq!(FdSelect::from_fds(&fds), FdSelect::format_0(fds.clone()));
+        cff.font_tables[0].fd_select = Some(FdSelect::format_0(fds[..5].to_vec()));
+        assert!(cff.validate().is_err());
+        let bytes = write_to_vec(&cff);
+        let reparsed = Cff::read(FontData::new(&bytes)).unwrap();
+        // uncovered glyphs select the first Font DICT
+        let mut expected = fds[..5].to_vec();
+        expected.resize(num_glyphs, 0);
+        assert_eq!(
+            reparsed.font_tables[0].fd_select,
+            Some(FdSelect::format_3(
+                vec![
+                    FdSelectRange3::new(0, 0),
+                    FdSelectRange3::new(1, 1),
+                    FdSelectRange3::new(2, 0),
+                    FdSelectRange3::new(3, 1),
+                    FdSelectRange3::new(4, 0),
+                ],
+                60
+            ))
+        );
+        assert_eq!(
+            FdSelect::from_fds(&expected),
+            reparsed.font_tables[0].fd_select.clone().unwrap()
+        );
+    }
+
+    #[test]
+    fn charset_format_selection() {
+        let sids = (400..1400).collect::<Vec<u16>>();
+        let charset = CustomCharset::from_sids(&sids);
+        assert_eq!(
+            charset,
+            CustomCharset::format_2(vec![CharsetRange2::new(400, 999)])
+        );
+        let data = write_to_vec(&charset);
+        assert_eq!(data, [2, 0x01, 0x90, 0x03, 0xE7]);
+        let read = read_fonts::tables::postscript::CustomCharset::read(FontData::new(&data))
+            .unwrap()
+            .to_owned_table();
+        assert_eq!(read, charset);
+        assert_eq!(charset_sids(&read), sids);
+
+        // short runs fit in format 1, and format 0 is smaller without runs
+        assert_eq!(
+            CustomCharset::from_sids(&[5, 6, 7, 8]),
+            CustomCharset::format_1(vec![CharsetRange1::new(5, 3)])
+        );
+        assert_eq!(
+            CustomCharset::from_sids(&[9, 3, 5]),
+            CustomCharset::format_0(vec![9, 3, 5])
+        );
+        assert_eq!(
+            CustomCharset::from_sids(&[]),
+            CustomCharset::format_0(Vec::new())
+        );
+
+        // the charset of a font with many glyphs round trips
+        let mut cff = owned(font_test_data::NOTO_SANS_JP_CFF);
+        let num_glyphs = cff.font_tables[0].char_strings.len();
+        let cids = (1000..1000 + num_glyphs as u16 - 1).collect::<Vec<_>>();
+        cff.font_tables[0].charset = Some(CustomCharset::from_sids(&cids));
+        let bytes = assert_cff_roundtrip(&cff);
+        let reparsed = Cff::read(FontData::new(&bytes)).unwrap();
+        let charset = reparsed.font_tables[0].charset.as_ref().unwrap();
+        assert_eq!(charset_sids(charset), cids);
+    }
+
+    #[test]
+    fn random_charset_format_0() {
+        use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+
+        let mut cff = owned(font_test_data::NOTO_SANS_JP_CFF);
+        let num_glyphs = cff.font_tables[0].char_strings.len();
+        let mut rng = StdRng::seed_from_u64(398);
+        // even CIDs in random order are never consecutive, so there are no
+        // runs to encode as ranges
+        let mut cids = (1..2000).map(|cid| cid * 2).collect::<Vec<u16>>();
+        cids.shuffle(&mut rng);
+        cids.truncate(num_glyphs - 1);
+        let charset = CustomCharset::from_sids(&cids);
+        assert_eq!(charset, CustomCharset::format_0(cids.clone()));
+        let data = write_to_vec(&charset);
+        assert_eq!(data.len(), 1 + 2 * cids.len());
+        assert_eq!(data[0], 0);
+
+        cff.font_tables[0].charset = Some(charset.clone());
+        let bytes = assert_cff_roundtrip(&cff);
+        let reparsed = Cff::read(FontData::new(&bytes)).unwrap();
+        assert_eq!(reparsed.font_tables[0].charset.as_ref(), Some(&charset));
+        assert_eq!(charset_sids(&charset), cids);
+    }
+
+    #[test]
+    fn referenced_string_ids() {
+        let cff = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        let top_dict = dict::parse(cff.get_top_dict_bytes(0).unwrap()).unwrap();
+        let sid = |key| {
+            top_dict
+                .iter()
+                .find(|entry| entry.key == key)
+                .and_then(string_operand)
+                .unwrap()
+        };
+        let sids = cff.referenced_string_ids(0).unwrap();
+        assert!(sids.contains(&sid(DictKey::Version)));
+        assert!(sids.contains(&sid(DictKey::FamilyName)));
+        assert!(sids
+            .windows(2)
+            .all(|pair| pair[0].to_u16() < pair[1].to_u16()));
+        assert!(matches!(
+            cff.referenced_string_ids(1),
+            Err(CffError::FontIndexOutOfBounds(1))
+        ));
+
+        // registry, ordering and the names of the Font DICTs
+        let cff = owned(font_test_data::NOTO_SANS_JP_CFF);
+        let sids = cff.referenced_string_ids(0).unwrap();
+        let strings = sids
+            .iter()
+            .map(|sid| cff.resolve_string(*sid).unwrap())
+            .collect::<Vec<_>>();
+        assert!(strings.iter().any(|s| s == "Adobe"));
+        assert!(strings.iter().any(|s| s == "Identity"));
+        assert!(sids.len() > 2 + cff.font_tables[0].fd_array.len());
+    }
+
+    #[test]
+    fn add_string_reuses_existing() {
+        let mut cff = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        assert_eq!(cff.add_string("space").unwrap(), StringId::new(1));
+        assert_eq!(
+            cff.add_string("Noto Serif Display").unwrap(),
+            StringId::new(395)
+        );
+        assert_eq!(cff.add_string("brand new").unwrap(), StringId::new(396));
+        assert_eq!(cff.add_string("brand new").unwrap(), StringId::new(396));
+        assert_eq!(
+            cff.resolve_string(StringId::new(396)).as_deref(),
+            Some("brand new")
+        );
+        assert!(cff.get_string_bytes(StringId::new(397)).is_err());
+    }
+
+    #[test]
+    fn add_string_exhausts_ids() {
+        let mut cff = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        let num_custom = u16::MAX as usize - STANDARD_STRING_COUNT;
+        cff.strings = Index1::from_bytes((0..num_custom).map(|i| format!("s{i}")));
+        // the last identifier can still be used
+        assert_eq!(cff.add_string("last").unwrap(), StringId::new(u16::MAX));
+        assert!(matches!(
+            cff.add_string("one too many"),
+            Err(CffError::StringIndexTooLarge)
+        ));
+        assert_eq!(cff.strings.len(), num_custom + 1);
+        // existing strings are still reused
+        assert_eq!(cff.add_string("last").unwrap(), StringId::new(u16::MAX));
+    }
+
+    #[test]
+    fn add_string_options() {
+        // (dedup, reuse_standard) -> SIDs for "space", "space", an existing
+        // custom string, "brand new", "brand new", and the added count
+        let cases = [
+            ((true, true), [1, 1, 395, 396, 396], 1),
+            ((false, true), [1, 1, 396, 397, 398], 3),
+            ((true, false), [396, 396, 395, 397, 397], 2),
+            ((false, false), [396, 397, 398, 399, 400], 5),
+        ];
+        for ((dedup, reuse_standard), expected, added) in cases {
+            let options = StringIndexOptions {
+                dedup,
+                reuse_standard,
+            };
+            let mut cff = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+            let count = cff.strings.len();
+            let sids = [
+                "space",
+                "space",
+                "Noto Serif Display",
+                "brand new",
+                "brand new",
+            ]
+            .map(|string| {
+                cff.add_string_with_options(string, options)
+                    .unwrap()
+                    .to_u16()
+            });
+            assert_eq!(sids, expected, "{options:?}");
+            assert_eq!(cff.strings.len(), count + added, "{options:?}");
+            assert_eq!(
+                cff.resolve_string(StringId::new(sids[0])).as_deref(),
+                Some("space")
+            );
+        }
+    }
+
+    #[test]
+    fn string_index_limit() {
+        let mut cff = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        let size = cff.strings.byte_len();
+        cff.set_string_index_limit(size + 16);
+        // existing strings are reused without growing the INDEX
+        assert!(cff.add_string("Noto Serif Display").is_ok());
+        assert!(cff.add_string("short").is_ok());
+        assert!(matches!(
+            cff.add_string(&"x".repeat(100)),
+            Err(CffError::StringIndexTooLarge)
+        ));
+        let mut top_dict = cff.get_top_dict_data(0).unwrap();
+        top_dict.notice = Some("a notice that doesn't fit".into());
+        assert!(matches!(
+            cff.set_top_dict_data(0, &top_dict),
+            Err(CffError::StringIndexTooLarge)
+        ));
+        assert!(cff.strings.byte_len() <= size + 16);
+
+        cff.string_index_limit = None;
+        assert!(cff.add_string(&"x".repeat(100)).is_ok());
+    }
+
+    /// Records the path commands sent to a pen.
+    #[derive(Default)]
+    struct PenRecorder(Vec<String>);
+
+    impl OutlinePen for PenRecorder {
+        fn move_to(&mut self, x: f64, y: f64) {
+            self.0.push(format!("M{x} {y}"));
+        }
+        fn line_to(&mut self, x: f64, y: f64) {
+            self.0.push(format!("L{x} {y}"));
+        }
+        fn curve_to(&mut self, cx0: f64, cy0: f64, cx1: f64, cy1: f64, x: f64, y: f64) {
+            self.0.push(format!("C{cx0} {cy0} {cx1} {cy1} {x} {y}"));
+        }
+        fn close(&mut self) {
+            self.0.push("Z".into());
+        }
+    }
+
+    #[test]
+    fn outline() {
+        let cff = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        let cmap = FontRef::new(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED)
+            .unwrap()
+            .cmap()
+            .unwrap();
+        assert_eq!(cff.glyph_for_char('A', &cmap), None);
+        let gid = cff.glyph_for_char('l', &cmap).unwrap();
+        assert_eq!(gid, 4);
+        let mut pen = PenRecorder::default();
+        cff.outline(0, gid, &mut pen).unwrap();
+        assert_eq!(
+            pen.0,
+            [
+                "M13 0",
+                "L297 0",
+                "L297 10",
+                "L284 10",
+                "C225 10 202 33 202 92",
+                "L202 760",
+                "L13 760",
+                "L13 750",
+                "L26 750",
+                "C85 750 108 728 108 672",
+                "L108 87",
+                "C108 32 85 10 26 10",
+                "L13 10",
+                "Z",
+            ]
+        );
+
+        // every glyph lies within the FontBBox and matches the outline of
+        // the written table
+        let [x_min, y_min, x_max, y_max] = cff.get_top_dict_data(0).unwrap().font_bbox.unwrap();
+        let bytes = crate::dump_table(&cff).unwrap();
+        for (gid, (bounds, expected)) in evaluate::<Bounds>(&bytes)
+            .into_iter()
+            .zip(outlines(&bytes))
+            .enumerate()
+        {
+            let mut pen = PenRecorder::default();
+            cff.outline(0, gid as u16, &mut pen).unwrap();
+            assert_eq!(pen.0.len(), expected.len());
+            if let Some([x0, y0, x1, y1]) = bounds.0 {
+                assert!(x0 >= x_min && y0 >= y_min && x1 <= x_max && y1 <= y_max);
+            }
+        }
+
+        // a CID-keyed font with local subroutines in each Font DICT
+        let cff = owned(font_test_data::NOTO_SANS_JP_CFF);
+        for gid in 0..cff.font_tables[0].char_strings.len() as u16 {
+            cff.outline(0, gid, &mut PenRecorder::default()).unwrap();
+        }
+        assert!(matches!(
+            cff.outline(0, 60, &mut PenRecorder::default()),
+            Err(CffError::GlyphIndexOutOfBounds(60))
+        ));
+        assert!(matches!(
+            cff.outline(1, 0, &mut PenRecorder::default()),
+            Err(CffError::FontIndexOutOfBounds(1))
+        ));
+    }
+
+    #[test]
+    fn outline_for_char() {
+        let operands = |values: &[i32]| {
+            values
+                .iter()
+                .map(|value| Token::Operand(CharstringOperand::Integer(*value)))
+                .collect::<Vec<_>>()
+        };
+        let mut tokens = operands(&[100, 0]);
+        tokens.push(Token::Operator(Operator::RMoveTo));
+        tokens.extend(operands(&[200, 0, -100, 300]));
+        tokens.push(Token::Operator(Operator::RLineTo));
+        tokens.push(Token::Operator(Operator::EndChar));
+        let charstring = crate::tables::postscript::charstring::encode(&tokens);
+        let mut cff = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        let gid = cff.add_glyph(0, "A", charstring).unwrap();
+
+        // a cmap with the mappings of the font and one for the new glyph
+        let font_cmap = FontRef::new(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED)
+            .unwrap()
+            .cmap()
+            .unwrap();
+        let mappings = ('i'..='l')
+            .filter_map(|ch| Some((ch, font_cmap.map_codepoint(ch)?)))
+            .chain([('A', GlyphId::new(gid as u32))]);
+        let cmap = crate::tables::cmap::Cmap::from_mappings(mappings).unwrap();
+        let cmap_bytes = crate::dump_table(&cmap).unwrap();
+        let cmap = read_fonts::tables::cmap::Cmap::read(FontData::new(&cmap_bytes)).unwrap();
+
+        let bytes = assert_cff_roundtrip(&cff);
+        let cff = Cff::read(FontData::new(&bytes)).unwrap();
+        let gid = cff.glyph_for_char('A', &cmap).unwrap();
+        assert_eq!(cff.glyph_name(0, gid).unwrap(), "A");
+        let mut pen = PenRecorder::default();
+        cff.outline_for_gid(gid, &mut pen).unwrap();
+        assert_eq!(pen.0, ["M100 0", "L300 0", "L200 300", "Z"]);
+        assert_eq!(cff.glyph_for_char('l', &cmap), Some(4));
+        assert_eq!(cff.glyph_for_char('B', &cmap), None);
+        assert!(matches!(
+            cff.outline_for_gid(gid + 1, &mut PenRecorder::default()),
+            Err(CffError::GlyphIndexOutOfBounds(_))
+        ));
+    }
+
+    #[test]
+    fn recompute_font_bbox() {
+        let mut cff = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        // the trimmed font keeps the FontBBox of the full font
+        let bbox = cff.recompute_font_bbox(0).unwrap();
+        assert_eq!(bbox, [-12.0, -240.0, 586.0, 760.0]);
+        assert_eq!(cff.get_top_dict_data(0).unwrap().font_bbox, Some(bbox));
+
+        cff.scale_outlines(2.0).unwrap();
+        assert_eq!(
+            cff.recompute_font_bbox(0).unwrap(),
+            [-24.0, -480.0, 1172.0, 1520.0]
+        );
+        let bytes = assert_cff_roundtrip(&cff);
+        let reparsed = Cff::read(FontData::new(&bytes)).unwrap();
+        assert_eq!(
+            reparsed.get_top_dict_data(0).unwrap().font_bbox,
+            Some([-24.0, -480.0, 1172.0, 1520.0])
+        );
+        assert!(matches!(
+            cff.recompute_font_bbox(1),
+            Err(CffError::FontIndexOutOfBounds(1))
+        ));
+    }
+
+    #[test]
+    fn global_subr_info() {
+        let cff = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        let count = cff.global_subrs.len();
+        assert!(count > 0);
+        assert_eq!(
+            cff.global_subr_info(),
+            (
+                count,
+                crate::tables::postscript::charstring::subr_bias(count)
+            )
+        );
+        assert_eq!(cff.global_subr_info().1, 107);
+        assert_eq!(<Cff>::default().global_subr_info(), (0, 107));
+    }
+
+    #[test]
+    fn set_global_subrs() {
+        let mut cff = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        let source_subrs = cff
+            .global_subrs
+            .iter()
+            .map(<[u8]>::to_vec)
+            .collect::<Vec<_>>();
+        let source_char_strings = cff.font_tables[0].char_strings.clone();
+        let num_glyphs = source_char_strings.len() as u16;
+        let draw = |cff: &Cff| {
+            (0..num_glyphs)
+                .map(|gid| {
+                    let mut pen = PenRecorder::default();
+                    cff.outline(0, gid, &mut pen).unwrap();
+                    pen.0
+                })
+                .collect::<Vec<_>>()
+        };
+        let source_outlines = draw(&cff);
+        assert_eq!(
+            crate::tables::postscript::charstring::subr_bias(source_subrs.len()),
+            107
+        );
+
+        // enough subroutines to change the bias to 1131, with the calls
+        // between the source subroutines updated to match
+        let map = SubrMap::rebias(source_subrs.len(), 1240);
+        let mut subrs = source_subrs
+            .iter()
+            .map(|subr| {
+                let mut tokens = crate::tables::postscript::charstring::parse(subr, &[]).unwrap();
+                renumber_subr_calls(&mut tokens, &map, None).unwrap();
+                crate::tables::postscript::charstring::encode(&tokens)
+            })
+            .collect::<Vec<_>>();
+        subrs.resize(1240, vec![11]);
+        cff.set_global_subrs(subrs.clone()).unwrap();
+        assert_eq!(cff.global_subrs.len(), 1240);
+        assert_ne!(cff.font_tables[0].char_strings, source_char_strings);
+        assert_eq!(draw(&cff), source_outlines);
+        let bytes = assert_cff_roundtrip(&cff);
+        let reparsed = Cff::read(FontData::new(&bytes)).unwrap();
+        assert_eq!(draw(&reparsed), source_outlines);
+
+        // the same bias leaves the charstrings unchanged
+        subrs.resize(1300, vec![11]);
+        let char_strings = cff.font_tables[0].char_strings.clone();
+        cff.set_global_subrs(subrs).unwrap();
+        assert_eq!(cff.font_tables[0].char_strings, char_strings);
+        assert_eq!(draw(&cff), source_outlines);
+
+        // and restoring the original subroutines restores the charstrings
+        cff.set_global_subrs(source_subrs.clone()).unwrap();
+        assert_eq!(cff.font_tables[0].char_strings, source_char_strings);
+        assert_eq!(draw(&cff), source_outlines);
+    }
+
+    /// Returns the total size of the charstrings and subroutines.
+    fn total_charstring_size(cff: &Cff) -> usize {
+        let subrs = |index: &Index1| index.iter().map(<[u8]>::len).sum::<usize>();
+        let mut size = subrs(&cff.global_subrs);
+        for font in &cff.font_tables {
+            size += subrs(&font.char_strings);
+            for private in font.private_dicts().into_iter().flatten() {
+                size += private.subrs.as_ref().map(subrs).unwrap_or_default();
+            }
+        }
+        size
+    }
+
+    #[test]
+    fn subroutinize() {
+        let mut cff = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        cff.inline_subrs().unwrap();
+        // repeat the outline of "l"
+        let l = cff.font_tables[0].char_strings.get(4).unwrap().to_vec();
+        let notdef = cff.font_tables[0].char_strings.get(0).unwrap().to_vec();
+        cff.font_tables[0].char_strings = Index1::from_bytes([&notdef, &l, &l, &l, &l]);
+        let bytes = assert_cff_roundtrip(&cff);
+        let source_outlines = outlines(&bytes);
+        let source_size = total_charstring_size(&cff);
+
+        cff.subroutinize().unwrap();
+        assert!(!cff.global_subrs.is_empty());
+        assert!(total_charstring_size(&cff) < source_size);
+        let bytes = assert_cff_roundtrip(&cff);
+        assert_eq!(outlines(&bytes), source_outlines);
+    }
+
+    #[test]
+    fn subroutinize_preserves_outlines() {
+        for font_data in [
+            font_test_data::NOTO_SERIF_DISPLAY_TRIMMED,
+            font_test_data::NOTO_SANS_JP_CFF,
+        ] {
+            let mut cff = owned(font_data);
+            let num_glyphs = cff.font_tables[0].char_strings.len() as u16;
+            let draw = |cff: &Cff| {
+                (0..num_glyphs)
+                    .map(|gid| {
+                        let mut pen = PenRecorder::default();
+                        cff.outline(0, gid, &mut pen).unwrap();
+                        pen.0
+                    })
+                    .collect::<Vec<_>>()
+            };
+            let source_outlines = draw(&cff);
+            cff.subroutinize().unwrap();
+            assert_eq!(draw(&cff), source_outlines);
+            assert!(cff.validate_subrs().is_ok());
+            assert_cff_roundtrip(&cff);
+        }
+    }
+
+    #[test]
+    fn subroutinize_full_size_font() {
+        // repeat the glyphs of the CID-keyed font to the size of a full CJK
+        // font, changing some operands of each copy so that the copies only
+        // share parts of their outlines
+        use crate::tables::postscript::charstring::{encode, Operand};
+        let mut cff = owned(font_test_data::NOTO_SANS_JP_CFF);
+        let source = cff.font_tables[0]
+            .char_strings
+            .iter()
+            .map(|charstring| Parser::new(&[]).parse(charstring).unwrap())
+            .collect::<Vec<_>>();
+        let char_strings = (0..10_000).map(|gid| {
+            let mut tokens = source[gid % source.len()].clone();
+            for (ix, token) in tokens.iter_mut().enumerate() {
+                if let Token::Operand(Operand::Integer(value)) = token {
+                    if (ix * 31 + gid) % 11 == 0 {
+                        *value += (gid % 3) as i32 + 1;
+                    }
+                }
+            }
+            encode(&tokens)
+        });
+        let font = &mut cff.font_tables[0];
+        let fds = (0..10_000)
+            .map(|gid| font.fd_index(gid % source.len()) as u8)
+            .collect::<Vec<_>>();
+        let cids = (1..10_000).collect::<Vec<_>>();
+        font.char_strings = Index1::from_bytes(char_strings);
+        font.charset = Some(CustomCharset::from_sids(&cids));
+        font.fd_select = Some(FdSelect::from_fds(&fds));
+        let source_size = total_charstring_size(&cff);
+        let draw = |cff: &Cff| {
+            (0..10_000)
+                .map(|gid| {
+                    let mut pen = PenRecorder::default();
+                    cff.outline(0, gid, &mut pen).unwrap();
+                    pen.0
+                })
+                .collect::<Vec<_>>()
+        };
+        let source_outlines = draw(&cff);
+
+        cff.subroutinize().unwrap();
+        assert!(total_charstring_size(&cff) < source_size);
+        assert_eq!(draw(&cff), source_outlines);
+        assert_cff_roundtrip(&cff);
+    }
+
+    #[test]
+    fn pad_to_four_bytes() {
+        for font_data in [
+            font_test_data::NOTO_SERIF_DISPLAY_TRIMMED,
+            font_test_data::NOTO_SANS_JP_CFF,
+        ] {
+            let mut cff = owned(font_data);
+            let unpadded = crate::dump_table(&cff).unwrap();
+            cff.pad_to_four_bytes = true;
+            let padded = crate::dump_table(&cff).unwrap();
+            assert_eq!(padded.len() % 4, 0);
+            assert!(padded.len() - unpadded.len() < 4);
+            assert_eq!(padded[..unpadded.len()], unpadded);
+            assert!(padded[unpadded.len()..].iter().all(|byte| *byte == 0));
+            assert_eq!(cff.layout().fonts.last().unwrap().end, unpadded.len());
+
+            let reparsed = Cff::read(FontData::new(&padded)).unwrap();
+            assert_eq!(
+                reparsed.font_tables[0].char_strings,
+                cff.font_tables[0].char_strings
+            );
+            let read = read_fonts::tables::cff::Cff::read(FontData::new(&padded)).unwrap();
+            assert_eq!(read.top_dicts().count(), 1);
+        }
+    }
+
+    #[test]
+    fn layout_covers_table() {
+        for font_data in [
+            font_test_data::NOTO_SERIF_DISPLAY_TRIMMED,
+            font_test_data::NOTO_SANS_JP_CFF,
+        ] {
+            let cff = owned(font_data);
+            let bytes = crate::dump_table(&cff).unwrap();
+            let layout = cff.layout();
+            let ranges = [
+                &layout.header,
+                &layout.names,
+                &layout.top_dicts,
+                &layout.strings,
+                &layout.global_subrs,
+                &layout.variation_store,
+            ]
+            .into_iter()
+            .chain(&layout.fonts)
+            .collect::<Vec<_>>();
+            assert_eq!(ranges[0].start, 0);
+            for pair in ranges.windows(2) {
+                assert_eq!(pair[0].end, pair[1].start);
+            }
+            assert_eq!(ranges.last().unwrap().end, bytes.len());
+            assert_eq!(layout.fonts.len(), cff.font_tables.len());
+            assert_eq!(bytes[layout.names.clone()], write_to_vec(&cff.names));
+            assert_eq!(bytes[layout.strings.clone()], write_to_vec(&cff.strings));
+        }
+    }
+
+    #[test]
+    fn serialized_len() {
+        for font_data in [
+            font_test_data::NOTO_SERIF_DISPLAY_TRIMMED,
+            font_test_data::NOTO_SANS_JP_CFF,
+        ] {
+            let mut cff = owned(font_data);
+            assert_eq!(cff.serialized_len(), cff.to_bytes().unwrap().len());
+            cff.pad_to_four_bytes = true;
+            cff.preserve_offset_sizes = true;
+            assert_eq!(cff.serialized_len(), cff.to_bytes().unwrap().len());
+            cff.font_tables[0].charset = None;
+            assert_eq!(cff.serialized_len(), cff.to_bytes().unwrap().len());
+
+            let source = FontRef::new(font_data).unwrap().cff().unwrap();
+            let borrowed = Cff::borrow_from(&source);
+            assert_eq!(
+                borrowed.serialized_len(),
+                borrowed.to_bytes().unwrap().len()
+            );
+        }
+    }
+
+    #[test]
+    fn variation_store_round_trip() {
+        let mut cff = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        assert_eq!(cff.variation_store, None);
+        use crate::tables::variations::{
+            ItemVariationData, ItemVariationStore, RegionAxisCoordinates, VariationRegion,
+            VariationRegionList,
+        };
+        let region = VariationRegion::new(vec![RegionAxisCoordinates::new(
+            F2Dot14::ZERO,
+            F2Dot14::ONE,
+            F2Dot14::ONE,
+        )]);
+        let store = ItemVariationStore::new(
+            VariationRegionList::new(1, vec![region]),
+            vec![Some(ItemVariationData::new(0, 0, vec![0], Vec::new()))],
+        );
+        let store_data = crate::dump_table(&store).unwrap();
+        cff.variation_store = Some(store_data.clone());
+
+        let bytes = assert_cff_roundtrip(&cff);
+        assert_eq!(bytes.len(), cff.serialized_len());
+        let layout = cff.layout();
+        assert_eq!(layout.variation_store.len(), store_data.len() + 2);
+        let reparsed = Cff::read(FontData::new(&bytes)).unwrap();
+        assert_eq!(reparsed.variation_store, Some(store_data));
+        // the offset in the Top DICT refers to the store's length
+        let entries = dict::parse(reparsed.get_top_dict_bytes(0).unwrap()).unwrap();
+        let offset = offset_operand(&entries, DictKey::VariationStoreOffset).unwrap();
+        assert_eq!(offset, layout.variation_store.start);
+        let read_store = read_fonts::tables::variations::ItemVariationStore::read(FontData::new(
+            &bytes[offset + 2..],
+        ))
+        .unwrap();
+        assert_eq!(read_store.item_variation_data_count(), 1);
+
+        // removing the store removes the operator
+        cff.variation_store = None;
+        let bytes = crate::dump_table(&cff).unwrap();
+        let reparsed = Cff::read(FontData::new(&bytes)).unwrap();
+        let entries = dict::parse(reparsed.get_top_dict_bytes(0).unwrap()).unwrap();
+        assert!(offset_operand(&entries, DictKey::VariationStoreOffset).is_none());
+        assert_eq!(reparsed.variation_store, None);
+    }
+
+    #[test]
+    fn set_top_dict_data_grows_offset_size() {
+        let mut cff = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        assert_eq!(cff.top_dicts.off_size, 1);
+        let mut data = cff.get_top_dict_data(0).unwrap();
+        // 60 five byte integers don't fit in one byte offsets
+        data.raw_entries.push(Entry::new(
+            DictKey::BaseFontBlend,
+            vec![Operand::Integer(100_000); 60],
+        ));
+        cff.set_top_dict_data(0, &data).unwrap();
+        assert_eq!(cff.top_dicts.off_size, 2);
+        assert!(cff.top_dicts.check_wellformed().is_ok());
+        let bytes = assert_cff_roundtrip(&cff);
+        let reparsed = Cff::read(FontData::new(&bytes)).unwrap();
+        assert_eq!(reparsed.top_dicts.off_size, 2);
+        let reparsed_data = reparsed.get_top_dict_data(0).unwrap();
+        assert_eq!(reparsed_data.full_name, data.full_name);
+        assert_eq!(reparsed_data.raw_entries.last(), data.raw_entries.last());
+    }
+
+    #[test]
+    fn set_top_dict_data_without_dedup() {
+        let mut cff = owned(font_test_data::NOTO_SERIF_DISPLAY_TRIMMED);
+        let data = cff.get_top_dict_data(0).unwrap();
+        let count = cff.strings.len();
+        cff.set_top_dict_data(0, &data).unwrap();
+        assert_eq!(cff.strings.len(), count);
+        let options = StringIndexOptions {
+            dedup: false,
+            ..Default::default()
+        };
+        cff.set_top_dict_data_with_options(0, &data, options)
+            .unwrap();
+        assert!(cff.strings.len() > count);
+        assert_eq!(cff.get_top_dict_data(0).unwrap(), data);
+        assert_cff_roundtrip(&cff);
+    }
+}
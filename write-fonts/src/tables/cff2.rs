@@ -0,0 +1,1294 @@
+//! The [CFF2](https://learn.microsoft.com/en-us/typography/opentype/spec/cff2) table
+
+include!("../../generated/generated_cff2.rs");
+
+use super::{
+    cff::{renumber_subr_calls, Cff, FontTables, SubrMap},
+    postscript::{
+        charstring::{self, Operator, Parser, Token},
+        dict::{self, encoded_offset, encoded_private_range, offset_operand, DictKey, Entry},
+        split_off, write_to_vec, CffError, CustomCharset, FdSelect, FontDict, Index1, Index2,
+        PrivateDict, PrivateDictData,
+    },
+    variations::{ItemVariationStore, VariationRegion},
+};
+
+/// The size of the header fields preceding any padding.
+const HEADER_LEN: usize = 5;
+
+/// The minimum operand stack depth reported by [`Cff2::compute_maxstack`].
+const DEFAULT_MAX_STACK: usize = 48;
+
+/// The [Compact Font Format (CFF) version 2](https://learn.microsoft.com/en-us/typography/opentype/spec/cff2) table.
+///
+/// The locations of subtables are computed when the table is written; any
+/// offsets present in the Top DICT, Font DICT and Private DICT data are
+/// replaced.
+///
+/// Unlike CFF, CFF2 has no Name or String INDEX, so a font has no name and
+/// DICTs contain no string values. Any `FontName` entry in a Font DICT can't
+/// be resolved and is preserved as part of the raw DICT data; the name of
+/// the font is given by the `name` table.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Cff2 {
+    /// The table header, containing the Top DICT.
+    ///
+    /// The `top_dict_length` field is computed and the `trailing_data` field
+    /// is ignored when writing.
+    pub header: Cff2Header,
+    /// Subroutines shared by all glyphs.
+    pub global_subrs: Index2,
+    /// The charstrings for each glyph.
+    pub char_strings: Index2,
+    /// The Font DICTs.
+    pub fd_array: Vec<FontDict<Index2>>,
+    /// The mapping from glyphs to entries in `fd_array`, if present.
+    ///
+    /// Glyphs use the first Font DICT if this is `None`. It is only written
+    /// if there is more than one Font DICT.
+    pub fd_select: Option<FdSelect>,
+    /// The variation data referenced by `blend` operators.
+    pub variation_store: Option<ItemVariationStore>,
+}
+
+/// A builder for a [`Cff2`] table.
+///
+/// Glyphs are added in glyph order, each with a charstring and the index of
+/// the Font DICT providing its hinting values. Charstrings may contain
+/// `blend` and `vsindex` operators referencing the variation store, but no
+/// subroutine calls.
+#[derive(Clone, Debug, Default)]
+pub struct Cff2Builder {
+    char_strings: Vec<Vec<u8>>,
+    fds: Vec<usize>,
+    private_dicts: Vec<PrivateDictData>,
+    variation_store: Option<ItemVariationStore>,
+}
+
+impl Cff2Builder {
+    /// Creates a builder for a font with the given variation data.
+    pub fn new(variation_store: Option<ItemVariationStore>) -> Self {
+        Cff2Builder {
+            variation_store,
+            ..Default::default()
+        }
+    }
+
+    /// Adds a Font DICT with the given Private DICT values, returning its
+    /// index.
+    pub fn add_font_dict(&mut self, private_dict: PrivateDictData) -> usize {
+        self.private_dicts.push(private_dict);
+        self.private_dicts.len() - 1
+    }
+
+    /// Adds a glyph using the Font DICT at `fd_index`, returning its glyph
+    /// id.
+    pub fn add_glyph(&mut self, charstring: Vec<u8>, fd_index: usize) -> usize {
+        self.char_strings.push(charstring);
+        self.fds.push(fd_index);
+        self.char_strings.len() - 1
+    }
+
+    /// Builds the table.
+    ///
+    /// An FDSelect is only included if there is more than one Font DICT, and
+    /// the `maxstack` operator is computed from the charstrings.
+    ///
+    /// Returns [`CffError::FdIndexOutOfBounds`] if a glyph references a Font
+    /// DICT that wasn't added, or [`CffError::InvalidCharstring`] if a
+    /// charstring can't be parsed.
+    pub fn build(self) -> Result<Cff2, CffError> {
+        let mut fd_array = self
+            .private_dicts
+            .iter()
+            .map(|private| FontDict {
+                dict: Vec::new(),
+                private_dict: Some(PrivateDict {
+                    dict: private.encode(),
+                    subrs: None,
+                }),
+            })
+            .collect::<Vec<_>>();
+        if fd_array.is_empty() {
+            fd_array.push(FontDict::default());
+        }
+        let mut fds = Vec::with_capacity(self.fds.len());
+        for fd_index in &self.fds {
+            match u8::try_from(*fd_index) {
+                Ok(fd) if *fd_index < fd_array.len() => fds.push(fd),
+                _ => return Err(CffError::FdIndexOutOfBounds(*fd_index)),
+            }
+        }
+        let mut cff2 = Cff2 {
+            char_strings: Index2::from_bytes(&self.char_strings),
+            fd_select: (fd_array.len() > 1).then(|| FdSelect::from_fds(&fds)),
+            fd_array,
+            variation_store: self.variation_store,
+            ..Default::default()
+        };
+        let region_counts = cff2.region_counts();
+        for charstring in &self.char_strings {
+            Parser::new(&region_counts).parse(charstring)?;
+        }
+        cff2.update_maxstack();
+        Ok(cff2)
+    }
+}
+
+impl Cff2 {
+    /// Serializes the table.
+    ///
+    /// This is equivalent to [`dump_table`](crate::dump_table).
+    pub fn to_bytes(&self) -> Result<Vec<u8>, CffError> {
+        Ok(crate::dump_table(self)?)
+    }
+
+    /// Returns the raw data of the Top DICT.
+    ///
+    /// Offsets in the data refer to the source table; they are replaced when
+    /// the table is written.
+    pub fn top_dict_bytes(&self) -> &[u8] {
+        &self.header.top_dict_data
+    }
+
+    /// Returns the entries of the Top DICT, in their encoded order.
+    pub fn top_dict_entries(&self) -> Result<Vec<Entry>, CffError> {
+        dict::parse(self.top_dict_bytes())
+    }
+
+    /// Returns the hinting values of the Private DICT referenced by the
+    /// Font DICT at the given index.
+    ///
+    /// If the Font DICT has no Private DICT, default values are returned.
+    pub fn get_private_dict_data(&self, fd_index: usize) -> Result<PrivateDictData, CffError> {
+        let font_dict = self
+            .fd_array
+            .get(fd_index)
+            .ok_or(CffError::FdIndexOutOfBounds(fd_index))?;
+        match &font_dict.private_dict {
+            Some(private) => PrivateDictData::parse(&private.dict, &self.region_counts()),
+            None => Ok(PrivateDictData::default()),
+        }
+    }
+
+    /// Replaces the Private DICT referenced by the Font DICT at the given
+    /// index.
+    ///
+    /// Local subroutines are retained.
+    pub fn set_private_dict_data(
+        &mut self,
+        fd_index: usize,
+        data: &PrivateDictData,
+    ) -> Result<(), CffError> {
+        let font_dict = self
+            .fd_array
+            .get_mut(fd_index)
+            .ok_or(CffError::FdIndexOutOfBounds(fd_index))?;
+        font_dict
+            .private_dict
+            .get_or_insert_with(Default::default)
+            .dict = data.encode();
+        Ok(())
+    }
+
+    /// Returns the greatest depth of the operand stack reached by any
+    /// charstring, including the operands of `blend` operators and those
+    /// pushed in subroutines.
+    ///
+    /// This is the value required for the `maxstack` operator of the Top
+    /// DICT; if no charstring exceeds it, the CFF limit of 48 is returned.
+    /// Charstrings that can't be parsed contribute the depth reached before
+    /// the error.
+    pub fn compute_maxstack(&self) -> u16 {
+        let region_counts = self.region_counts();
+        let global_subrs = self.global_subrs.iter().collect::<Vec<_>>();
+        let local_subrs = self.local_subrs();
+        let mut max_stack = DEFAULT_MAX_STACK;
+        for (gid, charstring) in self.char_strings.iter().enumerate() {
+            let local_subrs = local_subrs
+                .get(self.fd_index(gid))
+                .map_or(&[][..], Vec::as_slice);
+            let mut parser = Parser::new(&region_counts).with_subrs(&global_subrs, local_subrs);
+            let _ = parser.parse(charstring);
+            max_stack = max_stack.max(parser.max_stack_depth());
+        }
+        max_stack.min(u16::MAX as usize) as u16
+    }
+
+    /// Sets the `maxstack` operator of the Top DICT to the value computed
+    /// by [`Cff2::compute_maxstack`].
+    pub fn update_maxstack(&mut self) {
+        let mut operands = Vec::new();
+        dict::encode_int(self.compute_maxstack() as i32, &mut operands);
+        self.header.top_dict_data = dict::rewrite(
+            &self.header.top_dict_data,
+            &[(DictKey::MaxStack, Some(operands))],
+        );
+    }
+
+    /// Replaces the global subroutines.
+    ///
+    /// The subroutine at each index of `subrs` replaces the subroutine with
+    /// the same number. As in CFF, calls are biased by the number of
+    /// subroutines, so if the bias changes the operands of the `callgsubr`
+    /// operators in every charstring and local subroutine are updated to
+    /// keep calling the same numbers; calls within `subrs` must already use
+    /// the new bias. Local subroutines that aren't called by any charstring
+    /// can't be parsed, so they are left unchanged.
+    ///
+    /// Returns [`CffError::InvalidCharstring`] if the bias changes and a
+    /// charstring calls a subroutine that isn't in `subrs`.
+    pub fn set_global_subrs(&mut self, subrs: Vec<Vec<u8>>) -> Result<(), CffError> {
+        let old_count = self.global_subrs.len();
+        if charstring::subr_bias(old_count) != charstring::subr_bias(subrs.len()) {
+            let global_map = SubrMap::rebias(old_count, subrs.len());
+            let region_counts = self.region_counts();
+            let global_subrs = self.global_subrs.iter().collect::<Vec<_>>();
+            let local_subrs = self.local_subrs();
+            // the stem count at the first call of each local subroutine
+            let mut local_calls = local_subrs
+                .iter()
+                .map(|subrs| vec![None; subrs.len()])
+                .collect::<Vec<_>>();
+            let mut char_strings = Vec::with_capacity(self.char_strings.len());
+            for (gid, charstring) in self.char_strings.iter().enumerate() {
+                let fd_index = self.fd_index(gid);
+                let local = local_subrs.get(fd_index).map_or(&[][..], Vec::as_slice);
+                let mut parser = Parser::new(&region_counts).with_subrs(&global_subrs, local);
+                let mut tokens = parser.parse(charstring)?;
+                for call in parser.subr_calls().iter().filter(|call| !call.global) {
+                    local_calls[fd_index][call.index].get_or_insert(call.stem_count);
+                }
+                renumber_subr_calls(&mut tokens, &global_map, None)?;
+                char_strings.push(charstring::encode(&tokens));
+            }
+            let mut new_local_subrs = Vec::with_capacity(local_subrs.len());
+            for (local, calls) in local_subrs.iter().zip(&local_calls) {
+                let mut subrs = Vec::with_capacity(local.len());
+                for (subr, stem_count) in local.iter().zip(calls) {
+                    let Some(stem_count) = stem_count else {
+                        subrs.push(subr.to_vec());
+                        continue;
+                    };
+                    let mut tokens = Parser::new(&region_counts)
+                        .with_stem_count(*stem_count)
+                        .with_subrs(&global_subrs, local)
+                        .parse(subr)?;
+                    renumber_subr_calls(&mut tokens, &global_map, None)?;
+                    subrs.push(charstring::encode(&tokens));
+                }
+                new_local_subrs.push(subrs);
+            }
+            self.char_strings = Index2::from_bytes(char_strings);
+            for (font_dict, subrs) in self.fd_array.iter_mut().zip(new_local_subrs) {
+                if let Some(private) = font_dict.private_dict.as_mut() {
+                    if private.subrs.is_some() {
+                        private.subrs = Some(Index2::from_bytes(subrs));
+                    }
+                }
+            }
+        }
+        self.global_subrs = Index2::from_bytes(subrs);
+        Ok(())
+    }
+
+    /// Creates a static CFF table from this table at the given location in
+    /// the design space, in normalized coordinates.
+    ///
+    /// The `blend` operators of all charstrings and Private DICTs are
+    /// resolved and subroutines are inlined. The result is a CID-keyed font
+    /// using the `Adobe-Identity-0` character collection with an identity
+    /// charset, and is named `Instance`; the name can be replaced by editing
+    /// the `names` INDEX.
+    ///
+    /// CFF2 charstrings don't include an advance width, so the
+    /// `defaultWidthX` of each Private DICT applies to all glyphs.
+    ///
+    /// Returns [`CffError::GlyphIndexOutOfBounds`] if the table has more
+    /// glyphs than a CFF table can hold.
+    pub fn instance(&self, coords: &[F2Dot14]) -> Result<Cff, CffError> {
+        // the glyphs of a CFF table are limited to 16 bit ids
+        let num_glyphs = self.char_strings.len();
+        let max_cid =
+            u16::try_from(num_glyphs).map_err(|_| CffError::GlyphIndexOutOfBounds(u16::MAX))?;
+        let region_counts = self.region_counts();
+        let scalars = self.region_scalars(coords);
+        let global_subrs = self.global_subrs.iter().collect::<Vec<_>>();
+        let local_subrs = self.local_subrs();
+
+        let mut vsindexes = Vec::with_capacity(self.fd_array.len());
+        let mut fd_array = Vec::with_capacity(self.fd_array.len());
+        for (fd_index, font_dict) in self.fd_array.iter().enumerate() {
+            let mut private = self.get_private_dict_data(fd_index)?;
+            let vsindex =
+                usize::try_from(private.vsindex.unwrap_or(0)).map_err(|_| CffError::InvalidDict)?;
+            vsindexes.push(vsindex);
+            private.instance(&scalars);
+            fd_array.push(FontDict {
+                // a CFF2 Font DICT has no strings to resolve a name against
+                dict: dict::rewrite(&font_dict.dict, &[(DictKey::FontName, None)]),
+                private_dict: Some(PrivateDict {
+                    dict: private.encode(),
+                    subrs: None,
+                }),
+            });
+        }
+        if fd_array.is_empty() {
+            fd_array.push(FontDict::default());
+        }
+
+        let mut char_strings = Vec::with_capacity(num_glyphs);
+        let mut fds = Vec::with_capacity(num_glyphs);
+        for (gid, charstring) in self.char_strings.iter().enumerate() {
+            let fd_index = self.fd_index(gid);
+            fds.push(u8::try_from(fd_index).map_err(|_| CffError::FdIndexOutOfBounds(fd_index))?);
+            let local_subrs = local_subrs.get(fd_index).map_or(&[][..], Vec::as_slice);
+            let tokens = Parser::new(&region_counts)
+                .with_subrs(&global_subrs, local_subrs)
+                .with_inlined_subrs()
+                .parse(charstring)?;
+            let vsindex = vsindexes.get(fd_index).copied().unwrap_or(0);
+            let mut tokens = charstring::resolve_blends(&tokens, vsindex, &scalars)?;
+            // unlike CFF2, every Type 2 charstring ends with endchar
+            if tokens.last() != Some(&Token::Operator(Operator::EndChar)) {
+                tokens.push(Token::Operator(Operator::EndChar));
+            }
+            char_strings.push(charstring::encode(&tokens));
+        }
+
+        let mut cff = Cff {
+            names: Index1::from_bytes(["Instance"]),
+            ..Default::default()
+        };
+        let mut top_dict = Vec::new();
+        // the ROS operator must be the first in the Top DICT
+        let registry = cff.add_string("Adobe")?;
+        let ordering = cff.add_string("Identity")?;
+        for operand in [registry.to_u16() as i32, ordering.to_u16() as i32, 0] {
+            dict::encode_int(operand, &mut top_dict);
+        }
+        dict::encode_operator(DictKey::Ros, &mut top_dict);
+        dict::encode_int(num_glyphs as i32, &mut top_dict);
+        dict::encode_operator(DictKey::CidCount, &mut top_dict);
+        top_dict.extend(dict::rewrite(
+            &self.header.top_dict_data,
+            &[
+                (DictKey::CharstringsOffset, None),
+                (DictKey::FdArrayOffset, None),
+                (DictKey::FdSelectOffset, None),
+                (DictKey::VariationStoreOffset, None),
+                (DictKey::MaxStack, None),
+            ],
+        ));
+        cff.top_dicts = Index1::from_bytes([top_dict]);
+        // glyph ids map to the same CIDs
+        let cids = (1..max_cid).collect::<Vec<_>>();
+        cff.font_tables.push(FontTables {
+            char_strings: Index1::from_bytes(char_strings),
+            charset: Some(CustomCharset::from_sids(&cids)),
+            fd_array,
+            fd_select: Some(FdSelect::from_fds(&fds)),
+            ..Default::default()
+        });
+        Ok(cff)
+    }
+
+    /// The number of regions referenced by each item variation data.
+    pub(crate) fn region_counts(&self) -> Vec<usize> {
+        self.variation_store
+            .iter()
+            .flat_map(|store| store.item_variation_data.iter())
+            .map(|data| data.as_ref().map_or(0, |data| data.region_indexes.len()))
+            .collect()
+    }
+
+    /// The scalar of each region referenced by each item variation data at
+    /// the given location.
+    fn region_scalars(&self, coords: &[F2Dot14]) -> Vec<Vec<f64>> {
+        let Some(store) = &self.variation_store else {
+            return Vec::new();
+        };
+        let regions = &store.variation_region_list.variation_regions;
+        store
+            .item_variation_data
+            .iter()
+            .map(|data| {
+                data.as_ref()
+                    .map(|data| {
+                        data.region_indexes
+                            .iter()
+                            .map(|ix| {
+                                regions
+                                    .get(*ix as usize)
+                                    .map_or(0.0, |region| region_scalar(region, coords))
+                            })
+                            .collect()
+                    })
+                    .unwrap_or_default()
+            })
+            .collect()
+    }
+
+    /// The data of each local subroutine of each Font DICT.
+    fn local_subrs(&self) -> Vec<Vec<&[u8]>> {
+        self.fd_array
+            .iter()
+            .map(|font_dict| {
+                font_dict
+                    .private_dict
+                    .as_ref()
+                    .and_then(|private| private.subrs.as_ref())
+                    .map(|subrs| subrs.iter().collect())
+                    .unwrap_or_default()
+            })
+            .collect()
+    }
+
+    /// The index of the Font DICT used by the given glyph.
+    ///
+    /// This is 0 if there is no FDSelect or only one Font DICT.
+    fn fd_index(&self, gid: usize) -> usize {
+        self.written_fd_select()
+            .and_then(|fd_select| fd_select.font_index(gid as u32))
+            .unwrap_or(0)
+    }
+
+    /// The FDSelect, unless there is at most one Font DICT, in which case it
+    /// is redundant and not written.
+    fn written_fd_select(&self) -> Option<&FdSelect> {
+        self.fd_select.as_ref().filter(|_| self.fd_array.len() > 1)
+    }
+
+    /// The length of the Top DICT once the offsets of the subtables have
+    /// been written.
+    fn compiled_top_dict_len(&self) -> usize {
+        // offsets are always encoded with the same width, so their values
+        // don't affect the length
+        let offset = |present: bool| present.then(|| encoded_offset(0));
+        dict::rewrite(
+            &self.header.top_dict_data,
+            &[
+                (
+                    DictKey::VariationStoreOffset,
+                    offset(self.variation_store.is_some()),
+                ),
+                (
+                    DictKey::FdSelectOffset,
+                    offset(self.written_fd_select().is_some()),
+                ),
+                (DictKey::CharstringsOffset, offset(true)),
+                (DictKey::FdArrayOffset, offset(true)),
+            ],
+        )
+        .len()
+    }
+
+    fn compile(&self) -> Vec<u8> {
+        // As in CFF, offsets are always encoded with the same width so a
+        // first pass with a placeholder finds the start of the subtables.
+        let (_, start) = self.compile_with_start(0);
+        let (data, final_start) = self.compile_with_start(start);
+        debug_assert_eq!(start, final_start);
+        data
+    }
+
+    /// Compiles the table, assuming that the subtables following the global
+    /// subroutines start at the given position.
+    ///
+    /// Returns the table data and the actual start of the subtables.
+    fn compile_with_start(&self, start: usize) -> (Vec<u8>, usize) {
+        let mut updates = Vec::new();
+        let mut subtables = Vec::new();
+        let offset = |data: &Vec<u8>| Some(encoded_offset(start + data.len()));
+
+        match &self.variation_store {
+            Some(store) => {
+                updates.push((DictKey::VariationStoreOffset, offset(&subtables)));
+                let store_data = compile_variation_store(store);
+                // the store is preceded by its length, which is checked when
+                // the table is validated
+                subtables.extend((store_data.len() as u16).to_be_bytes());
+                subtables.extend(store_data);
+            }
+            None => updates.push((DictKey::VariationStoreOffset, None)),
+        }
+        match self.written_fd_select() {
+            Some(fd_select) => {
+                updates.push((DictKey::FdSelectOffset, offset(&subtables)));
+                subtables.extend(write_to_vec(fd_select));
+            }
+            None => updates.push((DictKey::FdSelectOffset, None)),
+        }
+        updates.push((DictKey::CharstringsOffset, offset(&subtables)));
+        subtables.extend(write_to_vec(&self.char_strings));
+
+        let mut font_dicts = Vec::with_capacity(self.fd_array.len());
+        let empty_private = PrivateDict::default();
+        for font_dict in &self.fd_array {
+            // every Font DICT references a Private DICT, even if empty
+            let private = font_dict.private_dict.as_ref().unwrap_or(&empty_private);
+            let private_start = start + subtables.len();
+            let (private_data, dict_len) = private.compile();
+            subtables.extend(private_data);
+            let range = encoded_private_range(dict_len, private_start);
+            font_dicts.push(dict::rewrite(
+                &font_dict.dict,
+                &[(DictKey::PrivateDictRange, Some(range))],
+            ));
+        }
+        updates.push((DictKey::FdArrayOffset, offset(&subtables)));
+        subtables.extend(write_to_vec(&Index2::from_bytes(font_dicts)));
+
+        let top_dict = dict::rewrite(&self.header.top_dict_data, &updates);
+        debug_assert_eq!(top_dict.len(), self.compiled_top_dict_len());
+        let mut data = vec![2, 0, self.header.header_size];
+        data.extend((top_dict.len() as u16).to_be_bytes());
+        data.extend_from_slice(&self.header._padding);
+        data.extend(top_dict);
+        data.extend(write_to_vec(&self.global_subrs));
+        let actual_start = data.len();
+        data.extend(subtables);
+        (data, actual_start)
+    }
+}
+
+/// Returns the scalar of a variation region at the given location.
+fn region_scalar(region: &VariationRegion, coords: &[F2Dot14]) -> f64 {
+    let mut scalar = 1.0;
+    for (i, axis) in region.region_axes.iter().enumerate() {
+        let [start, peak, end] =
+            [axis.start_coord, axis.peak_coord, axis.end_coord].map(|coord| coord.to_f32() as f64);
+        let coord = coords.get(i).copied().unwrap_or_default().to_f32() as f64;
+        // invalid regions and axes without a peak don't affect the scalar
+        if start > peak || peak > end || (start < 0.0 && end > 0.0) || peak == 0.0 {
+            continue;
+        }
+        if coord == peak {
+            continue;
+        }
+        if coord <= start || coord >= end {
+            return 0.0;
+        }
+        scalar *= if coord < peak {
+            (coord - start) / (peak - start)
+        } else {
+            (end - coord) / (end - peak)
+        };
+    }
+    scalar
+}
+
+impl TopLevelTable for Cff2 {
+    const TAG: Tag = Tag::new(b"CFF2");
+}
+
+impl FontWrite for Cff2 {
+    fn write_into(&self, writer: &mut TableWriter) {
+        writer.write_slice(&self.compile())
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::TopLevel(Cff2::TAG)
+    }
+}
+
+impl Validate for Cff2 {
+    fn validate_impl(&self, ctx: &mut ValidationCtx) {
+        ctx.in_table("Cff2", |ctx| {
+            ctx.in_field("header", |ctx| {
+                if dict::parse(&self.header.top_dict_data).is_err() {
+                    ctx.report("malformed Top DICT");
+                }
+                // the length is recomputed, but must fit in the u16 field
+                // once the offsets have been written
+                let top_dict_len = self.compiled_top_dict_len();
+                if top_dict_len > u16::MAX as usize {
+                    ctx.report(format!(
+                        "Top DICT is {top_dict_len} bytes, more than the maximum of {}",
+                        u16::MAX
+                    ));
+                }
+                if self.header.header_size as usize != HEADER_LEN + self.header._padding.len() {
+                    ctx.report("header_size must include the padding bytes");
+                }
+            });
+            ctx.in_field("fd_array", |ctx| {
+                let malformed = self.fd_array.iter().any(|font_dict| {
+                    dict::parse(&font_dict.dict).is_err()
+                        || font_dict
+                            .private_dict
+                            .as_ref()
+                            .is_some_and(|private| dict::parse(&private.dict).is_err())
+                });
+                if malformed {
+                    ctx.report("malformed Private or Font DICT");
+                }
+            });
+            ctx.in_field("variation_store", |ctx| {
+                self.variation_store.validate_impl(ctx);
+                // the length of the store is written as a u16
+                if let Some(store) = &self.variation_store {
+                    let store_len = compile_variation_store(store).len();
+                    if store_len > u16::MAX as usize {
+                        ctx.report(format!(
+                            "variation store is {store_len} bytes, more than the maximum of {}",
+                            u16::MAX
+                        ));
+                    }
+                }
+            });
+        })
+    }
+}
+
+/// Serializes an item variation store, which is written without its length.
+fn compile_variation_store(store: &ItemVariationStore) -> Vec<u8> {
+    let mut graph = TableWriter::make_graph(store);
+    graph.pack_objects();
+    graph.serialize()
+}
+
+impl<'a> FromObjRef<read_fonts::tables::cff2::Cff2<'a>> for Cff2 {
+    fn from_obj_ref(obj: &read_fonts::tables::cff2::Cff2<'a>, _: FontData) -> Self {
+        // offsets in the DICTs are relative to the start of the table, so
+        // they are resolved against the table's own data rather than the
+        // (empty) parent data passed by `FromTableRef`
+        let data = obj.offset_data();
+        let mut header: Cff2Header = obj.header().to_owned_table();
+        header.trailing_data.clear();
+        // a malformed header size has no padding, so the Top DICT follows
+        // the header fields
+        header.header_size = header.header_size.max(HEADER_LEN as u8);
+        let mut cff2 = Cff2 {
+            header,
+            global_subrs: obj.global_subrs().to_owned_table(),
+            ..Default::default()
+        };
+        let Ok(top_dict) = dict::parse(obj.top_dict_data()) else {
+            return cff2;
+        };
+        if let Some(offset) = offset_operand(&top_dict, DictKey::CharstringsOffset) {
+            cff2.char_strings = split_off(data, offset)
+                .and_then(Index2::read)
+                .unwrap_or_default();
+        }
+        if let Some(offset) = offset_operand(&top_dict, DictKey::FdArrayOffset) {
+            cff2.fd_array = FontDict::read_fd_array(data, offset, true).unwrap_or_default();
+        }
+        if let Some(offset) = offset_operand(&top_dict, DictKey::FdSelectOffset) {
+            cff2.fd_select = split_off(data, offset)
+                .and_then(read_fonts::tables::postscript::FdSelect::read)
+                .ok()
+                .map(|fd_select| {
+                    let mut fd_select: FdSelect = fd_select.to_owned_table();
+                    // format 0 has no explicit length and extends to the end
+                    // of the table
+                    if let FdSelect::Format0(format0) = &mut fd_select {
+                        format0.fds.truncate(cff2.char_strings.len());
+                    }
+                    fd_select
+                });
+        }
+        if let Some(offset) = offset_operand(&top_dict, DictKey::VariationStoreOffset) {
+            // skip the u16 length that precedes the store
+            cff2.variation_store = split_off(data, offset + 2)
+                .and_then(read_fonts::tables::variations::ItemVariationStore::read)
+                .ok()
+                .map(|store| store.to_owned_table());
+        }
+        cff2
+    }
+}
+
+impl<'a> FromTableRef<read_fonts::tables::cff2::Cff2<'a>> for Cff2 {}
+
+impl<'a> FontRead<'a> for Cff2 {
+    fn read(data: FontData<'a>) -> Result<Self, ReadError> {
+        <read_fonts::tables::cff2::Cff2 as FontRead>::read(data).map(|x| x.to_owned_table())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tables::postscript::charstring;
+    use read_fonts::{FontRef, TableProvider};
+
+    fn owned(font_data: &[u8]) -> Cff2 {
+        FontRef::new(font_data)
+            .unwrap()
+            .cff2()
+            .unwrap()
+            .to_owned_table()
+    }
+
+    #[test]
+    fn round_trip() {
+        let cff2 = owned(font_test_data::CANTARELL_VF_TRIMMED);
+        assert!(cff2.variation_store.is_some());
+        assert_eq!(cff2.fd_array.len(), 1);
+
+        let bytes = crate::dump_table(&cff2).unwrap();
+        let reparsed = Cff2::read(FontData::new(&bytes)).unwrap();
+        assert_eq!(reparsed.char_strings, cff2.char_strings);
+        assert_eq!(reparsed.global_subrs, cff2.global_subrs);
+        assert_eq!(reparsed.variation_store, cff2.variation_store);
+        assert_eq!(crate::dump_table(&reparsed).unwrap(), bytes);
+    }
+
+    #[test]
+    fn single_font_dict_omits_fd_select() {
+        let mut cff2 = owned(font_test_data::CANTARELL_VF_TRIMMED);
+        assert_eq!(cff2.fd_array.len(), 1);
+        let num_glyphs = cff2.char_strings.len();
+        cff2.fd_select = Some(FdSelect::from_fds(&vec![0; num_glyphs]));
+
+        let bytes = crate::dump_table(&cff2).unwrap();
+        let source = read_fonts::tables::cff2::Cff2::read(FontData::new(&bytes)).unwrap();
+        let top_dict = dict::parse(source.top_dict_data()).unwrap();
+        assert!(offset_operand(&top_dict, DictKey::FdSelectOffset).is_none());
+        let reparsed = Cff2::read(FontData::new(&bytes)).unwrap();
+        assert_eq!(reparsed.fd_select, None);
+        assert!((0..num_glyphs).all(|gid| reparsed.fd_index(gid) == 0));
+        cff2.fd_select = None;
+        assert_eq!(crate::dump_table(&cff2).unwrap(), bytes);
+
+        // with a second Font DICT the FDSelect is written
+        cff2.fd_array.push(cff2.fd_array[0].clone());
+        let fds = (0..num_glyphs)
+            .map(|gid| (gid % 2) as u8)
+            .collect::<Vec<_>>();
+        cff2.fd_select = Some(FdSelect::from_fds(&fds));
+        let bytes = crate::dump_table(&cff2).unwrap();
+        let reparsed = Cff2::read(FontData::new(&bytes)).unwrap();
+        assert_eq!(reparsed.fd_select, cff2.fd_select);
+        assert_eq!(reparsed.fd_index(1), 1);
+    }
+
+    #[test]
+    fn variation_store_without_fd_select() {
+        let mut cff2 = owned(font_test_data::CANTARELL_VF_TRIMMED);
+        cff2.fd_select = None;
+        let bytes = crate::dump_table(&cff2).unwrap();
+        let source = read_fonts::tables::cff2::Cff2::read(FontData::new(&bytes)).unwrap();
+        let top_dict = dict::parse(source.top_dict_data()).unwrap();
+        assert!(offset_operand(&top_dict, DictKey::FdSelectOffset).is_none());
+        assert!(offset_operand(&top_dict, DictKey::VariationStoreOffset).is_some());
+        let reparsed = Cff2::read(FontData::new(&bytes)).unwrap();
+        assert_eq!(reparsed.variation_store, cff2.variation_store);
+
+        // the blends of the first blended glyph resolve against the written
+        // store
+        let global_subrs = reparsed.global_subrs.iter().collect::<Vec<_>>();
+        let local_subrs = reparsed.local_subrs();
+        let region_counts = reparsed.region_counts();
+        let tokens = reparsed
+            .char_strings
+            .iter()
+            .find_map(|charstring| {
+                let mut parser = charstring::Parser::new(&region_counts)
+                    .with_subrs(&global_subrs, &local_subrs[0])
+                    .with_inlined_subrs();
+                let tokens = parser.parse(charstring).unwrap();
+                (!parser.blends().is_empty()).then_some(tokens)
+            })
+            .unwrap();
+        let axis_count = reparsed
+            .variation_store
+            .as_ref()
+            .unwrap()
+            .variation_region_list
+            .axis_count as usize;
+        let scalars = reparsed.region_scalars(&vec![F2Dot14::ONE; axis_count]);
+        let resolved = charstring::resolve_blends(&tokens, 0, &scalars).unwrap();
+        assert!(!resolved.contains(&Token::Operator(Operator::Blend)));
+        let coords = vec![F2Dot14::ONE; axis_count];
+        assert_eq!(
+            reparsed.instance(&coords).unwrap().font_tables[0].char_strings,
+            cff2.instance(&coords).unwrap().font_tables[0].char_strings
+        );
+    }
+
+    #[test]
+    fn default_header() {
+        let cff2 = Cff2::default();
+        assert_eq!(cff2.header.header_size as usize, HEADER_LEN);
+        let bytes = crate::dump_table(&cff2).unwrap();
+        let read = read_fonts::tables::cff2::Cff2::read(FontData::new(&bytes)).unwrap();
+        assert_eq!(read.header().major_version(), 2);
+        assert_eq!(read.header().minor_version(), 0);
+        assert_eq!(read.header().header_size() as usize, HEADER_LEN);
+
+        // a header size that leaves no room for the header fields
+        let mut bytes = crate::dump_table(&owned(font_test_data::CANTARELL_VF_TRIMMED)).unwrap();
+        bytes[2] = 0;
+        let cff2 = Cff2::read(FontData::new(&bytes)).unwrap();
+        assert_eq!(cff2.header.header_size as usize, HEADER_LEN);
+        let reparsed = Cff2::read(FontData::new(&crate::dump_table(&cff2).unwrap())).unwrap();
+        assert_eq!(reparsed.char_strings, cff2.char_strings);
+    }
+
+    #[test]
+    fn top_dict_entries() {
+        let font = FontRef::new(font_test_data::CANTARELL_VF_TRIMMED).unwrap();
+        let source = font.cff2().unwrap();
+        let cff2 = Cff2::from_table_ref(&source);
+        assert_eq!(cff2.top_dict_bytes(), source.top_dict_data());
+        let entries = cff2.top_dict_entries().unwrap();
+        let read_entries =
+            read_fonts::tables::postscript::dict::entries(source.top_dict_data(), None)
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+        assert_eq!(entries.len(), read_entries.len());
+        assert!(entries
+            .iter()
+            .any(|entry| entry.key == DictKey::CharstringsOffset));
+
+        let mut cff2 = cff2;
+        cff2.header.top_dict_data = vec![29, 0];
+        assert!(matches!(
+            cff2.top_dict_entries(),
+            Err(CffError::InvalidDict)
+        ));
+    }
+
+    #[test]
+    fn to_owned_captures_subtables() {
+        let font = FontRef::new(font_test_data::CANTARELL_VF_TRIMMED).unwrap();
+        let source = font.cff2().unwrap();
+        let cff2 = Cff2::from_table_ref(&source);
+        let top_dict = dict::parse(source.top_dict_data()).unwrap();
+        let offset = offset_operand(&top_dict, DictKey::CharstringsOffset).unwrap();
+        let charstrings = read_fonts::tables::postscript::Index2::read(
+            source.offset_data().split_off(offset).unwrap(),
+        )
+        .unwrap();
+        assert!(!cff2.char_strings.is_empty());
+        assert!(cff2
+            .char_strings
+            .iter()
+            .any(|charstring| !charstring.is_empty()));
+        assert_eq!(cff2.char_strings.len(), charstrings.count() as usize);
+        for (i, charstring) in cff2.char_strings.iter().enumerate() {
+            assert_eq!(charstring, charstrings.get(i).unwrap());
+        }
+        assert!(cff2.fd_array[0].private_dict.is_some());
+        assert!(cff2.variation_store.is_some());
+    }
+
+    #[test]
+    fn parse_blended_charstrings() {
+        let cff2 = owned(font_test_data::CANTARELL_VF_TRIMMED);
+        let region_counts = cff2.region_counts();
+        let subrs = cff2.fd_array[0]
+            .private_dict
+            .as_ref()
+            .unwrap()
+            .subrs
+            .as_ref();
+        let mut blend_count = 0;
+        for charstring in cff2
+            .char_strings
+            .iter()
+            .chain(cff2.global_subrs.iter())
+            .chain(subrs.into_iter().flat_map(Index2::iter))
+        {
+            let mut parser = charstring::Parser::new(&region_counts);
+            let tokens = parser.parse(charstring).unwrap();
+            assert_eq!(charstring::encode(&tokens), charstring);
+            assert!(parser.blends().iter().all(|blend| blend.vsindex == 0));
+            blend_count += parser.blends().len();
+        }
+        assert!(blend_count > 0);
+    }
+
+    #[test]
+    fn blended_blue_values() {
+        let mut cff2 = owned(font_test_data::CANTARELL_VF_TRIMMED);
+        let private = cff2.get_private_dict_data(0).unwrap();
+        let blue_values = private
+            .blue_values
+            .iter()
+            .map(|value| (value.default, value.deltas.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            blue_values,
+            [
+                (-10.0, vec![0.0, 0.0]),
+                (0.0, vec![0.0, 0.0]),
+                (482.0, vec![-2.0, 8.0]),
+                (492.0, vec![-2.0, 8.0]),
+                (694.0, vec![0.0, 0.0]),
+                (704.0, vec![0.0, 0.0]),
+                (739.0, vec![0.0, 0.0]),
+                (749.0, vec![0.0, 0.0]),
+            ]
+        );
+        assert!(cff2.get_private_dict_data(1).is_err());
+
+        cff2.set_private_dict_data(0, &private).unwrap();
+        let bytes = crate::dump_table(&cff2).unwrap();
+        let reparsed = Cff2::read(FontData::new(&bytes)).unwrap();
+        assert_eq!(reparsed.get_private_dict_data(0).unwrap(), private);
+    }
+
+    #[test]
+    fn compute_maxstack() {
+        let mut cff2 = owned(font_test_data::CANTARELL_VF_TRIMMED);
+        let top_dict = dict::parse(&cff2.header.top_dict_data).unwrap();
+        assert!(top_dict.iter().all(|entry| entry.key != DictKey::MaxStack));
+        // a subroutine pushes 24 operands for a blend of 16 values in two
+        // regions, which the caller completes with 24 more and the count
+        assert_eq!(cff2.compute_maxstack(), 49);
+
+        cff2.char_strings = Index2::from_bytes(vec![vec![139, 139, 21, 14]]);
+        assert_eq!(cff2.compute_maxstack(), 48);
+
+        // a blend of 20 values with two regions needs 61 operands
+        let mut charstring = vec![139; 60];
+        charstring.extend([139 + 20, 16, 14]);
+        cff2.char_strings = Index2::from_bytes(vec![charstring]);
+        assert_eq!(cff2.compute_maxstack(), 61);
+
+        cff2.update_maxstack();
+        let bytes = crate::dump_table(&cff2).unwrap();
+        let reparsed = Cff2::read(FontData::new(&bytes)).unwrap();
+        let top_dict = dict::parse(&reparsed.header.top_dict_data).unwrap();
+        let entry = top_dict
+            .iter()
+            .find(|entry| entry.key == DictKey::MaxStack)
+            .unwrap();
+        assert_eq!(entry.operands, [dict::Operand::Integer(61)]);
+    }
+
+    #[test]
+    fn set_global_subrs() {
+        // the test font only has local subroutines, so copy them to the
+        // global subroutines and call those instead
+        let mut cff2 = owned(font_test_data::CANTARELL_VF_TRIMMED);
+        let region_counts = cff2.region_counts();
+        let to_global_calls = |data: &[u8]| {
+            let mut tokens = Parser::new(&region_counts).parse(data).unwrap();
+            for token in &mut tokens {
+                if *token == Token::Operator(Operator::CallSubr) {
+                    *token = Token::Operator(Operator::CallGsubr);
+                }
+            }
+            charstring::encode(&tokens)
+        };
+        let source_subrs = cff2.local_subrs()[0]
+            .iter()
+            .map(|subr| to_global_calls(subr))
+            .collect::<Vec<_>>();
+        assert!(!source_subrs.is_empty());
+        cff2.char_strings = Index2::from_bytes(cff2.char_strings.iter().map(to_global_calls));
+        cff2.global_subrs = Index2::from_bytes(&source_subrs);
+        assert_eq!(charstring::subr_bias(source_subrs.len()), 107);
+        let source_char_strings = cff2.char_strings.clone();
+        // the charstrings with every subroutine call inlined
+        let inline = |cff2: &Cff2| {
+            let region_counts = cff2.region_counts();
+            let global_subrs = cff2.global_subrs.iter().collect::<Vec<_>>();
+            let local_subrs = cff2.local_subrs();
+            cff2.char_strings
+                .iter()
+                .enumerate()
+                .map(|(gid, charstring)| {
+                    let local_subrs = local_subrs
+                        .get(cff2.fd_index(gid))
+                        .map_or(&[][..], Vec::as_slice);
+                    Parser::new(&region_counts)
+                        .with_subrs(&global_subrs, local_subrs)
+                        .with_inlined_subrs()
+                        .parse(charstring)
+                        .unwrap()
+                })
+                .collect::<Vec<_>>()
+        };
+        let source_outlines = inline(&cff2);
+
+        // enough subroutines to change the bias to 1131, with the calls
+        // between the source subroutines updated to match
+        let map = SubrMap::rebias(source_subrs.len(), 1240);
+        let mut subrs = source_subrs
+            .iter()
+            .map(|subr| {
+                let mut tokens = Parser::new(&region_counts).parse(subr).unwrap();
+                renumber_subr_calls(&mut tokens, &map, None).unwrap();
+                charstring::encode(&tokens)
+            })
+            .collect::<Vec<_>>();
+        subrs.resize(1240, vec![11]);
+        cff2.set_global_subrs(subrs.clone()).unwrap();
+        assert_eq!(cff2.global_subrs.len(), 1240);
+        assert_ne!(cff2.char_strings, source_char_strings);
+        assert_eq!(inline(&cff2), source_outlines);
+        let bytes = crate::dump_table(&cff2).unwrap();
+        let reparsed = Cff2::read(FontData::new(&bytes)).unwrap();
+        assert_eq!(inline(&reparsed), source_outlines);
+
+        // the same bias leaves the charstrings unchanged
+        subrs.resize(1300, vec![11]);
+        let char_strings = cff2.char_strings.clone();
+        cff2.set_global_subrs(subrs).unwrap();
+        assert_eq!(cff2.char_strings, char_strings);
+        assert_eq!(inline(&cff2), source_outlines);
+    }
+
+    #[test]
+    fn instance_at_default_location() {
+        let cff2 = owned(font_test_data::CANTARELL_VF_TRIMMED);
+        let cff = cff2.instance(&[]).unwrap();
+        assert!(cff.is_cid_keyed());
+        let bytes = crate::dump_table(&cff).unwrap();
+        let reparsed = Cff::read(FontData::new(&bytes)).unwrap();
+        assert!(reparsed.is_cid_keyed());
+        assert_eq!(
+            reparsed.font_tables[0].char_strings,
+            cff.font_tables[0].char_strings
+        );
+        let char_strings = &cff.font_tables[0].char_strings;
+        assert_eq!(char_strings.len(), cff2.char_strings.len());
+        for charstring in char_strings.iter() {
+            let tokens = charstring::parse(charstring, &[]).unwrap();
+            assert_eq!(tokens.last(), Some(&Token::Operator(Operator::EndChar)));
+            assert!(!tokens.iter().any(|token| matches!(
+                token,
+                Token::Operator(
+                    Operator::Blend
+                        | Operator::VariationStoreIndex
+                        | Operator::CallSubr
+                        | Operator::CallGsubr
+                )
+            )));
+        }
+        let private = cff2.get_private_dict_data(0).unwrap();
+        let instanced = reparsed.font_tables[0].fd_array[0]
+            .private_dict
+            .as_ref()
+            .map(|private| PrivateDictData::parse(&private.dict, &[]).unwrap())
+            .unwrap();
+        assert_eq!(instanced.vsindex, None);
+        assert_eq!(instanced.blue_values.len(), private.blue_values.len());
+        for (instanced, value) in instanced.blue_values.iter().zip(&private.blue_values) {
+            assert_eq!(instanced.default, value.default);
+            assert!(instanced.deltas.is_empty());
+        }
+
+        // the same location given explicitly
+        let axis_count = cff2
+            .variation_store
+            .as_ref()
+            .unwrap()
+            .variation_region_list
+            .axis_count as usize;
+        let coords = vec![F2Dot14::ZERO; axis_count];
+        assert_eq!(cff2.instance(&coords).unwrap(), cff);
+        // and a location where the outlines differ
+        let coords = vec![F2Dot14::ONE; axis_count];
+        let other = cff2.instance(&coords).unwrap();
+        assert_ne!(other.font_tables[0].char_strings, *char_strings);
+    }
+
+    #[test]
+    fn instance_at_fractional_location() {
+        let cff2 = owned(font_test_data::CANTARELL_VF_TRIMMED);
+        let axis_count = cff2
+            .variation_store
+            .as_ref()
+            .unwrap()
+            .variation_region_list
+            .axis_count as usize;
+        // blended coordinates are fractional between the masters
+        let coords = vec![F2Dot14::from_f32(0.3); axis_count];
+        let cff = cff2.instance(&coords).unwrap();
+        for charstring in cff.font_tables[0].char_strings.iter() {
+            let tokens = charstring::parse(charstring, &[]).unwrap();
+            assert!(tokens
+                .iter()
+                .all(|token| !matches!(token, Token::Operand(charstring::Operand::Fixed(_)))));
+        }
+        for _ in 0..5 {
+            assert_eq!(cff2.instance(&coords).unwrap(), cff);
+        }
+    }
+
+    #[test]
+    fn top_dict_length() {
+        let mut cff2 = owned(font_test_data::CANTARELL_VF_TRIMMED);
+        assert_eq!(
+            cff2.header.top_dict_length as usize,
+            cff2.header.top_dict_data.len()
+        );
+        // offsets may be encoded with a different width when written
+        let bytes = crate::dump_table(&cff2).unwrap();
+        let written_len = Cff2::read(FontData::new(&bytes))
+            .unwrap()
+            .header
+            .top_dict_length;
+        let mut matrix = Vec::new();
+        for value in [0.001, 0.0, 0.0, 0.001, 0.0, 0.0] {
+            dict::encode_number(value, &mut matrix);
+        }
+        cff2.header.top_dict_data = dict::rewrite(
+            &cff2.header.top_dict_data,
+            &[(DictKey::FontMatrix, Some(matrix.clone()))],
+        );
+        let bytes = crate::dump_table(&cff2).unwrap();
+        let reparsed = Cff2::read(FontData::new(&bytes)).unwrap();
+        // the matrix and the two byte operator are added
+        assert_eq!(
+            reparsed.header.top_dict_length,
+            written_len + matrix.len() as u16 + 2
+        );
+        assert_eq!(
+            reparsed.header.top_dict_length as usize,
+            reparsed.header.top_dict_data.len()
+        );
+        let entries = dict::parse(&reparsed.header.top_dict_data).unwrap();
+        assert!(entries.iter().any(|entry| entry.key == DictKey::FontMatrix));
+
+        // a Top DICT that can't fit in the header field
+        let mut large = Vec::new();
+        for _ in 0..40_000 {
+            dict::encode_int(1000, &mut large);
+        }
+        cff2.header.top_dict_data = dict::rewrite(
+            &cff2.header.top_dict_data,
+            &[(DictKey::FontMatrix, Some(large))],
+        );
+        assert!(crate::dump_table(&cff2).is_err());
+    }
+
+    #[test]
+    fn variation_store_too_large() {
+        use crate::tables::variations::{RegionAxisCoordinates, VariationRegionList};
+
+        let mut cff2 = owned(font_test_data::CANTARELL_VF_TRIMMED);
+        let region = VariationRegion::new(vec![RegionAxisCoordinates::new(
+            F2Dot14::ZERO,
+            F2Dot14::ONE,
+            F2Dot14::ONE,
+        )]);
+        // each region of a single axis takes six bytes
+        let store = ItemVariationStore::new(
+            VariationRegionList::new(1, vec![region; 12_000]),
+            Vec::new(),
+        );
+        cff2.variation_store = Some(store);
+        let err = cff2.validate().unwrap_err();
+        assert!(err.to_string().contains("variation store is"));
+        assert!(crate::dump_table(&cff2).is_err());
+    }
+
+    #[test]
+    fn instance_with_too_many_glyphs() {
+        let mut cff2 = owned(font_test_data::CANTARELL_VF_TRIMMED);
+        cff2.char_strings = Index2::from_bytes(vec![b"".as_slice(); u16::MAX as usize + 1]);
+        assert!(matches!(
+            cff2.instance(&[]),
+            Err(CffError::GlyphIndexOutOfBounds(u16::MAX))
+        ));
+    }
+
+    #[test]
+    fn build_variable_font() {
+        use crate::tables::{
+            postscript::BlendedValue,
+            variations::{ItemVariationData, RegionAxisCoordinates, VariationRegionList},
+        };
+        use charstring::{Operand::Integer, Operator, Token::*};
+
+        // a single region peaking at the maximum of one axis
+        let region = VariationRegion::new(vec![RegionAxisCoordinates::new(
+            F2Dot14::ZERO,
+            F2Dot14::ONE,
+            F2Dot14::ONE,
+        )]);
+        let store = ItemVariationStore::new(
+            VariationRegionList::new(1, vec![region]),
+            vec![Some(ItemVariationData::new(0, 0, vec![0], Vec::new()))],
+        );
+        let mut builder = Cff2Builder::new(Some(store.clone()));
+        let private = PrivateDictData {
+            std_vw: Some(BlendedValue {
+                default: 80.0,
+                deltas: vec![40.0],
+            }),
+            ..Default::default()
+        };
+        let fd_index = builder.add_font_dict(private);
+        assert_eq!(builder.add_glyph(Vec::new(), fd_index), 0);
+        // a 100 unit line, extended by 50 units at the maximum
+        let line = [
+            Operand(Integer(0)),
+            Operand(Integer(0)),
+            Operator(Operator::RMoveTo),
+            Operand(Integer(100)),
+            Operand(Integer(50)),
+            Operand(Integer(1)),
+            Operator(Operator::Blend),
+            Operand(Integer(0)),
+            Operator(Operator::RLineTo),
+        ];
+        assert_eq!(builder.add_glyph(charstring::encode(&line), fd_index), 1);
+        let cff2 = builder.build().unwrap();
+        assert_eq!(cff2.fd_select, None);
+        assert_eq!(cff2.compute_maxstack(), 48);
+
+        let bytes = crate::dump_table(&cff2).unwrap();
+        let source = read_fonts::tables::cff2::Cff2::read(FontData::new(&bytes)).unwrap();
+        let top_dict = dict::parse(source.top_dict_data()).unwrap();
+        assert!(top_dict.iter().any(|entry| entry.key == DictKey::MaxStack));
+        let offset = offset_operand(&top_dict, DictKey::VariationStoreOffset).unwrap();
+        let read_store = read_fonts::tables::variations::ItemVariationStore::read(
+            source.offset_data().split_off(offset + 2).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(read_store.variation_region_list().unwrap().axis_count(), 1);
+        assert_eq!(read_store.item_variation_data_count(), 1);
+
+        let reparsed = Cff2::read(FontData::new(&bytes)).unwrap();
+        assert_eq!(reparsed.variation_store, Some(store));
+        assert_eq!(reparsed.char_strings.len(), 2);
+        assert_eq!(
+            reparsed.get_private_dict_data(0).unwrap().std_vw,
+            Some(BlendedValue {
+                default: 80.0,
+                deltas: vec![40.0],
+            })
+        );
+        // the blend is resolved when instancing at the maximum
+        let instance = reparsed.instance(&[F2Dot14::ONE]).unwrap();
+        let tokens =
+            charstring::parse(instance.font_tables[0].char_strings.get(1).unwrap(), &[]).unwrap();
+        assert_eq!(tokens[3], Operand(Integer(150)));
+
+        let mut builder = Cff2Builder::new(None);
+        builder.add_glyph(Vec::new(), 1);
+        assert!(matches!(
+            builder.build(),
+            Err(CffError::FdIndexOutOfBounds(1))
+        ));
+    }
+
+    #[test]
+    fn font_dict_name_is_preserved() {
+        let mut cff2 = owned(font_test_data::CANTARELL_VF_TRIMMED);
+        let entries = dict::parse(&cff2.fd_array[0].dict).unwrap();
+        assert!(entries.iter().all(|entry| entry.key != DictKey::FontName));
+        // without a string INDEX the identifier is retained as it is
+        let font_name = dict::Entry::new(DictKey::FontName, vec![dict::Operand::Integer(391)]);
+        font_name.encode(&mut cff2.fd_array[0].dict);
+
+        let bytes = crate::dump_table(&cff2).unwrap();
+        let reparsed = Cff2::read(FontData::new(&bytes)).unwrap();
+        let entries = dict::parse(&reparsed.fd_array[0].dict).unwrap();
+        assert!(entries.contains(&font_name));
+        assert_eq!(
+            reparsed.get_private_dict_data(0).unwrap(),
+            cff2.get_private_dict_data(0).unwrap()
+        );
+    }
+}
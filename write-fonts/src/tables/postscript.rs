@@ -0,0 +1,247 @@
+//! PostScript (CFF and CFF2) common tables.
+
+use std::fmt;
+
+mod index;
+mod private_dict;
+mod subroutinize;
+
+pub mod charstring;
+pub mod dict;
+
+include!("../../generated/generated_postscript.rs");
+
+pub(crate) use index::{check_wellformed_offsets, min_offset_size};
+pub use index::{CffIndex, CowIndex1};
+pub use private_dict::{BlendedValue, FontDict, PrivateDict, PrivateDictData};
+pub use read_fonts::tables::postscript::StringId;
+pub(crate) use subroutinize::subroutinize;
+
+/// Errors that can occur when inspecting or modifying CFF and CFF2 tables.
+#[derive(Clone, Debug)]
+pub enum CffError {
+    /// The requested font is not present in the font set.
+    FontIndexOutOfBounds(usize),
+    /// The string identifier does not refer to a standard or custom string.
+    InvalidStringId(StringId),
+    /// The requested Font DICT is not present in the FDArray.
+    FdIndexOutOfBounds(usize),
+    /// The requested glyph is not present in the font.
+    GlyphIndexOutOfBounds(u16),
+    /// The offsets of an object in an INDEX are malformed.
+    InvalidOffsetRange,
+    /// DICT data could not be parsed.
+    InvalidDict,
+    /// A charstring could not be parsed.
+    InvalidCharstring,
+    /// The operation is not supported for CID-keyed fonts.
+    CidFontUnsupported,
+    /// The operation is only supported for CID-keyed fonts.
+    NameKeyedFontUnsupported,
+    /// The charstrings are of a type other than Type 2, as given by the
+    /// `CharstringType` operator.
+    UnsupportedCharstringType(i32),
+    /// A FontMatrix has a determinant of zero, so it can't be inverted.
+    DegenerateFontMatrix,
+    /// A new glyph order isn't a permutation of all glyphs that keeps
+    /// `.notdef` as glyph 0.
+    InvalidGlyphOrder,
+    /// A DICT contains an operator that isn't defined by the specification,
+    /// given by its opcode.
+    ///
+    /// Two byte operators are represented as `0x0c00 | second_byte`.
+    UnknownOperator(u16),
+    /// Adding a string would make the string INDEX larger than the limit
+    /// set with [`Cff::set_string_index_limit`](crate::tables::cff::Cff::set_string_index_limit),
+    /// or would need a string identifier larger than 65535.
+    StringIndexTooLarge,
+    /// Two tables that are combined have different variation stores.
+    IncompatibleVariationStores,
+    /// The glyph names or CIDs for a new charset don't give a distinct
+    /// identifier to each glyph, beginning with `.notdef` or CID 0.
+    InvalidCharset,
+    /// A CID-keyed font has more than one Font DICT, so its hinting can't be
+    /// represented by the single Private DICT of a name-keyed font.
+    MultipleFontDicts,
+    /// An error occurred when parsing the source data.
+    Read(ReadError),
+    /// An error occurred when serializing the table.
+    Write(crate::error::Error),
+}
+
+impl From<ReadError> for CffError {
+    fn from(value: ReadError) -> Self {
+        Self::Read(value)
+    }
+}
+
+impl From<crate::error::Error> for CffError {
+    fn from(value: crate::error::Error) -> Self {
+        Self::Write(value)
+    }
+}
+
+impl fmt::Display for CffError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CffError::FontIndexOutOfBounds(index) => {
+                write!(f, "no font at index {index} in the font set")
+            }
+            CffError::InvalidStringId(sid) => {
+                write!(f, "string id {} does not refer to a string", sid.to_u16())
+            }
+            CffError::FdIndexOutOfBounds(index) => {
+                write!(f, "no Font DICT at index {index} in the FDArray")
+            }
+            CffError::GlyphIndexOutOfBounds(gid) => write!(f, "no glyph with index {gid}"),
+            CffError::InvalidOffsetRange => write!(f, "malformed INDEX offsets"),
+            CffError::InvalidDict => write!(f, "malformed DICT data"),
+            CffError::InvalidCharstring => write!(f, "malformed charstring"),
+            CffError::CidFontUnsupported => {
+                write!(f, "operation not supported for CID-keyed fonts")
+            }
+            CffError::NameKeyedFontUnsupported => {
+                write!(f, "operation not supported for name-keyed fonts")
+            }
+            CffError::UnsupportedCharstringType(charstring_type) => {
+                write!(f, "charstring type {charstring_type} is not supported")
+            }
+            CffError::DegenerateFontMatrix => write!(f, "FontMatrix is degenerate"),
+            CffError::InvalidGlyphOrder => write!(f, "invalid glyph order"),
+            CffError::UnknownOperator(opcode) => write!(f, "unknown DICT operator {opcode:#06x}"),
+            CffError::StringIndexTooLarge => {
+                write!(
+                    f,
+                    "string INDEX exceeds the size limit or the number of string ids"
+                )
+            }
+            CffError::IncompatibleVariationStores => {
+                write!(f, "tables have different variation stores")
+            }
+            CffError::InvalidCharset => write!(f, "invalid glyph names or CIDs for a charset"),
+            CffError::MultipleFontDicts => write!(f, "font has more than one Font DICT"),
+            CffError::Read(err) => write!(f, "failed to read source data: {err}"),
+            CffError::Write(err) => write!(f, "failed to write table: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for CffError {}
+
+impl FdSelect {
+    /// Creates the smallest CFF FDSelect for the given Font DICT index of
+    /// each glyph.
+    ///
+    /// Format 3 is used if it is smaller than format 0; the sentinel is the
+    /// number of glyphs.
+    pub fn from_fds(fds: &[u8]) -> Self {
+        let mut ranges: Vec<FdSelectRange3> = Vec::new();
+        for (gid, fd) in fds.iter().enumerate() {
+            if ranges.last().map(|range| range.fd) != Some(*fd) {
+                ranges.push(FdSelectRange3::new(gid as u16, *fd));
+            }
+        }
+        // format, count, ranges and sentinel vs. format and one byte per glyph
+        if 2 + 3 * ranges.len() + 2 < fds.len() {
+            FdSelect::format_3(ranges, fds.len() as u16)
+        } else {
+            FdSelect::format_0(fds.to_vec())
+        }
+    }
+
+    /// Returns the equivalent CFF FDSelect in the smallest format, covering
+    /// the given number of glyphs.
+    ///
+    /// Glyphs that aren't covered select the first Font DICT. Format 4, which
+    /// is only used in CFF2, is returned unchanged.
+    pub(crate) fn compact(&self, num_glyphs: usize) -> Self {
+        if let FdSelect::Format4(_) = self {
+            return self.clone();
+        }
+        let fds = (0..num_glyphs as u32)
+            .map(|gid| self.font_index(gid).unwrap_or_default() as u8)
+            .collect::<Vec<_>>();
+        Self::from_fds(&fds)
+    }
+
+    /// Returns the index of the Font DICT selected for the given glyph.
+    pub(crate) fn font_index(&self, glyph_id: u32) -> Option<usize> {
+        match self {
+            FdSelect::Format0(table) => table.fds.get(glyph_id as usize).map(|fd| *fd as usize),
+            FdSelect::Format3(table) => (glyph_id < table.sentinel as u32)
+                .then(|| {
+                    table
+                        .ranges
+                        .iter()
+                        .rev()
+                        .find(|r| r.first as u32 <= glyph_id)
+                })
+                .flatten()
+                .map(|range| range.fd as usize),
+            FdSelect::Format4(table) => (glyph_id < table.sentinel)
+                .then(|| table.ranges.iter().rev().find(|r| r.first <= glyph_id))
+                .flatten()
+                .map(|range| range.fd as usize),
+        }
+    }
+}
+
+impl CustomCharset {
+    /// Creates the smallest charset for the given string identifiers, or
+    /// CIDs, of the glyphs following `.notdef`.
+    ///
+    /// Format 0 lists each identifier, while formats 1 and 2 encode runs of
+    /// consecutive identifiers as ranges, which are limited to 256 and 65536
+    /// identifiers respectively. The format with the smallest encoding is
+    /// chosen, preferring the lower format if the sizes are equal.
+    pub fn from_sids(sids: &[u16]) -> Self {
+        let ranges1 = charset_runs(sids, u8::MAX as usize);
+        let ranges2 = charset_runs(sids, u16::MAX as usize);
+        // the size of each format, excluding the format byte
+        let sizes = [2 * sids.len(), 3 * ranges1.len(), 4 * ranges2.len()];
+        let smallest = sizes.iter().min().copied().unwrap_or_default();
+        match sizes.iter().position(|size| *size == smallest) {
+            Some(1) => CustomCharset::format_1(
+                ranges1
+                    .into_iter()
+                    .map(|(first, n_left)| CharsetRange1::new(first, n_left as u8))
+                    .collect(),
+            ),
+            Some(2) => CustomCharset::format_2(
+                ranges2
+                    .into_iter()
+                    .map(|(first, n_left)| CharsetRange2::new(first, n_left as u16))
+                    .collect(),
+            ),
+            _ => CustomCharset::format_0(sids.to_vec()),
+        }
+    }
+}
+
+/// Returns the runs of consecutive identifiers as `(first, n_left)` pairs,
+/// with at most `max_n_left` identifiers following the first of each run.
+fn charset_runs(sids: &[u16], max_n_left: usize) -> Vec<(u16, usize)> {
+    let mut runs: Vec<(u16, usize)> = Vec::new();
+    for sid in sids {
+        match runs.last_mut() {
+            Some((first, n_left))
+                if *n_left < max_n_left && *first as usize + *n_left + 1 == *sid as usize =>
+            {
+                *n_left += 1
+            }
+            _ => runs.push((*sid, 0)),
+        }
+    }
+    runs
+}
+
+/// Serializes an object containing no offsets into a vector of bytes.
+pub(crate) fn write_to_vec(obj: &impl FontWrite) -> Vec<u8> {
+    let mut writer = TableWriter::default();
+    obj.write_into(&mut writer);
+    writer.into_data().bytes
+}
+
+pub(crate) fn split_off(data: FontData, offset: usize) -> Result<FontData, ReadError> {
+    data.split_off(offset).ok_or(ReadError::OutOfBounds)
+}
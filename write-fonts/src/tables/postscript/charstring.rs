@@ -0,0 +1,1434 @@
+//! Parsing and encoding of Type 2 and CFF2 charstrings.
+//!
+//! This works at the level of individual operands and operators, without
+//! interpreting the outline, so that a charstring can be modified and
+//! written back without loss. The outline of a glyph can be obtained with
+//! [`Cff::outline`](crate::tables::cff::Cff::outline), which sends the
+//! resulting path to an [`OutlinePen`].
+//!
+//! Operations that compute new coordinates, such as [`scale`] and
+//! [`resolve_blends`], write integer operands, rounded with
+//! [`round_coordinate`].
+
+use types::Fixed;
+
+use super::CffError;
+
+/// The escape byte introducing a two byte operator.
+const ESCAPE: u8 = 12;
+
+/// The maximum nesting depth of subroutine calls.
+const MAX_SUBR_DEPTH: usize = 10;
+
+/// The data of each subroutine in an INDEX.
+type Subrs<'a> = &'a [&'a [u8]];
+
+/// Receives the path commands of an outline.
+///
+/// The hint operators and the advance width of a charstring are not
+/// reported. Each contour begins with `move_to` and ends with `close`.
+pub trait OutlinePen {
+    /// Begins a new contour at the given point.
+    fn move_to(&mut self, x: f64, y: f64);
+    /// Adds a straight line to the given point.
+    fn line_to(&mut self, x: f64, y: f64);
+    /// Adds a cubic Bézier curve with the given control points, ending at
+    /// `(x, y)`.
+    fn curve_to(&mut self, cx0: f64, cy0: f64, cx1: f64, cy1: f64, x: f64, y: f64);
+    /// Closes the current contour.
+    fn close(&mut self);
+}
+
+/// Forwards the commands produced by the read-fonts interpreter to a pen.
+pub(crate) struct PenSink<'a, P>(pub(crate) &'a mut P);
+
+impl<P: OutlinePen> read_fonts::tables::postscript::charstring::CommandSink for PenSink<'_, P> {
+    fn move_to(&mut self, x: Fixed, y: Fixed) {
+        self.0.move_to(x.to_f64(), y.to_f64());
+    }
+
+    fn line_to(&mut self, x: Fixed, y: Fixed) {
+        self.0.line_to(x.to_f64(), y.to_f64());
+    }
+
+    fn curve_to(&mut self, cx0: Fixed, cy0: Fixed, cx1: Fixed, cy1: Fixed, x: Fixed, y: Fixed) {
+        let [cx0, cy0, cx1, cy1, x, y] = [cx0, cy0, cx1, cy1, x, y].map(Fixed::to_f64);
+        self.0.curve_to(cx0, cy0, cx1, cy1, x, y);
+    }
+
+    fn close(&mut self) {
+        self.0.close();
+    }
+}
+
+/// A charstring operator.
+///
+/// See "Appendix A Type 2 Charstring Command Codes" at
+/// <https://adobe-type-tools.github.io/font-tech-notes/pdfs/5177.Type2.pdf>
+/// and <https://learn.microsoft.com/en-us/typography/opentype/spec/cff2charstr>
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Operator {
+    HStem,
+    VStem,
+    VMoveTo,
+    RLineTo,
+    HLineTo,
+    VLineTo,
+    RrCurveTo,
+    CallSubr,
+    Return,
+    EndChar,
+    VariationStoreIndex,
+    Blend,
+    HStemHm,
+    HintMask,
+    CntrMask,
+    RMoveTo,
+    HMoveTo,
+    VStemHm,
+    RCurveLine,
+    RLineCurve,
+    VvCurveTo,
+    HhCurveTo,
+    CallGsubr,
+    VhCurveTo,
+    HvCurveTo,
+    HFlex,
+    Flex,
+    HFlex1,
+    Flex1,
+    /// Any other operator, including the deprecated arithmetic and storage
+    /// operators.
+    ///
+    /// Two byte operators are represented as `0x0c00 | second_byte`.
+    Unknown(u16),
+}
+
+impl Operator {
+    /// Returns the operator for the given opcode.
+    ///
+    /// Two byte operators are represented as `0x0c00 | second_byte`.
+    pub fn from_opcode(opcode: u16) -> Self {
+        use Operator::*;
+        match opcode {
+            1 => HStem,
+            3 => VStem,
+            4 => VMoveTo,
+            5 => RLineTo,
+            6 => HLineTo,
+            7 => VLineTo,
+            8 => RrCurveTo,
+            10 => CallSubr,
+            11 => Return,
+            14 => EndChar,
+            15 => VariationStoreIndex,
+            16 => Blend,
+            18 => HStemHm,
+            19 => HintMask,
+            20 => CntrMask,
+            21 => RMoveTo,
+            22 => HMoveTo,
+            23 => VStemHm,
+            24 => RCurveLine,
+            25 => RLineCurve,
+            26 => VvCurveTo,
+            27 => HhCurveTo,
+            29 => CallGsubr,
+            30 => VhCurveTo,
+            31 => HvCurveTo,
+            0x0c22 => HFlex,
+            0x0c23 => Flex,
+            0x0c24 => HFlex1,
+            0x0c25 => Flex1,
+            other => Unknown(other),
+        }
+    }
+
+    /// Returns the opcode for this operator.
+    ///
+    /// Two byte operators are represented as `0x0c00 | second_byte`.
+    pub fn opcode(self) -> u16 {
+        use Operator::*;
+        match self {
+            HStem => 1,
+            VStem => 3,
+            VMoveTo => 4,
+            RLineTo => 5,
+            HLineTo => 6,
+            VLineTo => 7,
+            RrCurveTo => 8,
+            CallSubr => 10,
+            Return => 11,
+            EndChar => 14,
+            VariationStoreIndex => 15,
+            Blend => 16,
+            HStemHm => 18,
+            HintMask => 19,
+            CntrMask => 20,
+            RMoveTo => 21,
+            HMoveTo => 22,
+            VStemHm => 23,
+            RCurveLine => 24,
+            RLineCurve => 25,
+            VvCurveTo => 26,
+            HhCurveTo => 27,
+            CallGsubr => 29,
+            VhCurveTo => 30,
+            HvCurveTo => 31,
+            HFlex => 0x0c22,
+            Flex => 0x0c23,
+            HFlex1 => 0x0c24,
+            Flex1 => 0x0c25,
+            Unknown(opcode) => opcode,
+        }
+    }
+}
+
+/// Writes the name of the operator used in the Type 2 charstring format
+/// specification, or `op12.N` or `opN` for an unknown operator.
+impl std::fmt::Display for Operator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use Operator::*;
+        let name = match self {
+            HStem => "hstem",
+            VStem => "vstem",
+            VMoveTo => "vmoveto",
+            RLineTo => "rlineto",
+            HLineTo => "hlineto",
+            VLineTo => "vlineto",
+            RrCurveTo => "rrcurveto",
+            CallSubr => "callsubr",
+            Return => "return",
+            EndChar => "endchar",
+            VariationStoreIndex => "vsindex",
+            Blend => "blend",
+            HStemHm => "hstemhm",
+            HintMask => "hintmask",
+            CntrMask => "cntrmask",
+            RMoveTo => "rmoveto",
+            HMoveTo => "hmoveto",
+            VStemHm => "vstemhm",
+            RCurveLine => "rcurveline",
+            RLineCurve => "rlinecurve",
+            VvCurveTo => "vvcurveto",
+            HhCurveTo => "hhcurveto",
+            CallGsubr => "callgsubr",
+            VhCurveTo => "vhcurveto",
+            HvCurveTo => "hvcurveto",
+            HFlex => "hflex",
+            Flex => "flex",
+            HFlex1 => "hflex1",
+            Flex1 => "flex1",
+            Unknown(opcode) if opcode >> 8 == ESCAPE as u16 => {
+                return write!(f, "op{ESCAPE}.{}", opcode & 0xFF)
+            }
+            Unknown(opcode) => return write!(f, "op{opcode}"),
+        };
+        f.write_str(name)
+    }
+}
+
+/// An operand in a charstring.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Operand {
+    /// An integer in the range -32768..=32767.
+    Integer(i32),
+    /// A 16.16 fixed point number.
+    Fixed(Fixed),
+}
+
+impl Operand {
+    /// Returns the value of the operand as a fixed point number.
+    pub fn to_fixed(self) -> Fixed {
+        match self {
+            Operand::Integer(value) => Fixed::from_i32(value),
+            Operand::Fixed(value) => value,
+        }
+    }
+}
+
+/// An element of a charstring.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Token {
+    Operand(Operand),
+    Operator(Operator),
+    /// A `hintmask` operator and the mask that follows it.
+    HintMask(Vec<u8>),
+    /// A `cntrmask` operator and the mask that follows it.
+    CntrMask(Vec<u8>),
+}
+
+/// A `blend` operator encountered while parsing a CFF2 charstring.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BlendInfo {
+    /// The index of the item variation data providing the regions.
+    pub vsindex: usize,
+    /// The number of regions in the item variation data.
+    pub region_count: usize,
+    /// The number of values produced by the operator.
+    pub value_count: usize,
+}
+
+/// A subroutine call encountered while parsing a charstring.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SubrCall {
+    /// `true` for a call to a global subroutine.
+    pub global: bool,
+    /// The unbiased index of the subroutine.
+    pub index: usize,
+    /// The number of stem hints declared before the call.
+    pub stem_count: usize,
+}
+
+/// A parser for charstrings.
+///
+/// The parser tracks the number of stem hints, which determines the size of
+/// the masks following the `hintmask` and `cntrmask` operators, and for CFF2
+/// the active `vsindex`, which determines the number of operands consumed by
+/// the `blend` operator.
+///
+/// By default subroutines are not followed; if stem hints are declared in a
+/// subroutine the initial count can be provided with
+/// [`Parser::with_stem_count`], or the subroutines can be provided with
+/// [`Parser::with_subrs`].
+#[derive(Clone, Debug, Default)]
+pub struct Parser<'a> {
+    region_counts: &'a [usize],
+    subrs: Option<(Subrs<'a>, Subrs<'a>)>,
+    depth: usize,
+    vsindex: usize,
+    stem_count: usize,
+    stack: Vec<Operand>,
+    max_stack: usize,
+    blends: Vec<BlendInfo>,
+    subr_calls: Vec<SubrCall>,
+    inline_subrs: bool,
+    /// The tokens of the most recently parsed subroutine, when inlining.
+    inlined: Vec<Token>,
+}
+
+impl<'a> Parser<'a> {
+    /// Creates a new parser.
+    ///
+    /// `region_counts` contains the number of regions for each item
+    /// variation data in the variation store of a CFF2 table, and should be
+    /// empty for CFF.
+    pub fn new(region_counts: &'a [usize]) -> Self {
+        Parser {
+            region_counts,
+            ..Default::default()
+        }
+    }
+
+    /// Sets the number of stem hints declared before the charstring.
+    pub fn with_stem_count(mut self, stem_count: usize) -> Self {
+        self.stem_count = stem_count;
+        self
+    }
+
+    /// Sets the global and local subroutines, which are then followed by
+    /// the parser to track the effect of calls on its state.
+    ///
+    /// The tokens of a subroutine are not included in the output of
+    /// [`Parser::parse`].
+    pub fn with_subrs(mut self, global_subrs: &'a [&'a [u8]], local_subrs: &'a [&'a [u8]]) -> Self {
+        self.subrs = Some((global_subrs, local_subrs));
+        self
+    }
+
+    /// Replaces each subroutine call in the output of [`Parser::parse`]
+    /// with the tokens of the subroutine, excluding any final `return`.
+    ///
+    /// This has no effect unless subroutines are provided with
+    /// [`Parser::with_subrs`].
+    pub fn with_inlined_subrs(mut self) -> Self {
+        self.inline_subrs = true;
+        self
+    }
+
+    /// The number of stem hints declared so far.
+    pub fn stem_count(&self) -> usize {
+        self.stem_count
+    }
+
+    /// The active `vsindex`, which defaults to zero.
+    pub fn vsindex(&self) -> usize {
+        self.vsindex
+    }
+
+    /// The `blend` operators encountered so far, in order.
+    pub fn blends(&self) -> &[BlendInfo] {
+        &self.blends
+    }
+
+    /// The greatest number of operands on the stack so far, including the
+    /// operands of `blend` operators.
+    pub fn max_stack_depth(&self) -> usize {
+        self.max_stack
+    }
+
+    /// The subroutine calls encountered so far, in order, including those
+    /// made from subroutines.
+    ///
+    /// This is only populated if subroutines are provided with
+    /// [`Parser::with_subrs`].
+    pub fn subr_calls(&self) -> &[SubrCall] {
+        &self.subr_calls
+    }
+
+    /// Parses the given charstring.
+    pub fn parse(&mut self, data: &[u8]) -> Result<Vec<Token>, CffError> {
+        let mut tokens = Vec::new();
+        let mut pos = 0;
+        while pos < data.len() {
+            let b0 = data[pos];
+            let byte = |ix: usize| {
+                data.get(pos + ix)
+                    .copied()
+                    .ok_or(CffError::InvalidCharstring)
+            };
+            let (operand, len) = match b0 {
+                28 => {
+                    let value = i16::from_be_bytes([byte(1)?, byte(2)?]);
+                    (Operand::Integer(value as i32), 3)
+                }
+                32..=246 => (Operand::Integer(b0 as i32 - 139), 1),
+                247..=250 => {
+                    let value = (b0 as i32 - 247) * 256 + byte(1)? as i32 + 108;
+                    (Operand::Integer(value), 2)
+                }
+                251..=254 => {
+                    let value = -(b0 as i32 - 251) * 256 - byte(1)? as i32 - 108;
+                    (Operand::Integer(value), 2)
+                }
+                255 => {
+                    let bits = i32::from_be_bytes([byte(1)?, byte(2)?, byte(3)?, byte(4)?]);
+                    (Operand::Fixed(Fixed::from_bits(bits)), 5)
+                }
+                _ => {
+                    let opcode = if b0 == ESCAPE {
+                        let b1 = byte(1)?;
+                        pos += 2;
+                        0x0c00 | b1 as u16
+                    } else {
+                        pos += 1;
+                        b0 as u16
+                    };
+                    let operator = Operator::from_opcode(opcode);
+                    match operator {
+                        Operator::HintMask | Operator::CntrMask => {
+                            let mask = self.mask(data.get(pos..).unwrap_or_default())?;
+                            pos += mask.len();
+                            tokens.push(match operator {
+                                Operator::HintMask => Token::HintMask(mask),
+                                _ => Token::CntrMask(mask),
+                            });
+                        }
+                        Operator::CallSubr | Operator::CallGsubr
+                            if self.inline_subrs && self.subrs.is_some() =>
+                        {
+                            self.apply(operator)?;
+                            // the subroutine number is the last operand
+                            if let Some(Token::Operand(_)) = tokens.last() {
+                                tokens.pop();
+                            }
+                            let mut subr = std::mem::take(&mut self.inlined);
+                            if subr.last() == Some(&Token::Operator(Operator::Return)) {
+                                subr.pop();
+                            }
+                            tokens.extend(subr);
+                        }
+                        _ => {
+                            self.apply(operator)?;
+                            tokens.push(Token::Operator(operator));
+                        }
+                    }
+                    continue;
+                }
+            };
+            pos += len;
+            self.stack.push(operand);
+            self.max_stack = self.max_stack.max(self.stack.len());
+            tokens.push(Token::Operand(operand));
+        }
+        Ok(tokens)
+    }
+
+    /// Updates the state for an operator other than the mask operators.
+    fn apply(&mut self, operator: Operator) -> Result<(), CffError> {
+        match operator {
+            Operator::HStem | Operator::VStem | Operator::HStemHm | Operator::VStemHm => {
+                // an odd number of operands includes the advance width
+                self.stem_count += self.stack.len() / 2;
+            }
+            Operator::VariationStoreIndex => {
+                let vsindex = match self.stack.pop() {
+                    Some(Operand::Integer(value)) => usize::try_from(value).ok(),
+                    _ => None,
+                };
+                self.vsindex = vsindex
+                    .filter(|ix| *ix < self.region_counts.len())
+                    .ok_or(CffError::InvalidCharstring)?;
+            }
+            Operator::Blend => {
+                let region_count = self
+                    .region_counts
+                    .get(self.vsindex)
+                    .copied()
+                    .ok_or(CffError::InvalidCharstring)?;
+                let value_count = match self.stack.pop() {
+                    Some(Operand::Integer(value)) => {
+                        usize::try_from(value).map_err(|_| CffError::InvalidCharstring)?
+                    }
+                    Some(Operand::Fixed(_)) => return Err(CffError::InvalidCharstring),
+                    // in a subroutine the operands may have been pushed by
+                    // the caller
+                    None => return Ok(()),
+                };
+                let operand_count = value_count * (region_count + 1);
+                let start = self.stack.len().saturating_sub(operand_count);
+                // the results are the default values, adjusted by the deltas
+                self.stack.truncate(start + value_count);
+                self.blends.push(BlendInfo {
+                    vsindex: self.vsindex,
+                    region_count,
+                    value_count,
+                });
+                return Ok(());
+            }
+            Operator::CallSubr | Operator::CallGsubr => {
+                // the subroutine may consume the remaining operands
+                let number = self.stack.pop();
+                if let Some((global_subrs, local_subrs)) = self.subrs {
+                    let global = operator == Operator::CallGsubr;
+                    let subrs = if global { global_subrs } else { local_subrs };
+                    self.call_subr(global, subrs, number)?;
+                }
+                return Ok(());
+            }
+            Operator::Return => return Ok(()),
+            _ => (),
+        }
+        self.stack.clear();
+        Ok(())
+    }
+
+    /// Parses the subroutine with the given biased number.
+    fn call_subr(
+        &mut self,
+        global: bool,
+        subrs: Subrs<'a>,
+        number: Option<Operand>,
+    ) -> Result<(), CffError> {
+        let index = match number {
+            Some(Operand::Integer(number)) => usize::try_from(number + subr_bias(subrs.len())).ok(),
+            _ => None,
+        };
+        let (index, data) = index
+            .and_then(|index| Some((index, *subrs.get(index)?)))
+            .ok_or(CffError::InvalidCharstring)?;
+        if self.depth >= MAX_SUBR_DEPTH {
+            return Err(CffError::InvalidCharstring);
+        }
+        self.subr_calls.push(SubrCall {
+            global,
+            index,
+            stem_count: self.stem_count,
+        });
+        self.depth += 1;
+        let result = self.parse(data);
+        self.depth -= 1;
+        let tokens = result?;
+        if self.inline_subrs {
+            self.inlined = tokens;
+        }
+        Ok(())
+    }
+
+    /// Returns the mask following a `hintmask` or `cntrmask` operator.
+    fn mask(&mut self, data: &[u8]) -> Result<Vec<u8>, CffError> {
+        // operands preceding the first mask are an implicit vstem
+        self.stem_count += self.stack.len() / 2;
+        self.stack.clear();
+        let len = self.stem_count.div_ceil(8);
+        data.get(..len)
+            .map(<[u8]>::to_vec)
+            .ok_or(CffError::InvalidCharstring)
+    }
+}
+
+/// Parses the given charstring, which must not contain stem hints declared
+/// in subroutines.
+pub fn parse(data: &[u8], region_counts: &[usize]) -> Result<Vec<Token>, CffError> {
+    Parser::new(region_counts).parse(data)
+}
+
+/// Returns the bias added to subroutine numbers for an INDEX containing
+/// `count` subroutines.
+pub fn subr_bias(count: usize) -> i32 {
+    match count {
+        0..=1239 => 107,
+        1240..=33899 => 1131,
+        _ => 32768,
+    }
+}
+
+/// Returns the first operator of a Type 2 charstring that is given an
+/// invalid number of operands, along with that number.
+///
+/// The tokens must have their subroutine calls inlined, as operands may be
+/// pushed by a subroutine and consumed by its caller. An optional advance
+/// width before the first stack clearing operator is allowed. Checking
+/// stops at any operator whose effect on the stack isn't known, such as the
+/// arithmetic operators, a subroutine call or `blend`.
+pub fn invalid_operand_count(tokens: &[Token]) -> Option<(Operator, usize)> {
+    let mut count = 0;
+    let mut may_have_width = true;
+    for token in tokens {
+        let operator = match token {
+            Token::Operand(_) => {
+                count += 1;
+                continue;
+            }
+            Token::Operator(operator) => *operator,
+            Token::HintMask(_) => Operator::HintMask,
+            Token::CntrMask(_) => Operator::CntrMask,
+        };
+        let is_valid = |count| is_valid_operand_count(operator, count);
+        let valid = is_valid(count)?;
+        let has_width = may_have_width && takes_width(operator) && count > 0;
+        if !(valid || has_width && is_valid(count - 1)?) {
+            return Some((operator, count));
+        }
+        may_have_width = false;
+        count = 0;
+    }
+    None
+}
+
+/// Returns the first operator of a Type 2 charstring that takes more
+/// operands than are on the stack.
+///
+/// As for [`invalid_operand_count`], the tokens must have their subroutine
+/// calls inlined. Checking stops at any operator whose effect on the stack
+/// depends on the values of its operands, such as `index` and `roll`.
+pub fn stack_underflow(tokens: &[Token]) -> Option<Operator> {
+    let mut depth = 0;
+    for token in tokens {
+        let operator = match token {
+            Token::Operand(_) => {
+                depth += 1;
+                continue;
+            }
+            Token::Operator(operator) => *operator,
+            Token::HintMask(_) => Operator::HintMask,
+            Token::CntrMask(_) => Operator::CntrMask,
+        };
+        let (taken, results) = stack_effect(operator)?;
+        if depth < taken {
+            return Some(operator);
+        }
+        depth = match results {
+            Some(results) => depth - taken + results,
+            // the operator clears the stack
+            None => 0,
+        };
+    }
+    None
+}
+
+/// Returns the number of operands an operator takes from the stack and the
+/// number of results it pushes, or `None` for the results if it clears the
+/// stack.
+///
+/// Returns `None` if the effect isn't known.
+fn stack_effect(operator: Operator) -> Option<(usize, Option<usize>)> {
+    use Operator::*;
+    Some(match operator {
+        HStem | VStem | HStemHm | VStemHm | RMoveTo | RLineTo => (2, None),
+        HMoveTo | VMoveTo | HLineTo | VLineTo => (1, None),
+        HintMask | CntrMask | EndChar => (0, None),
+        RrCurveTo => (6, None),
+        HhCurveTo | VvCurveTo | HvCurveTo | VhCurveTo => (4, None),
+        RCurveLine | RLineCurve => (8, None),
+        HFlex => (7, None),
+        Flex => (13, None),
+        HFlex1 => (9, None),
+        Flex1 => (11, None),
+        Return => (0, Some(0)),
+        Unknown(opcode) => match opcode {
+            // random
+            0x0c17 => (0, Some(1)),
+            // not, abs, neg, sqrt, get
+            0x0c05 | 0x0c09 | 0x0c0e | 0x0c1a | 0x0c15 => (1, Some(1)),
+            // drop
+            0x0c12 => (1, Some(0)),
+            // dup
+            0x0c1b => (1, Some(2)),
+            // and, or, add, sub, div, eq, mul
+            0x0c03 | 0x0c04 | 0x0c0a | 0x0c0b | 0x0c0c | 0x0c0f | 0x0c18 => (2, Some(1)),
+            // put
+            0x0c14 => (2, Some(0)),
+            // exch
+            0x0c1c => (2, Some(2)),
+            // ifelse
+            0x0c16 => (4, Some(1)),
+            _ => return None,
+        },
+        CallSubr | CallGsubr | Blend | VariationStoreIndex => return None,
+    })
+}
+
+/// Returns `true` if a parsed subroutine ends with `return` or `endchar`,
+/// or with a call to a subroutine that does.
+///
+/// A call in a local subroutine refers to the same `local_subrs`, while a
+/// call to a local subroutine in a global subroutine is checked against
+/// each of `local_subrs`. Subroutines that weren't parsed, which are
+/// `None`, are never called from that context and so aren't checked.
+pub(crate) fn subr_terminates(
+    subr: &[Token],
+    global_subrs: &[Option<Vec<Token>>],
+    local_subrs: &[&[Option<Vec<Token>>]],
+) -> bool {
+    fn terminates(
+        subr: &[Token],
+        global_subrs: &[Option<Vec<Token>>],
+        local_subrs: &[&[Option<Vec<Token>>]],
+        depth: usize,
+    ) -> bool {
+        let (global, number) = match subr {
+            [.., Token::Operator(Operator::Return | Operator::EndChar)] => return true,
+            [.., Token::Operand(Operand::Integer(number)), Token::Operator(call @ (Operator::CallSubr | Operator::CallGsubr))] => {
+                (*call == Operator::CallGsubr, *number)
+            }
+            // the number of a trailing call is computed, or there is no call
+            _ => return false,
+        };
+        if depth >= MAX_SUBR_DEPTH {
+            return false;
+        }
+        let callees: &[&[Option<Vec<Token>>]] = match global {
+            true => &[global_subrs],
+            false => local_subrs,
+        };
+        callees.iter().all(|subrs| {
+            let callee = usize::try_from(number + subr_bias(subrs.len()))
+                .ok()
+                .and_then(|index| subrs.get(index)?.as_deref());
+            let Some(callee) = callee else {
+                return true;
+            };
+            match global {
+                true => terminates(callee, global_subrs, local_subrs, depth + 1),
+                false => terminates(callee, global_subrs, &[*subrs], depth + 1),
+            }
+        })
+    }
+    terminates(subr, global_subrs, local_subrs, 0)
+}
+
+/// Returns `true` if `count` operands are valid for the operator, or `None`
+/// if its effect on the stack isn't known.
+fn is_valid_operand_count(operator: Operator, count: usize) -> Option<bool> {
+    use Operator::*;
+    Some(match operator {
+        HStem | VStem | HStemHm | VStemHm => count > 0 && count % 2 == 0,
+        // operands before a mask are an implicit vstem
+        HintMask | CntrMask => count % 2 == 0,
+        RMoveTo => count == 2,
+        HMoveTo | VMoveTo => count == 1,
+        RLineTo => count > 0 && count % 2 == 0,
+        // alternating horizontal and vertical lines
+        HLineTo | VLineTo => count > 0,
+        RrCurveTo => count > 0 && count % 6 == 0,
+        HhCurveTo | VvCurveTo => count >= 4 && count % 4 <= 1,
+        HvCurveTo | VhCurveTo => count >= 4 && matches!(count % 8, 0 | 1 | 4 | 5),
+        RCurveLine => count >= 8 && (count - 2) % 6 == 0,
+        RLineCurve => count >= 8 && count % 2 == 0,
+        HFlex => count == 7,
+        Flex => count == 13,
+        HFlex1 => count == 9,
+        Flex1 => count == 11,
+        // adx ady bchar achar for an accented character
+        EndChar => count == 0 || count == 4,
+        _ => return None,
+    })
+}
+
+/// Returns `true` if the advance width may precede the operator, when it
+/// is the first stack clearing operator of a charstring.
+fn takes_width(operator: Operator) -> bool {
+    use Operator::*;
+    matches!(
+        operator,
+        HStem
+            | VStem
+            | HStemHm
+            | VStemHm
+            | HintMask
+            | CntrMask
+            | RMoveTo
+            | HMoveTo
+            | VMoveTo
+            | EndChar
+    )
+}
+
+/// Scales the coordinates in the given charstring by `factor`, rounding
+/// with [`round_coordinate`].
+///
+/// Subroutine numbers, the operand count of `blend`, the `vsindex`
+/// and the character codes of an `endchar` accent are left unchanged.
+pub fn scale(tokens: &mut [Token], factor: f64) {
+    let mut start = 0;
+    // operands at the end of a subroutine are consumed by the caller
+    for i in 0..=tokens.len() {
+        let operand_count = i - start;
+        // the number of trailing operands that aren't coordinates
+        let keep = match tokens.get(i) {
+            Some(Token::Operand(_)) => continue,
+            Some(Token::Operator(
+                Operator::CallSubr
+                | Operator::CallGsubr
+                | Operator::Blend
+                | Operator::VariationStoreIndex,
+            )) => 1,
+            // adx ady bchar achar, optionally preceded by the width
+            Some(Token::Operator(Operator::EndChar)) if operand_count >= 4 => 2,
+            _ => 0,
+        };
+        for token in &mut tokens[start..i - keep.min(operand_count)] {
+            if let Token::Operand(operand) = token {
+                let value = operand.to_fixed().to_f64() * factor;
+                *operand = Operand::Integer(round_coordinate(value));
+            }
+        }
+        start = i + 1;
+    }
+}
+
+/// Returns the standard encoding codes of the base and accent characters
+/// of a charstring that ends with the `seac` form of `endchar`.
+///
+/// This form, with four operands (`adx ady bchar achar`) or five if the
+/// width is included, composes an accented glyph from two other glyphs.
+/// Subroutine calls should have been inlined with
+/// [`Parser::with_inlined_subrs`], since the operands may be pushed by a
+/// subroutine.
+pub fn seac_codes(tokens: &[Token]) -> Option<(u8, u8)> {
+    let end = tokens
+        .iter()
+        .position(|token| *token == Token::Operator(Operator::EndChar))?;
+    let operands = tokens[..end]
+        .iter()
+        .rev()
+        .take_while(|token| matches!(token, Token::Operand(_)))
+        .collect::<Vec<_>>();
+    let code = |token: &Token| match token {
+        Token::Operand(Operand::Integer(value)) => u8::try_from(*value).ok(),
+        _ => None,
+    };
+    match operands.as_slice() {
+        [achar, bchar, _, _] | [achar, bchar, _, _, _] => Some((code(bchar)?, code(achar)?)),
+        _ => None,
+    }
+}
+
+/// Removes the stem hints and the `hintmask` and `cntrmask` operators from
+/// the given charstring or subroutine, with their operands.
+///
+/// A width preceding the operands of the first stem hint of a charstring
+/// is kept for the following operator. Returns `false`, leaving the tokens
+/// unchanged, if the operands of a hint are not all in `tokens`, since they
+/// may be pushed by a subroutine or by the caller of a subroutine, or if
+/// the width can't be determined.
+pub fn strip_hints(tokens: &mut Vec<Token>, is_subr: bool) -> bool {
+    let mut out = Vec::with_capacity(tokens.len());
+    // the start of the operands following the last operator in `out`
+    let mut start = 0;
+    // whether the operands since `start` are known to be complete
+    let mut complete = !is_subr;
+    let mut stack_cleared = false;
+    for token in tokens.iter() {
+        match token {
+            Token::Operand(_) => {
+                out.push(token.clone());
+                continue;
+            }
+            Token::Operator(
+                Operator::HStem | Operator::VStem | Operator::HStemHm | Operator::VStemHm,
+            )
+            | Token::HintMask(_)
+            | Token::CntrMask(_) => {
+                let operand_count = out.len() - start;
+                let has_width = operand_count % 2 == 1;
+                if !complete || (has_width && (is_subr || stack_cleared)) {
+                    return false;
+                }
+                out.truncate(start + has_width as usize);
+                start += has_width as usize;
+                stack_cleared = true;
+                continue;
+            }
+            Token::Operator(Operator::CallSubr | Operator::CallGsubr) => complete = false,
+            Token::Operator(Operator::Return) => (),
+            Token::Operator(_) => {
+                complete = true;
+                stack_cleared = true;
+            }
+        }
+        out.push(token.clone());
+        start = out.len();
+    }
+    *tokens = out;
+    true
+}
+
+/// Resolves the `blend` and `vsindex` operators of a CFF2 charstring at a
+/// location in the design space, producing a static charstring.
+///
+/// `scalars` contains the scalar of each region of each item variation
+/// data at the location, and `vsindex` is the initial index of the item
+/// variation data, given by the Private DICT. Blended values are rounded
+/// with [`round_coordinate`].
+///
+/// Subroutine calls should have been inlined with
+/// [`Parser::with_inlined_subrs`], since the operands of a `blend` may be
+/// pushed by the caller of a subroutine.
+pub fn resolve_blends(
+    tokens: &[Token],
+    vsindex: usize,
+    scalars: &[Vec<f64>],
+) -> Result<Vec<Token>, CffError> {
+    let mut vsindex = vsindex;
+    let mut out = Vec::with_capacity(tokens.len());
+    // the start of the operands following the last operator in `out`
+    let mut start = 0;
+    for token in tokens {
+        match token {
+            Token::Operand(_) => out.push(token.clone()),
+            Token::Operator(Operator::VariationStoreIndex) => {
+                vsindex = match out.pop() {
+                    Some(Token::Operand(Operand::Integer(value))) => usize::try_from(value).ok(),
+                    _ => None,
+                }
+                .filter(|ix| *ix < scalars.len())
+                .ok_or(CffError::InvalidCharstring)?;
+                out.truncate(start);
+            }
+            Token::Operator(Operator::Blend) => {
+                let scalars = scalars.get(vsindex).ok_or(CffError::InvalidCharstring)?;
+                let value_count = match out.pop() {
+                    Some(Token::Operand(Operand::Integer(value))) => usize::try_from(value).ok(),
+                    _ => None,
+                }
+                .ok_or(CffError::InvalidCharstring)?;
+                let operand_count = value_count * (scalars.len() + 1);
+                let first = out
+                    .len()
+                    .checked_sub(operand_count)
+                    .filter(|first| *first >= start)
+                    .ok_or(CffError::InvalidCharstring)?;
+                let values = out
+                    .split_off(first)
+                    .into_iter()
+                    .map(|token| match token {
+                        Token::Operand(operand) => operand.to_fixed().to_f64(),
+                        _ => 0.0,
+                    })
+                    .collect::<Vec<_>>();
+                let (defaults, deltas) = values.split_at(value_count);
+                for (i, default) in defaults.iter().enumerate() {
+                    let deltas = &deltas[i * scalars.len()..(i + 1) * scalars.len()];
+                    let value = default
+                        + deltas
+                            .iter()
+                            .zip(scalars)
+                            .map(|(delta, scalar)| delta * scalar)
+                            .sum::<f64>();
+                    out.push(Token::Operand(Operand::Integer(round_coordinate(value))));
+                }
+            }
+            _ => {
+                out.push(token.clone());
+                start = out.len();
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// Rounds a computed coordinate to an integer operand.
+///
+/// Values halfway between two integers are rounded to the even one, so
+/// that a blended or scaled outline doesn't drift in one direction, and the
+/// same value always gives the same operand. Values outside the range of
+/// `i32` are clamped.
+pub fn round_coordinate(value: f64) -> i32 {
+    value.round_ties_even() as i32
+}
+
+/// Returns a textual listing of the given tokens, with one line for each
+/// operator preceded by its operands.
+///
+/// The operand of a subroutine call is written as the index of the
+/// subroutine, with the bias for INDEXes of `global_subr_count` or
+/// `local_subr_count` subroutines removed. The mask of `hintmask` and
+/// `cntrmask` is written in binary.
+pub fn disassemble(tokens: &[Token], global_subr_count: usize, local_subr_count: usize) -> String {
+    let mut lines = Vec::new();
+    let mut line = Vec::new();
+    for (i, token) in tokens.iter().enumerate() {
+        match token {
+            Token::Operand(Operand::Integer(value)) => {
+                let bias = match tokens.get(i + 1) {
+                    Some(Token::Operator(Operator::CallSubr)) => subr_bias(local_subr_count),
+                    Some(Token::Operator(Operator::CallGsubr)) => subr_bias(global_subr_count),
+                    _ => 0,
+                };
+                line.push((value + bias).to_string());
+            }
+            Token::Operand(Operand::Fixed(value)) => line.push(value.to_f64().to_string()),
+            Token::Operator(operator) => {
+                line.push(operator.to_string());
+                lines.push(line.join(" "));
+                line.clear();
+            }
+            Token::HintMask(mask) | Token::CntrMask(mask) => {
+                let operator = match token {
+                    Token::HintMask(_) => Operator::HintMask,
+                    _ => Operator::CntrMask,
+                };
+                let mask = mask.iter().map(|byte| format!("{byte:08b}")).collect();
+                line.extend([operator.to_string(), mask]);
+                lines.push(line.join(" "));
+                line.clear();
+            }
+        }
+    }
+    // operands left for the caller of a subroutine
+    if !line.is_empty() {
+        lines.push(line.join(" "));
+    }
+    lines.join("\n")
+}
+
+/// Encodes the given tokens as a charstring.
+pub fn encode(tokens: &[Token]) -> Vec<u8> {
+    let mut out = Vec::new();
+    for token in tokens {
+        match token {
+            Token::Operand(operand) => encode_operand(*operand, &mut out),
+            Token::Operator(operator) => encode_operator(*operator, &mut out),
+            Token::HintMask(mask) => {
+                encode_operator(Operator::HintMask, &mut out);
+                out.extend_from_slice(mask);
+            }
+            Token::CntrMask(mask) => {
+                encode_operator(Operator::CntrMask, &mut out);
+                out.extend_from_slice(mask);
+            }
+        }
+    }
+    out
+}
+
+/// Appends the encoding of the given operator to `out`.
+pub fn encode_operator(operator: Operator, out: &mut Vec<u8>) {
+    let opcode = operator.opcode();
+    if opcode >> 8 == ESCAPE as u16 {
+        out.extend_from_slice(&[ESCAPE, opcode as u8]);
+    } else {
+        out.push(opcode as u8);
+    }
+}
+
+/// Appends the most compact encoding of the given operand to `out`.
+///
+/// Integers outside of the 16-bit range are encoded as fixed point numbers,
+/// saturating at the limits of that range.
+pub fn encode_operand(operand: Operand, out: &mut Vec<u8>) {
+    match operand {
+        Operand::Integer(value @ -1131..=1131) => super::dict::encode_int(value, out),
+        Operand::Integer(value @ -32768..=32767) => {
+            out.push(28);
+            out.extend_from_slice(&(value as i16).to_be_bytes());
+        }
+        Operand::Integer(value) => encode_operand(
+            Operand::Fixed(Fixed::from_i32(value.clamp(-32768, 32767))),
+            out,
+        ),
+        Operand::Fixed(value) => {
+            out.push(255);
+            out.extend_from_slice(&value.to_bits().to_be_bytes());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        // hints with an implicit vstem before the hintmask
+        let tokens = vec![
+            Token::Operand(Operand::Integer(-1200)),
+            Token::Operand(Operand::Integer(30)),
+            Token::Operand(Operand::Integer(20)),
+            Token::Operand(Operand::Integer(50)),
+            Token::Operator(Operator::HStemHm),
+            Token::Operand(Operand::Integer(5)),
+            Token::Operand(Operand::Integer(40)),
+            Token::HintMask(vec![0b1110_0000]),
+            Token::Operand(Operand::Fixed(Fixed::from_f64(1.5))),
+            Token::Operand(Operand::Integer(1000)),
+            Token::Operator(Operator::RMoveTo),
+            Token::Operator(Operator::Flex1),
+            Token::Operator(Operator::Unknown(0x0c0a)),
+            Token::Operator(Operator::EndChar),
+        ];
+        let data = encode(&tokens);
+        assert_eq!(parse(&data, &[]).unwrap(), tokens);
+    }
+
+    #[test]
+    fn operand_counts() {
+        let tokens = |ops: &[(usize, Operator)]| {
+            ops.iter()
+                .flat_map(|(count, operator)| {
+                    (0..*count)
+                        .map(|i| Token::Operand(Operand::Integer(i as i32)))
+                        .chain([Token::Operator(*operator)])
+                })
+                .collect::<Vec<_>>()
+        };
+        use Operator::*;
+        let valid = [
+            tokens(&[(2, RMoveTo), (4, HLineTo), (12, RrCurveTo), (0, EndChar)]),
+            // with a width
+            tokens(&[(3, HStem), (1, VMoveTo), (9, VhCurveTo), (4, EndChar)]),
+            tokens(&[(1, EndChar)]),
+            tokens(&[(4, HStemHm), (2, HintMask), (2, RMoveTo), (8, RLineCurve)]),
+            // a width and an implicit vstem
+            tokens(&[(3, HintMask), (2, RMoveTo)]),
+            // the stack effect of an arithmetic operator is unknown
+            tokens(&[(2, Unknown(0x0c0a)), (3, RMoveTo)]),
+        ];
+        for tokens in valid {
+            assert_eq!(invalid_operand_count(&tokens), None, "{tokens:?}");
+        }
+        assert_eq!(
+            invalid_operand_count(&tokens(&[(2, RMoveTo), (7, RrCurveTo)])),
+            Some((RrCurveTo, 7))
+        );
+        // a width is only allowed before the first operator
+        assert_eq!(
+            invalid_operand_count(&tokens(&[(2, RMoveTo), (3, RMoveTo)])),
+            Some((RMoveTo, 3))
+        );
+        assert_eq!(
+            invalid_operand_count(&tokens(&[(2, RMoveTo), (3, HhCurveTo)])),
+            Some((HhCurveTo, 3))
+        );
+        assert_eq!(
+            invalid_operand_count(&tokens(&[(3, HStem), (5, EndChar)])),
+            Some((EndChar, 5))
+        );
+    }
+
+    #[test]
+    fn stack_underflow() {
+        let tokens = |ops: &[(usize, Operator)]| {
+            ops.iter()
+                .flat_map(|(count, operator)| {
+                    (0..*count)
+                        .map(|i| Token::Operand(Operand::Integer(i as i32)))
+                        .chain([Token::Operator(*operator)])
+                })
+                .collect::<Vec<_>>()
+        };
+        use Operator::*;
+        let (add, drop, dup, index) = (
+            Unknown(0x0c0a),
+            Unknown(0x0c12),
+            Unknown(0x0c1b),
+            Unknown(0x0c1d),
+        );
+        let valid = [
+            tokens(&[(2, RMoveTo), (1, HLineTo), (0, EndChar)]),
+            // dup leaves two values for rmoveto
+            tokens(&[(1, dup), (0, RMoveTo)]),
+            tokens(&[(2, add), (1, RMoveTo)]),
+            // the stack effect of index is unknown
+            tokens(&[(0, index), (0, RMoveTo)]),
+        ];
+        for tokens in valid {
+            assert_eq!(super::stack_underflow(&tokens), None, "{tokens:?}");
+        }
+        assert_eq!(
+            super::stack_underflow(&tokens(&[(2, RMoveTo), (1, RLineTo)])),
+            Some(RLineTo)
+        );
+        assert_eq!(
+            super::stack_underflow(&tokens(&[(1, add), (1, RMoveTo)])),
+            Some(add)
+        );
+        assert_eq!(
+            super::stack_underflow(&tokens(&[(1, drop), (1, RMoveTo)])),
+            Some(RMoveTo)
+        );
+    }
+
+    #[test]
+    fn follow_subrs() {
+        // the subroutine declares the stems used by the hintmask
+        let mut subr = Vec::new();
+        for value in (1..=12).map(|i| i * 10) {
+            encode_operand(Operand::Integer(value), &mut subr);
+        }
+        encode_operator(Operator::HStemHm, &mut subr);
+        encode_operator(Operator::Return, &mut subr);
+        let tokens = vec![
+            Token::Operand(Operand::Integer(-107)),
+            Token::Operator(Operator::CallSubr),
+            Token::Operand(Operand::Integer(10)),
+            Token::Operand(Operand::Integer(20)),
+            Token::Operand(Operand::Integer(30)),
+            Token::Operand(Operand::Integer(40)),
+            Token::Operand(Operand::Integer(50)),
+            Token::Operand(Operand::Integer(60)),
+            Token::HintMask(vec![0xFF, 0x00]),
+            Token::Operator(Operator::EndChar),
+        ];
+        let data = encode(&tokens);
+        // without the subroutine the mask appears to be a single byte
+        assert_ne!(parse(&data, &[]).unwrap(), tokens);
+        let local_subrs = [subr.as_slice()];
+        let mut parser = Parser::new(&[]).with_subrs(&[], &local_subrs);
+        assert_eq!(parser.parse(&data).unwrap(), tokens);
+        assert_eq!(parser.stem_count(), 9);
+        assert_eq!(
+            parser.subr_calls(),
+            [SubrCall {
+                global: false,
+                index: 0,
+                stem_count: 0
+            }]
+        );
+        // a missing subroutine is an error
+        let mut parser = Parser::new(&[]).with_subrs(&[], &[]);
+        assert!(parser.parse(&data).is_err());
+    }
+
+    #[test]
+    fn disassemble_debiases_subrs() {
+        use Operand::*;
+        let tokens = [
+            Token::Operand(Integer(100)),
+            Token::Operand(Fixed(types::Fixed::from_f64(0.5))),
+            Token::Operator(Operator::RMoveTo),
+            Token::Operand(Integer(-107)),
+            Token::Operator(Operator::CallSubr),
+            Token::Operand(Integer(-1130)),
+            Token::Operator(Operator::CallGsubr),
+            Token::HintMask(vec![0b1010_0000]),
+            Token::Operator(Operator::Unknown(0x0c03)),
+            Token::Operator(Operator::EndChar),
+        ];
+        assert_eq!(
+            disassemble(&tokens, 2000, 10),
+            "100 0.5 rmoveto\n0 callsubr\n1 callgsubr\nhintmask 10100000\nop12.3\nendchar"
+        );
+    }
+
+    #[test]
+    fn seac() {
+        let int = |value| Token::Operand(Operand::Integer(value));
+        let mut tokens = vec![
+            int(0),
+            int(100),
+            int(65),
+            int(194),
+            Token::Operator(Operator::EndChar),
+        ];
+        assert_eq!(seac_codes(&tokens), Some((65, 194)));
+        // with a width
+        tokens.insert(0, int(500));
+        assert_eq!(seac_codes(&tokens), Some((65, 194)));
+        // a plain endchar, after a path
+        let tokens = [
+            int(10),
+            int(20),
+            Token::Operator(Operator::RMoveTo),
+            Token::Operator(Operator::EndChar),
+        ];
+        assert_eq!(seac_codes(&tokens), None);
+    }
+
+    #[test]
+    fn strip_hints_keeps_width() {
+        let int = |value| Token::Operand(Operand::Integer(value));
+        let mut tokens = vec![
+            int(-50),
+            int(10),
+            int(20),
+            Token::Operator(Operator::HStemHm),
+            int(5),
+            int(40),
+            Token::HintMask(vec![0b1100_0000]),
+            int(100),
+            int(200),
+            Token::Operator(Operator::RMoveTo),
+            Token::CntrMask(vec![0b1000_0000]),
+            Token::Operator(Operator::EndChar),
+        ];
+        assert!(strip_hints(&mut tokens, false));
+        assert_eq!(
+            tokens,
+            [
+                int(-50),
+                int(100),
+                int(200),
+                Token::Operator(Operator::RMoveTo),
+                Token::Operator(Operator::EndChar),
+            ]
+        );
+        // the operands of the implicit vstem may be pushed by the caller
+        let source = vec![
+            Token::HintMask(vec![0xFF]),
+            int(10),
+            Token::Operator(Operator::HLineTo),
+            Token::Operator(Operator::Return),
+        ];
+        let mut tokens = source.clone();
+        assert!(!strip_hints(&mut tokens, true));
+        assert_eq!(tokens, source);
+        // or by a subroutine
+        let mut tokens = vec![
+            int(-107),
+            Token::Operator(Operator::CallSubr),
+            Token::Operator(Operator::HStem),
+            Token::Operator(Operator::EndChar),
+        ];
+        assert!(!strip_hints(&mut tokens, false));
+    }
+
+    #[test]
+    fn scale_coordinates() {
+        let mut tokens = vec![
+            Token::Operand(Operand::Integer(100)),
+            Token::Operand(Operand::Fixed(Fixed::from_f64(1.5))),
+            Token::Operator(Operator::RMoveTo),
+            Token::Operand(Operand::Integer(5)),
+            Token::Operand(Operand::Integer(-107)),
+            Token::Operator(Operator::CallGsubr),
+            Token::Operand(Operand::Integer(-3)),
+            Token::Operator(Operator::RLineTo),
+            Token::Operand(Operand::Integer(10)),
+            Token::Operand(Operand::Integer(20)),
+            Token::Operand(Operand::Integer(65)),
+            Token::Operand(Operand::Integer(97)),
+            Token::Operator(Operator::EndChar),
+            Token::Operand(Operand::Integer(7)),
+        ];
+        scale(&mut tokens, 2.0);
+        let operands = tokens
+            .iter()
+            .filter_map(|token| match token {
+                Token::Operand(Operand::Integer(value)) => Some(*value),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(operands, [200, 3, 10, -107, -6, 20, 40, 65, 97, 14]);
+    }
+
+    #[test]
+    fn resolve_fractional_blends() {
+        let push = |tokens: &mut Vec<Token>, values: &[f64], operator| {
+            for value in values {
+                tokens.push(Token::Operand(Operand::Fixed(Fixed::from_f64(*value))));
+            }
+            tokens.push(Token::Operand(Operand::Integer(values.len() as i32 / 2)));
+            tokens.push(Token::Operator(operator));
+        };
+        // one region, with a default and a delta for each value
+        let mut tokens = Vec::new();
+        push(
+            &mut tokens,
+            &[10.0, 11.0, -10.0, 0.0, 1.0, 1.0, -1.0, 0.75],
+            Operator::Blend,
+        );
+        tokens.push(Token::Operator(Operator::RLineTo));
+        let scalars = [vec![0.5]];
+        let resolved = resolve_blends(&tokens, 0, &scalars).unwrap();
+        // halfway values are rounded to even, others to the nearest
+        assert_eq!(
+            resolved,
+            [
+                Token::Operand(Operand::Integer(10)),
+                Token::Operand(Operand::Integer(12)),
+                Token::Operand(Operand::Integer(-10)),
+                Token::Operand(Operand::Integer(0)),
+                Token::Operator(Operator::RLineTo),
+            ]
+        );
+        for _ in 0..10 {
+            assert_eq!(resolve_blends(&tokens, 0, &scalars).unwrap(), resolved);
+        }
+    }
+
+    #[test]
+    fn truncated_mask() {
+        let mut data = Vec::new();
+        for value in [10, 20, 30, 40] {
+            encode_operand(Operand::Integer(value), &mut data);
+        }
+        encode_operator(Operator::HStem, &mut data);
+        encode_operator(Operator::HintMask, &mut data);
+        assert!(parse(&data, &[]).is_err());
+    }
+
+    #[test]
+    fn vsindex_selects_regions() {
+        let mut data = Vec::new();
+        let mut push = |values: &[i32], operator| {
+            for value in values {
+                encode_operand(Operand::Integer(*value), &mut data);
+            }
+            encode_operator(operator, &mut data);
+        };
+        // vsindex 0 has two regions: one value with two deltas
+        push(&[100, 1, 2, 1], Operator::Blend);
+        push(&[0], Operator::RMoveTo);
+        // vsindex 1 has three regions: two values with three deltas each
+        push(&[1], Operator::VariationStoreIndex);
+        push(&[10, 20, 1, 2, 3, 4, 5, 6, 2], Operator::Blend);
+        push(&[], Operator::RLineTo);
+
+        let mut parser = Parser::new(&[2, 3]);
+        parser.parse(&data).unwrap();
+        assert_eq!(parser.vsindex(), 1);
+        assert_eq!(
+            parser.blends(),
+            [
+                BlendInfo {
+                    vsindex: 0,
+                    region_count: 2,
+                    value_count: 1
+                },
+                BlendInfo {
+                    vsindex: 1,
+                    region_count: 3,
+                    value_count: 2
+                },
+            ]
+        );
+        // without the second subtable the operands can't be consumed
+        assert!(parse(&data, &[2]).is_err());
+    }
+}
@@ -0,0 +1,803 @@
+//! Parsing and encoding of PostScript DICT data.
+//!
+//! Unlike the parser in read-fonts, which normalizes operands for use in
+//! rendering, this preserves operands exactly so that a DICT can be modified
+//! and written back without loss.
+
+use std::ops::Range;
+
+use super::CffError;
+
+/// The escape byte introducing a two byte operator.
+const ESCAPE: u8 = 12;
+
+/// A PostScript DICT operator.
+///
+/// See "Table 9 Top DICT Operator Entries" and "Table 23 Private DICT
+/// Operators" at <https://adobe-type-tools.github.io/font-tech-notes/pdfs/5176.CFF.pdf>
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DictKey {
+    Version,
+    Notice,
+    FullName,
+    FamilyName,
+    Weight,
+    FontBbox,
+    CharstringsOffset,
+    PrivateDictRange,
+    VariationStoreOffset,
+    MaxStack,
+    Copyright,
+    IsFixedPitch,
+    ItalicAngle,
+    UnderlinePosition,
+    UnderlineThickness,
+    PaintType,
+    CharstringType,
+    FontMatrix,
+    StrokeWidth,
+    FdArrayOffset,
+    FdSelectOffset,
+    BlueValues,
+    OtherBlues,
+    FamilyBlues,
+    FamilyOtherBlues,
+    SubrsOffset,
+    VariationStoreIndex,
+    BlueScale,
+    BlueShift,
+    BlueFuzz,
+    LanguageGroup,
+    ExpansionFactor,
+    Encoding,
+    Charset,
+    UniqueId,
+    Xuid,
+    SyntheticBase,
+    PostScript,
+    BaseFontName,
+    BaseFontBlend,
+    Ros,
+    CidFontVersion,
+    CidFontRevision,
+    CidFontType,
+    CidCount,
+    UidBase,
+    FontName,
+    StdHw,
+    StdVw,
+    DefaultWidthX,
+    NominalWidthX,
+    Blend,
+    StemSnapH,
+    StemSnapV,
+    ForceBold,
+    InitialRandomSeed,
+    /// An operator that is not defined by the specification.
+    ///
+    /// Two byte operators are represented as `0x0c00 | second_byte`.
+    Unknown(u16),
+}
+
+impl DictKey {
+    /// Returns the key for the given opcode.
+    ///
+    /// Two byte operators are represented as `0x0c00 | second_byte`.
+    pub fn from_opcode(opcode: u16) -> Self {
+        use DictKey::*;
+        match opcode {
+            0 => Version,
+            1 => Notice,
+            2 => FullName,
+            3 => FamilyName,
+            4 => Weight,
+            5 => FontBbox,
+            6 => BlueValues,
+            7 => OtherBlues,
+            8 => FamilyBlues,
+            9 => FamilyOtherBlues,
+            10 => StdHw,
+            11 => StdVw,
+            13 => UniqueId,
+            14 => Xuid,
+            15 => Charset,
+            16 => Encoding,
+            17 => CharstringsOffset,
+            18 => PrivateDictRange,
+            19 => SubrsOffset,
+            20 => DefaultWidthX,
+            21 => NominalWidthX,
+            22 => VariationStoreIndex,
+            23 => Blend,
+            24 => VariationStoreOffset,
+            25 => MaxStack,
+            0x0c00 => Copyright,
+            0x0c01 => IsFixedPitch,
+            0x0c02 => ItalicAngle,
+            0x0c03 => UnderlinePosition,
+            0x0c04 => UnderlineThickness,
+            0x0c05 => PaintType,
+            0x0c06 => CharstringType,
+            0x0c07 => FontMatrix,
+            0x0c08 => StrokeWidth,
+            0x0c09 => BlueScale,
+            0x0c0a => BlueShift,
+            0x0c0b => BlueFuzz,
+            0x0c0c => StemSnapH,
+            0x0c0d => StemSnapV,
+            0x0c0e => ForceBold,
+            0x0c11 => LanguageGroup,
+            0x0c12 => ExpansionFactor,
+            0x0c13 => InitialRandomSeed,
+            0x0c14 => SyntheticBase,
+            0x0c15 => PostScript,
+            0x0c16 => BaseFontName,
+            0x0c17 => BaseFontBlend,
+            0x0c1e => Ros,
+            0x0c1f => CidFontVersion,
+            0x0c20 => CidFontRevision,
+            0x0c21 => CidFontType,
+            0x0c22 => CidCount,
+            0x0c23 => UidBase,
+            0x0c24 => FdArrayOffset,
+            0x0c25 => FdSelectOffset,
+            0x0c26 => FontName,
+            other => Unknown(other),
+        }
+    }
+
+    /// Returns the opcode for this key.
+    ///
+    /// Two byte operators are represented as `0x0c00 | second_byte`.
+    pub fn opcode(self) -> u16 {
+        use DictKey::*;
+        match self {
+            Version => 0,
+            Notice => 1,
+            FullName => 2,
+            FamilyName => 3,
+            Weight => 4,
+            FontBbox => 5,
+            BlueValues => 6,
+            OtherBlues => 7,
+            FamilyBlues => 8,
+            FamilyOtherBlues => 9,
+            StdHw => 10,
+            StdVw => 11,
+            UniqueId => 13,
+            Xuid => 14,
+            Charset => 15,
+            Encoding => 16,
+            CharstringsOffset => 17,
+            PrivateDictRange => 18,
+            SubrsOffset => 19,
+            DefaultWidthX => 20,
+            NominalWidthX => 21,
+            VariationStoreIndex => 22,
+            Blend => 23,
+            VariationStoreOffset => 24,
+            MaxStack => 25,
+            Copyright => 0x0c00,
+            IsFixedPitch => 0x0c01,
+            ItalicAngle => 0x0c02,
+            UnderlinePosition => 0x0c03,
+            UnderlineThickness => 0x0c04,
+            PaintType => 0x0c05,
+            CharstringType => 0x0c06,
+            FontMatrix => 0x0c07,
+            StrokeWidth => 0x0c08,
+            BlueScale => 0x0c09,
+            BlueShift => 0x0c0a,
+            BlueFuzz => 0x0c0b,
+            StemSnapH => 0x0c0c,
+            StemSnapV => 0x0c0d,
+            ForceBold => 0x0c0e,
+            LanguageGroup => 0x0c11,
+            ExpansionFactor => 0x0c12,
+            InitialRandomSeed => 0x0c13,
+            SyntheticBase => 0x0c14,
+            PostScript => 0x0c15,
+            BaseFontName => 0x0c16,
+            BaseFontBlend => 0x0c17,
+            Ros => 0x0c1e,
+            CidFontVersion => 0x0c1f,
+            CidFontRevision => 0x0c20,
+            CidFontType => 0x0c21,
+            CidCount => 0x0c22,
+            UidBase => 0x0c23,
+            FdArrayOffset => 0x0c24,
+            FdSelectOffset => 0x0c25,
+            FontName => 0x0c26,
+            Unknown(opcode) => opcode,
+        }
+    }
+
+    /// Returns the operands implied by the specification when this operator
+    /// is absent from a Top DICT, or `None` if it has no default.
+    ///
+    /// See "Table 9 Top DICT Operator Entries" and "Table 10 CIDFont
+    /// Operator Extensions" at <https://adobe-type-tools.github.io/font-tech-notes/pdfs/5176.CFF.pdf>
+    pub fn top_dict_default(self) -> Option<&'static [f64]> {
+        use DictKey::*;
+        Some(match self {
+            IsFixedPitch | ItalicAngle | PaintType | StrokeWidth => &[0.0],
+            UnderlinePosition => &[-100.0],
+            UnderlineThickness => &[50.0],
+            CharstringType => &[2.0],
+            FontMatrix => &[0.001, 0.0, 0.0, 0.001, 0.0, 0.0],
+            FontBbox => &[0.0; 4],
+            CidFontVersion | CidFontRevision | CidFontType => &[0.0],
+            CidCount => &[8720.0],
+            _ => return None,
+        })
+    }
+}
+
+/// An operand in a DICT.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Operand {
+    /// An integer operand.
+    Integer(i32),
+    /// A real number operand.
+    Real(f64),
+}
+
+impl Operand {
+    /// Returns the value of the operand as a floating point number.
+    pub fn to_f64(self) -> f64 {
+        match self {
+            Operand::Integer(value) => value as f64,
+            Operand::Real(value) => value,
+        }
+    }
+
+    /// Returns the value of the operand if it is an integer.
+    pub fn to_i32(self) -> Option<i32> {
+        match self {
+            Operand::Integer(value) => Some(value),
+            Operand::Real(_) => None,
+        }
+    }
+}
+
+impl From<i32> for Operand {
+    fn from(value: i32) -> Self {
+        Operand::Integer(value)
+    }
+}
+
+impl From<f64> for Operand {
+    fn from(value: f64) -> Self {
+        Operand::Real(value)
+    }
+}
+
+/// An operator in a DICT along with its operands.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Entry {
+    /// The operator.
+    pub key: DictKey,
+    /// The operands, in the order they are encoded.
+    pub operands: Vec<Operand>,
+}
+
+impl Entry {
+    /// Create a new entry.
+    pub fn new(key: DictKey, operands: Vec<Operand>) -> Self {
+        Self { key, operands }
+    }
+
+    /// Returns `true` if this is a Top DICT entry with the operands implied
+    /// by the absence of the operator.
+    pub fn is_top_dict_default(&self) -> bool {
+        self.key.top_dict_default().is_some_and(|default| {
+            default.len() == self.operands.len()
+                && default
+                    .iter()
+                    .zip(&self.operands)
+                    .all(|(default, operand)| *default == operand.to_f64())
+        })
+    }
+
+    /// Appends the encoded entry to `out`.
+    pub fn encode(&self, out: &mut Vec<u8>) {
+        for operand in &self.operands {
+            encode_operand(*operand, out);
+        }
+        encode_operator(self.key, out);
+    }
+}
+
+/// Parses all entries in the given DICT data.
+pub fn parse(data: &[u8]) -> Result<Vec<Entry>, CffError> {
+    Ok(parse_with_ranges(data)?
+        .into_iter()
+        .map(|(entry, _)| entry)
+        .collect())
+}
+
+/// Parses all entries in the given DICT data, along with the range of bytes
+/// covering each entry, including its operands.
+pub(crate) fn parse_with_ranges(data: &[u8]) -> Result<Vec<(Entry, Range<usize>)>, CffError> {
+    let mut entries = Vec::new();
+    let mut operands = Vec::new();
+    let mut entry_start = 0;
+    let mut pos = 0;
+    while pos < data.len() {
+        let b0 = data[pos];
+        let byte = |ix: usize| data.get(pos + ix).copied().ok_or(CffError::InvalidDict);
+        match b0 {
+            0..=25 => {
+                let opcode = if b0 == ESCAPE {
+                    let b1 = byte(1)?;
+                    pos += 2;
+                    0x0c00 | b1 as u16
+                } else {
+                    pos += 1;
+                    b0 as u16
+                };
+                let entry = Entry::new(DictKey::from_opcode(opcode), std::mem::take(&mut operands));
+                entries.push((entry, entry_start..pos));
+                entry_start = pos;
+            }
+            28 => {
+                let value = i16::from_be_bytes([byte(1)?, byte(2)?]);
+                operands.push(Operand::Integer(value as i32));
+                pos += 3;
+            }
+            29 => {
+                let value = i32::from_be_bytes([byte(1)?, byte(2)?, byte(3)?, byte(4)?]);
+                operands.push(Operand::Integer(value));
+                pos += 5;
+            }
+            30 => {
+                let (value, len) = parse_real(&data[pos + 1..])?;
+                operands.push(Operand::Real(value));
+                pos += 1 + len;
+            }
+            32..=246 => {
+                operands.push(Operand::Integer(b0 as i32 - 139));
+                pos += 1;
+            }
+            247..=250 => {
+                let value = (b0 as i32 - 247) * 256 + byte(1)? as i32 + 108;
+                operands.push(Operand::Integer(value));
+                pos += 2;
+            }
+            251..=254 => {
+                let value = -(b0 as i32 - 251) * 256 - byte(1)? as i32 - 108;
+                operands.push(Operand::Integer(value));
+                pos += 2;
+            }
+            _ => return Err(CffError::InvalidDict),
+        }
+    }
+    // operands must be followed by an operator
+    if !operands.is_empty() {
+        return Err(CffError::InvalidDict);
+    }
+    Ok(entries)
+}
+
+/// Parses a BCD encoded real number, returning the value and the number of
+/// bytes consumed.
+fn parse_real(data: &[u8]) -> Result<(f64, usize), CffError> {
+    let mut text = String::new();
+    for (i, byte) in data.iter().enumerate() {
+        for nibble in [byte >> 4, byte & 0xF] {
+            match nibble {
+                0..=9 => text.push((b'0' + nibble) as char),
+                0xA => text.push('.'),
+                0xB => text.push('E'),
+                0xC => text.push_str("E-"),
+                0xE => text.push('-'),
+                0xF => {
+                    let value = text.parse().map_err(|_| CffError::InvalidDict)?;
+                    return Ok((value, i + 1));
+                }
+                _ => return Err(CffError::InvalidDict),
+            }
+        }
+    }
+    Err(CffError::InvalidDict)
+}
+
+/// Encodes the given entries as DICT data.
+pub fn encode(entries: &[Entry]) -> Vec<u8> {
+    let mut out = Vec::new();
+    for entry in entries {
+        entry.encode(&mut out);
+    }
+    out
+}
+
+/// Appends the encoded operator to `out`.
+pub fn encode_operator(key: DictKey, out: &mut Vec<u8>) {
+    let opcode = key.opcode();
+    if opcode >> 8 == ESCAPE as u16 {
+        out.extend_from_slice(&[ESCAPE, opcode as u8]);
+    } else {
+        out.push(opcode as u8);
+    }
+}
+
+/// Appends the encoded operand to `out`.
+pub fn encode_operand(operand: Operand, out: &mut Vec<u8>) {
+    match operand {
+        Operand::Integer(value) => encode_int(value, out),
+        Operand::Real(value) => encode_real(value, out),
+    }
+}
+
+/// Appends the most compact encoding of the given integer to `out`.
+///
+/// Values in the range -1131..=1131 use the one and two byte forms, those
+/// that fit in 16 bits use the three byte form (prefix 28) and all others use
+/// the five byte form (prefix 29).
+pub fn encode_int(value: i32, out: &mut Vec<u8>) {
+    match value {
+        -107..=107 => out.push((value + 139) as u8),
+        108..=1131 => {
+            let value = value - 108;
+            out.extend_from_slice(&[(value >> 8) as u8 + 247, value as u8]);
+        }
+        -1131..=-108 => {
+            let value = -value - 108;
+            out.extend_from_slice(&[(value >> 8) as u8 + 251, value as u8]);
+        }
+        -32768..=32767 => {
+            out.push(28);
+            out.extend_from_slice(&(value as i16).to_be_bytes());
+        }
+        _ => encode_offset(value, out),
+    }
+}
+
+/// Appends the five byte encoding of the given integer to `out`.
+///
+/// This is used for offsets, so that the size of a DICT does not depend on
+/// the values of the offsets it contains.
+pub(crate) fn encode_offset(value: i32, out: &mut Vec<u8>) {
+    out.push(29);
+    out.extend_from_slice(&value.to_be_bytes());
+}
+
+/// Appends the shortest encoding of the given real number to `out`.
+///
+/// Non-finite values are encoded as zero.
+pub fn encode_real(value: f64, out: &mut Vec<u8>) {
+    let value = if value.is_finite() { value } else { 0.0 };
+    let plain = real_nibbles(&trim_leading_zero(format!("{value}")));
+    let scientific = real_nibbles(&format!("{value:e}"));
+    let mut nibbles = if scientific.len() < plain.len() {
+        scientific
+    } else {
+        plain
+    };
+    nibbles.push(0xF);
+    if nibbles.len() % 2 == 1 {
+        nibbles.push(0xF);
+    }
+    out.push(30);
+    out.extend(nibbles.chunks(2).map(|pair| (pair[0] << 4) | pair[1]));
+}
+
+/// Converts a formatted number to the nibbles of the real number encoding,
+/// excluding the terminator.
+fn real_nibbles(text: &str) -> Vec<u8> {
+    let mut nibbles = Vec::with_capacity(text.len() + 2);
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        nibbles.push(match c {
+            '0'..='9' => c as u8 - b'0',
+            '.' => 0xA,
+            '-' => 0xE,
+            'e' if chars.peek() == Some(&'-') => {
+                chars.next();
+                0xC
+            }
+            'e' => 0xB,
+            _ => unreachable!("unexpected character in formatted float"),
+        });
+    }
+    nibbles
+}
+
+/// Removes a redundant zero before the decimal point, e.g. "0.5" -> ".5"
+fn trim_leading_zero(text: String) -> String {
+    if let Some(rest) = text.strip_prefix("0.") {
+        format!(".{rest}")
+    } else if let Some(rest) = text.strip_prefix("-0.") {
+        format!("-.{rest}")
+    } else {
+        text
+    }
+}
+
+/// Appends the encoding of a number, using the integer form when the value
+/// is integral.
+pub(crate) fn encode_number(value: f64, out: &mut Vec<u8>) {
+    if value.fract() == 0.0 && value.abs() <= i32::MAX as f64 {
+        encode_int(value as i32, out);
+    } else {
+        encode_real(value, out);
+    }
+}
+
+/// Returns the value of an operator with a single non-negative integer
+/// operand, such as an offset.
+pub(crate) fn offset_operand(entries: &[Entry], key: DictKey) -> Option<usize> {
+    let entry = entries.iter().find(|entry| entry.key == key)?;
+    match entry.operands.as_slice() {
+        [Operand::Integer(value)] => usize::try_from(*value).ok(),
+        _ => None,
+    }
+}
+
+/// Returns the `(size, offset)` operands of the Private operator.
+pub(crate) fn private_dict_range(entries: &[Entry]) -> Option<(usize, usize)> {
+    let entry = entries
+        .iter()
+        .find(|entry| entry.key == DictKey::PrivateDictRange)?;
+    match entry.operands.as_slice() {
+        [Operand::Integer(size), Operand::Integer(offset)] => {
+            Some((usize::try_from(*size).ok()?, usize::try_from(*offset).ok()?))
+        }
+        _ => None,
+    }
+}
+
+/// Returns the fixed width encoding of an offset operand.
+pub(crate) fn encoded_offset(offset: usize) -> Vec<u8> {
+    let mut data = Vec::with_capacity(5);
+    encode_offset(offset as i32, &mut data);
+    data
+}
+
+/// Returns the encoded `(size, offset)` operands of the Private operator.
+pub(crate) fn encoded_private_range(size: usize, offset: usize) -> Vec<u8> {
+    let mut data = Vec::with_capacity(10);
+    encode_int(size as i32, &mut data);
+    encode_offset(offset as i32, &mut data);
+    data
+}
+
+/// New encoded operands for an operator, or `None` to remove it.
+pub(crate) type Update = (DictKey, Option<Vec<u8>>);
+
+/// Rewrites DICT data, replacing the operands of the given operators.
+///
+/// For each `(key, operands)` pair, an existing entry with the given key is
+/// replaced with the encoded operands, or removed if `operands` is `None`.
+/// Keys with operands that are not present in the source data are appended.
+/// All other entries are copied unchanged.
+///
+/// If the source data is malformed, it is returned unchanged.
+pub(crate) fn rewrite(data: &[u8], updates: &[Update]) -> Vec<u8> {
+    let Ok(entries) = parse_with_ranges(data) else {
+        return data.to_vec();
+    };
+    let mut out = Vec::with_capacity(data.len());
+    let mut written = vec![false; updates.len()];
+    for (entry, range) in entries {
+        match updates.iter().position(|(key, _)| *key == entry.key) {
+            Some(ix) => {
+                if let (Some(operands), false) = (&updates[ix].1, written[ix]) {
+                    out.extend_from_slice(operands);
+                    encode_operator(entry.key, &mut out);
+                    written[ix] = true;
+                }
+            }
+            None => out.extend_from_slice(&data[range]),
+        }
+    }
+    for ((key, operands), written) in updates.iter().zip(written) {
+        if let (Some(operands), false) = (operands, written) {
+            out.extend_from_slice(operands);
+            encode_operator(*key, &mut out);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn round_trip(operand: Operand) -> (Operand, usize) {
+        let mut data = Vec::new();
+        encode_operand(operand, &mut data);
+        let len = data.len();
+        encode_operator(DictKey::Version, &mut data);
+        let entries = parse(&data).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].key, DictKey::Version);
+        (entries[0].operands[0], len)
+    }
+
+    #[test]
+    fn int_encoding() {
+        for (value, len) in [
+            (0, 1),
+            (107, 1),
+            (-107, 1),
+            (108, 2),
+            (1131, 2),
+            (-1131, 2),
+            (1132, 3),
+            (-32768, 3),
+            (32768, 5),
+            (i32::MIN, 5),
+            (i32::MAX, 5),
+        ] {
+            assert_eq!(
+                round_trip(Operand::Integer(value)),
+                (Operand::Integer(value), len),
+                "{value}"
+            );
+        }
+    }
+
+    #[test]
+    fn large_offset() {
+        let mut data = Vec::new();
+        encode_int(0x01FF_FFFF, &mut data);
+        assert_eq!(data, [29, 0x01, 0xFF, 0xFF, 0xFF]);
+        encode_operator(DictKey::CharstringsOffset, &mut data);
+        let entries = parse(&data).unwrap();
+        assert_eq!(
+            entries,
+            [Entry::new(
+                DictKey::CharstringsOffset,
+                vec![Operand::Integer(0x01FF_FFFF)]
+            )]
+        );
+        // matches the read-fonts interpretation
+        let read = read_fonts::tables::postscript::dict::entries(&data, None)
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            read,
+            read_fonts::tables::postscript::dict::Entry::CharstringsOffset(0x01FF_FFFF)
+        );
+    }
+
+    #[test]
+    fn real_encoding() {
+        // examples from the spec
+        let mut data = Vec::new();
+        encode_real(-2.25, &mut data);
+        assert_eq!(data, [0x1e, 0xe2, 0xa2, 0x5f]);
+        data.clear();
+        encode_real(0.140541e-3, &mut data);
+        assert_eq!(data, [0x1e, 0x1a, 0x40, 0x54, 0x1c, 0x4f]);
+        for value in [0.0, -0.5, 0.001, 1e-20, 123456.75, -3.5e12, 0.039625] {
+            assert_eq!(round_trip(Operand::Real(value)).0, Operand::Real(value));
+        }
+    }
+
+    #[test]
+    fn escaped_and_unknown_operators() {
+        let data = [139, 12, 30, 139, 12, 99, 139, 24];
+        let entries = parse(&data).unwrap();
+        assert_eq!(entries[0].key, DictKey::Ros);
+        assert_eq!(entries[1].key, DictKey::Unknown(0x0c63));
+        assert_eq!(entries[2].key, DictKey::VariationStoreOffset);
+        assert_eq!(encode(&entries), data);
+    }
+
+    #[test]
+    fn malformed() {
+        // trailing operand
+        assert!(parse(&[139]).is_err());
+        // truncated operand
+        assert!(parse(&[28, 0]).is_err());
+        // reserved byte
+        assert!(parse(&[255, 0]).is_err());
+        // unterminated real
+        assert!(parse(&[30, 0x12]).is_err());
+    }
+
+    #[test]
+    fn rewrite_entries() {
+        let entries = vec![
+            Entry::new(DictKey::Version, vec![Operand::Integer(391)]),
+            Entry::new(DictKey::Charset, vec![Operand::Integer(100)]),
+            Entry::new(DictKey::Encoding, vec![Operand::Integer(120)]),
+        ];
+        let data = encode(&entries);
+        let mut offset = Vec::new();
+        encode_offset(5000, &mut offset);
+        let rewritten = rewrite(
+            &data,
+            &[
+                (DictKey::Charset, Some(offset.clone())),
+                (DictKey::Encoding, None),
+                (DictKey::CharstringsOffset, Some(offset)),
+            ],
+        );
+        assert_eq!(
+            parse(&rewritten).unwrap(),
+            [
+                Entry::new(DictKey::Version, vec![Operand::Integer(391)]),
+                Entry::new(DictKey::Charset, vec![Operand::Integer(5000)]),
+                Entry::new(DictKey::CharstringsOffset, vec![Operand::Integer(5000)]),
+            ]
+        );
+    }
+
+    /// Encodes a real number and parses it back with read-fonts, optionally
+    /// with the extra precision used for the FontMatrix.
+    fn read_fonts_real(value: f64, scale_by_1000: bool) -> f64 {
+        use read_fonts::tables::postscript::dict::{tokens, Token};
+        let mut data = Vec::new();
+        encode_real(value, &mut data);
+        let parsed = tokens(&data).collect::<Vec<_>>();
+        let [Ok(Token::Operand(_, Some(components)))] = parsed.as_slice() else {
+            panic!("expected a single real operand, got {parsed:?}");
+        };
+        components.value(scale_by_1000).to_f64()
+    }
+
+    /// Checks that `value` is read back by read-fonts within the precision
+    /// of a 16.16 fixed point value, allowing for the nine significant
+    /// digits kept by the parser.
+    ///
+    /// Like FreeType, read-fonts drops decimal places beyond the number of
+    /// significant digits for values below 0.1, so smaller values are
+    /// checked with the extra precision used for the FontMatrix.
+    fn check_read_fonts_real(value: f64) -> Result<(), String> {
+        let (actual, expected) = match value.abs() {
+            magnitude if magnitude == 0.0 || magnitude >= 0.1 => {
+                (read_fonts_real(value, false), value)
+            }
+            magnitude if magnitude >= 1e-4 => (read_fonts_real(value, true), value),
+            _ => return Ok(()),
+        };
+        let tolerance = 2.0 / 65536.0 + expected.abs() * 1e-8;
+        if (actual - expected).abs() <= tolerance {
+            Ok(())
+        } else {
+            Err(format!("{actual} is not within {tolerance} of {expected}"))
+        }
+    }
+
+    #[test]
+    fn font_matrix_reals() {
+        for value in [
+            0.001,
+            -0.001,
+            0.000488281,
+            0.00048828125,
+            0.000244140625,
+            0.0002,
+            0.000212577,
+            -0.000176327,
+            0.0,
+            1.0,
+        ] {
+            check_read_fonts_real(value).unwrap();
+            assert_eq!(round_trip(Operand::Real(value)).0, Operand::Real(value));
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn encode_real_round_trips(
+            value in prop_oneof![
+                -32000.0..32000.0f64,
+                -1.0..1.0f64,
+                (-100_000..100_000i32, -20..=-1i32)
+                    .prop_map(|(mantissa, exponent)| mantissa as f64 * 10f64.powi(exponent)),
+            ]
+        ) {
+            prop_assert_eq!(round_trip(Operand::Real(value)).0, Operand::Real(value));
+            check_read_fonts_real(value).map_err(TestCaseError::fail)?;
+        }
+    }
+}
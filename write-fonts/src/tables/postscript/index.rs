@@ -0,0 +1,643 @@
+//! Construction and serialization of INDEX structures.
+
+use super::{CffError, Index1, Index2};
+use crate::{
+    from_obj::{FromObjRef, FromTableRef, ToOwnedTable},
+    table_type::TableType,
+    validate::ValidationCtx,
+    FontWrite, TableWriter,
+};
+use read_fonts::{FontData, FontRead, ReadError};
+
+// Implements the methods shared by the CFF and CFF2 INDEX types, which
+// differ only in the width of the count field.
+macro_rules! index_impl {
+    ($name:ident, $count:ty) => {
+        impl $name {
+            /// Create a new INDEX containing the given objects.
+            ///
+            /// The smallest offset size capable of addressing all of the
+            /// object data is selected.
+            ///
+            /// # Panics
+            ///
+            /// Panics if the number of objects exceeds the capacity of the
+            /// count field.
+            pub fn from_bytes<T: AsRef<[u8]>>(objects: impl IntoIterator<Item = T>) -> Self {
+                let mut data = Vec::new();
+                let mut ends = Vec::new();
+                for object in objects {
+                    data.extend_from_slice(object.as_ref());
+                    ends.push(data.len());
+                }
+                if ends.is_empty() {
+                    return Self::default();
+                }
+                let off_size = min_offset_size(data.len() + 1);
+                let mut offsets = Vec::with_capacity((ends.len() + 1) * off_size as usize);
+                write_offset(1, off_size, &mut offsets);
+                for end in &ends {
+                    write_offset((end + 1) as u32, off_size, &mut offsets);
+                }
+                Self {
+                    count: <$count>::try_from(ends.len()).unwrap(),
+                    off_size,
+                    offsets,
+                    data,
+                }
+            }
+
+            /// Create a new INDEX containing the given objects, allocating the
+            /// object data and offsets up front.
+            ///
+            /// This produces the same INDEX as [`from_bytes`](Self::from_bytes)
+            /// but avoids repeated reallocation when building large INDEXes,
+            /// such as the string INDEX of a font with many glyph names.
+            ///
+            /// # Panics
+            ///
+            /// Panics if the number of objects exceeds the capacity of the
+            /// count field.
+            pub fn with_capacity_from(objects: &[&[u8]]) -> Self {
+                if objects.is_empty() {
+                    return Self::default();
+                }
+                let data_len = objects.iter().map(|object| object.len()).sum::<usize>();
+                let off_size = min_offset_size(data_len + 1);
+                let mut data = Vec::with_capacity(data_len);
+                let mut offsets = Vec::with_capacity((objects.len() + 1) * off_size as usize);
+                write_offset(1, off_size, &mut offsets);
+                for object in objects {
+                    data.extend_from_slice(object);
+                    write_offset((data.len() + 1) as u32, off_size, &mut offsets);
+                }
+                Self {
+                    count: <$count>::try_from(objects.len()).unwrap(),
+                    off_size,
+                    offsets,
+                    data,
+                }
+            }
+
+            /// Returns the number of objects in the INDEX.
+            pub fn len(&self) -> usize {
+                self.count as usize
+            }
+
+            /// Returns `true` if the INDEX contains no objects.
+            pub fn is_empty(&self) -> bool {
+                self.count == 0
+            }
+
+            /// Returns the object at the given index.
+            ///
+            /// Returns `None` if the index is out of bounds or if the offsets
+            /// of the object are malformed.
+            pub fn get(&self, index: usize) -> Option<&[u8]> {
+                if index >= self.len() {
+                    return None;
+                }
+                let start = self.object_offset(index)?;
+                let end = self.object_offset(index + 1)?;
+                self.data.get(start..end)
+            }
+
+            /// Returns an iterator over all objects in the INDEX.
+            ///
+            /// Objects with malformed offsets are yielded as empty slices.
+            pub fn iter(&self) -> impl Iterator<Item = &[u8]> + '_ {
+                (0..self.len()).map(|i| self.get(i).unwrap_or_default())
+            }
+
+            /// Returns the number of bytes the INDEX occupies when written.
+            pub fn byte_len(&self) -> usize {
+                // an empty INDEX consists of only the count field
+                if self.is_empty() {
+                    return std::mem::size_of::<$count>();
+                }
+                std::mem::size_of::<$count>() + 1 + self.offsets.len() + self.data.len()
+            }
+
+            /// Appends an object to the end of the INDEX.
+            ///
+            /// The offset size is increased if necessary.
+            ///
+            /// # Panics
+            ///
+            /// Panics if the INDEX is already full, as the number of objects
+            /// would exceed the capacity of the count field.
+            pub fn push(&mut self, object: &[u8]) {
+                let count = self
+                    .count
+                    .checked_add(1)
+                    .expect("number of objects exceeds the INDEX count");
+                let end = self.data.len() + object.len() + 1;
+                if self.is_empty() || min_offset_size(end) > self.off_size {
+                    *self = Self::from_bytes(self.iter().chain(std::iter::once(object)));
+                    return;
+                }
+                self.data.extend_from_slice(object);
+                write_offset(end as u32, self.off_size, &mut self.offsets);
+                self.count = count;
+            }
+
+            /// Checks that the offsets describe a sequence of objects that
+            /// don't overlap.
+            ///
+            /// Returns [`CffError::InvalidOffsetRange`] unless the offset size
+            /// is valid, the first offset is 1, each offset is at least the
+            /// previous one and the last offset is within the object data.
+            pub fn check_wellformed(&self) -> Result<(), CffError> {
+                check_wellformed_offsets(self.len(), self.off_size, &self.offsets, self.data.len())
+            }
+
+            /// Reports a non-empty INDEX with an invalid offset size or with
+            /// the wrong number of offsets.
+            ///
+            /// An empty INDEX is written as only the count field, so its
+            /// other fields are ignored.
+            pub(super) fn check_offsets(&self, ctx: &mut ValidationCtx) {
+                if self.is_empty() {
+                    return;
+                }
+                if !(1..=4).contains(&self.off_size) {
+                    ctx.report(format!(
+                        "off_size must be between 1 and 4, not {}",
+                        self.off_size
+                    ));
+                } else if self.offsets.len() != (self.len() + 1) * self.off_size as usize {
+                    ctx.report("offsets must contain count + 1 entries");
+                }
+            }
+
+            /// Returns the offset of the object at the given index, relative
+            /// to the start of the object data.
+            fn object_offset(&self, index: usize) -> Option<usize> {
+                read_offset(&self.offsets, self.off_size, index)?.checked_sub(1)
+            }
+
+            /// The number of bytes of object data referenced by the offsets.
+            fn data_len(&self) -> usize {
+                if self.is_empty() {
+                    return 0;
+                }
+                self.object_offset(self.len()).unwrap_or(self.data.len())
+            }
+        }
+
+        impl FontWrite for $name {
+            fn write_into(&self, writer: &mut TableWriter) {
+                self.count.write_into(writer);
+                // an empty INDEX consists of only the count field
+                if self.is_empty() {
+                    return;
+                }
+                self.off_size.write_into(writer);
+                self.offsets.write_into(writer);
+                self.data.write_into(writer);
+            }
+
+            fn table_type(&self) -> TableType {
+                TableType::Named(stringify!($name))
+            }
+        }
+
+        impl<'a> FromObjRef<read_fonts::tables::postscript::$name<'a>> for $name {
+            fn from_obj_ref(obj: &read_fonts::tables::postscript::$name<'a>, _: FontData) -> Self {
+                if obj.count() == 0 {
+                    return Self::default();
+                }
+                let mut index = $name {
+                    count: obj.count(),
+                    off_size: obj.off_size(),
+                    offsets: obj.offsets().to_vec(),
+                    data: obj.data().to_vec(),
+                };
+                // the source data extends to the end of the parent table, so
+                // trim it to the range actually covered by the offsets
+                let data_len = index.data_len().min(index.data.len());
+                index.data.truncate(data_len);
+                index
+            }
+        }
+
+        impl<'a> FromTableRef<read_fonts::tables::postscript::$name<'a>> for $name {}
+
+        impl<'a> FontRead<'a> for $name {
+            fn read(data: FontData<'a>) -> Result<Self, ReadError> {
+                <read_fonts::tables::postscript::$name as FontRead>::read(data)
+                    .map(|x| x.to_owned_table())
+            }
+        }
+    };
+}
+
+index_impl!(Index1, u16);
+index_impl!(Index2, u32);
+
+impl Index1 {
+    /// Re-encodes the offsets with the given size, if it is larger
+    /// than the current offset size.
+    pub(crate) fn widen_offsets(&mut self, off_size: u8) {
+        if self.is_empty() || off_size <= self.off_size || off_size > 4 {
+            return;
+        }
+        let mut offsets = Vec::with_capacity((self.len() + 1) * off_size as usize);
+        for i in 0..=self.len() {
+            let offset = read_offset(&self.offsets, self.off_size, i).unwrap_or_default();
+            write_offset(offset as u32, off_size, &mut offsets);
+        }
+        self.off_size = off_size;
+        self.offsets = offsets;
+    }
+}
+
+/// Read access to the objects of a CFF INDEX, whether owned or borrowed.
+///
+/// This allows a [`Cff`](crate::tables::cff::Cff) table to be written from
+/// either [`Index1`] or [`CowIndex1`].
+pub trait CffIndex: FontWrite {
+    /// Returns the number of objects in the INDEX.
+    fn len(&self) -> usize;
+
+    /// Returns the object at the given index.
+    fn get(&self, index: usize) -> Option<&[u8]>;
+
+    /// Returns the size of the offsets, which is 0 for an empty INDEX.
+    fn off_size(&self) -> u8;
+
+    /// Returns the number of bytes the INDEX occupies when written.
+    fn byte_len(&self) -> usize;
+
+    /// Returns `true` if the INDEX contains no objects.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns an iterator over all objects in the INDEX.
+    fn iter(&self) -> impl Iterator<Item = &[u8]> + '_ {
+        (0..self.len()).map(|i| self.get(i).unwrap_or_default())
+    }
+}
+
+impl CffIndex for Index1 {
+    fn len(&self) -> usize {
+        Index1::len(self)
+    }
+
+    fn get(&self, index: usize) -> Option<&[u8]> {
+        Index1::get(self, index)
+    }
+
+    fn byte_len(&self) -> usize {
+        Index1::byte_len(self)
+    }
+
+    fn off_size(&self) -> u8 {
+        if self.is_empty() {
+            0
+        } else {
+            self.off_size
+        }
+    }
+}
+
+/// A CFF INDEX that either borrows the data of a source font or owns its
+/// data.
+///
+/// A borrowed INDEX is written by copying the source bytes directly, so an
+/// INDEX that isn't modified is never copied into an owned [`Index1`]. The
+/// data is copied on the first call to [`CowIndex1::to_mut`].
+#[derive(Clone)]
+pub enum CowIndex1<'a> {
+    /// An INDEX borrowed from the source data.
+    Borrowed {
+        /// The complete encoded INDEX, including the count and offsets.
+        data: &'a [u8],
+        /// The INDEX parsed from `data`, which is only parsed once.
+        index: read_fonts::tables::postscript::Index1<'a>,
+    },
+    /// An INDEX that owns its data, because it was modified or created
+    /// rather than read.
+    Owned(Index1),
+}
+
+impl<'a> CowIndex1<'a> {
+    /// Returns `true` if the INDEX still borrows the source data.
+    pub fn is_borrowed(&self) -> bool {
+        matches!(self, CowIndex1::Borrowed { .. })
+    }
+
+    /// Returns a mutable reference to the owned INDEX, copying the source
+    /// data if it is borrowed.
+    pub fn to_mut(&mut self) -> &mut Index1 {
+        if let CowIndex1::Borrowed { index, .. } = &*self {
+            *self = CowIndex1::Owned(index.to_owned_table());
+        }
+        match self {
+            CowIndex1::Owned(index) => index,
+            CowIndex1::Borrowed { .. } => unreachable!(),
+        }
+    }
+
+    /// Returns the owned INDEX, copying the source data if it is borrowed.
+    pub fn into_owned(mut self) -> Index1 {
+        std::mem::take(self.to_mut())
+    }
+}
+
+impl CffIndex for CowIndex1<'_> {
+    fn len(&self) -> usize {
+        match self {
+            CowIndex1::Borrowed { index, .. } => index.count() as usize,
+            CowIndex1::Owned(index) => index.len(),
+        }
+    }
+
+    fn get(&self, index: usize) -> Option<&[u8]> {
+        match self {
+            CowIndex1::Borrowed {
+                index: borrowed, ..
+            } => borrowed.get(index).ok(),
+            CowIndex1::Owned(owned) => owned.get(index),
+        }
+    }
+
+    fn byte_len(&self) -> usize {
+        match self {
+            CowIndex1::Borrowed { data, .. } => data.len(),
+            CowIndex1::Owned(index) => index.byte_len(),
+        }
+    }
+
+    fn off_size(&self) -> u8 {
+        match self {
+            CowIndex1::Borrowed { index, .. } if index.count() > 0 => index.off_size(),
+            CowIndex1::Borrowed { .. } => 0,
+            CowIndex1::Owned(index) => CffIndex::off_size(index),
+        }
+    }
+}
+
+impl std::fmt::Debug for CowIndex1<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CowIndex1::Borrowed { data, .. } => f
+                .debug_struct("Borrowed")
+                .field("data", data)
+                .finish_non_exhaustive(),
+            CowIndex1::Owned(index) => f.debug_tuple("Owned").field(index).finish(),
+        }
+    }
+}
+
+impl Default for CowIndex1<'_> {
+    fn default() -> Self {
+        CowIndex1::Owned(Index1::default())
+    }
+}
+
+impl From<Index1> for CowIndex1<'_> {
+    fn from(index: Index1) -> Self {
+        CowIndex1::Owned(index)
+    }
+}
+
+/// INDEXes are equal if they contain the same objects, regardless of
+/// whether they are borrowed.
+impl PartialEq for CowIndex1<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().eq(other.iter())
+    }
+}
+
+impl Eq for CowIndex1<'_> {}
+
+impl FontWrite for CowIndex1<'_> {
+    fn write_into(&self, writer: &mut TableWriter) {
+        match self {
+            CowIndex1::Borrowed { data, .. } => writer.write_slice(data),
+            CowIndex1::Owned(index) => index.write_into(writer),
+        }
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::Named("Index1")
+    }
+}
+
+impl<'a> FontRead<'a> for CowIndex1<'a> {
+    /// Borrows the INDEX at the start of the given data.
+    fn read(data: FontData<'a>) -> Result<Self, ReadError> {
+        let index = read_fonts::tables::postscript::Index1::read(data)?;
+        let len = index.size_in_bytes()?;
+        let data = data.as_bytes().get(..len).ok_or(ReadError::OutOfBounds)?;
+        Ok(CowIndex1::Borrowed { data, index })
+    }
+}
+
+/// Returns the smallest offset size capable of representing `max_offset`.
+pub(crate) fn min_offset_size(max_offset: usize) -> u8 {
+    match max_offset {
+        0..=0xFF => 1,
+        0x100..=0xFFFF => 2,
+        0x10000..=0xFF_FFFF => 3,
+        _ => 4,
+    }
+}
+
+/// Appends `value` to `out` as a big-endian integer of `off_size` bytes.
+///
+/// This is the inverse of [`read_offset`].
+///
+/// # Panics
+///
+/// Panics if the offset size isn't between 1 and 4 or is too small to
+/// represent `value`, which would silently truncate the offset.
+fn write_offset(value: u32, off_size: u8, out: &mut Vec<u8>) {
+    assert!(
+        (1..=4).contains(&off_size),
+        "invalid offset size {off_size}"
+    );
+    assert!(
+        min_offset_size(value as usize) <= off_size,
+        "offset {value} doesn't fit in {off_size} bytes"
+    );
+    out.extend_from_slice(&value.to_be_bytes()[4 - off_size as usize..]);
+}
+
+/// Checks the offsets of an INDEX with `count` objects and `data_len` bytes
+/// of object data, as for [`Index1::check_wellformed`].
+///
+/// This also checks INDEXes borrowed from a source table in place.
+pub(crate) fn check_wellformed_offsets(
+    count: usize,
+    off_size: u8,
+    offsets: &[u8],
+    data_len: usize,
+) -> Result<(), CffError> {
+    if count == 0 {
+        return Ok(());
+    }
+    if !(1..=4).contains(&off_size) || offsets.len() != (count + 1) * off_size as usize {
+        return Err(CffError::InvalidOffsetRange);
+    }
+    let mut previous = 1;
+    for i in 0..=count {
+        let offset = read_offset(offsets, off_size, i).ok_or(CffError::InvalidOffsetRange)?;
+        if (i == 0 && offset != 1) || offset < previous {
+            return Err(CffError::InvalidOffsetRange);
+        }
+        previous = offset;
+    }
+    if previous - 1 > data_len {
+        return Err(CffError::InvalidOffsetRange);
+    }
+    Ok(())
+}
+
+/// Reads the offset at `index` from an array of `off_size` byte offsets.
+fn read_offset(offsets: &[u8], off_size: u8, index: usize) -> Option<usize> {
+    if !(1..=4).contains(&off_size) {
+        return None;
+    }
+    let size = off_size as usize;
+    let bytes = offsets.get(index * size..(index + 1) * size)?;
+    Some(bytes.iter().fold(0, |acc, b| (acc << 8) | *b as usize))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn from_bytes_picks_offset_size() {
+        let small = Index1::from_bytes([b"abc".as_slice(), b"de"]);
+        assert_eq!(small.off_size, 1);
+        assert_eq!(small.offsets, [1, 4, 6]);
+        assert_eq!(small.get(1), Some(b"de".as_slice()));
+
+        let big = vec![0u8; 300];
+        let large = Index1::from_bytes([big.as_slice()]);
+        assert_eq!(large.off_size, 2);
+        assert_eq!(large.offsets, [0, 1, 0x01, 0x2D]);
+    }
+
+    #[test]
+    fn with_capacity_from_matches_from_bytes() {
+        let big = vec![1u8; 70000];
+        let objects = [b"abc".as_slice(), b"", b"de", big.as_slice()];
+        for count in 0..=objects.len() {
+            let objects = &objects[..count];
+            let index = Index1::with_capacity_from(objects);
+            assert_eq!(index, Index1::from_bytes(objects));
+            assert_eq!(index.data.capacity(), index.data.len());
+            assert_eq!(index.offsets.capacity(), index.offsets.len());
+            assert_eq!(
+                Index2::with_capacity_from(objects),
+                Index2::from_bytes(objects)
+            );
+        }
+        assert_eq!(Index1::with_capacity_from(&objects).off_size, 3);
+    }
+
+    #[test]
+    fn push_grows_offset_size() {
+        let mut index = Index1::default();
+        index.push(b"hi");
+        assert_eq!(index.off_size, 1);
+        index.push(&[7u8; 260]);
+        assert_eq!(index.off_size, 2);
+        assert_eq!(index.len(), 2);
+        assert_eq!(index.get(0), Some(b"hi".as_slice()));
+        assert_eq!(index.get(1).map(<[u8]>::len), Some(260));
+        assert_eq!(index, Index1::from_bytes([&b"hi"[..], &[7u8; 260]]));
+    }
+
+    #[test]
+    fn empty_index_is_count_only() {
+        assert_eq!(crate::dump_table(&Index1::default()).unwrap(), [0, 0]);
+        assert_eq!(crate::dump_table(&Index2::default()).unwrap(), [0, 0, 0, 0]);
+        // the offset size of an empty INDEX is ignored
+        let empty = Index1::new(0, 0, Vec::new(), Vec::new());
+        assert_eq!(crate::dump_table(&empty).unwrap(), [0, 0]);
+    }
+
+    #[test]
+    fn invalid_offset_size() {
+        let index = Index1::new(1, 0, Vec::new(), b"a".to_vec());
+        assert!(crate::dump_table(&index).is_err());
+        let index = Index2::new(1, 5, vec![0; 10], b"a".to_vec());
+        assert!(crate::dump_table(&index).is_err());
+        // a single offset for one object
+        let index = Index1::new(1, 1, vec![1], b"a".to_vec());
+        assert!(crate::dump_table(&index).is_err());
+        let index = Index1::new(1, 1, vec![1, 2], b"a".to_vec());
+        assert!(crate::dump_table(&index).is_ok());
+    }
+
+    #[test]
+    fn overlapping_offsets() {
+        let index = Index1::from_bytes(["one", "two", "three"]);
+        assert!(index.check_wellformed().is_ok());
+        assert!(Index1::default().check_wellformed().is_ok());
+        // the second object would start inside the first
+        let overlapping = Index1::new(3, 1, vec![1, 4, 2, 12], b"onetwothree".to_vec());
+        assert!(matches!(
+            overlapping.check_wellformed(),
+            Err(CffError::InvalidOffsetRange)
+        ));
+        // offsets are one-based
+        let index = Index1::new(1, 1, vec![0, 4], b"one".to_vec());
+        assert!(index.check_wellformed().is_err());
+        // the last object extends past the data
+        let index = Index2::new(1, 1, vec![1, 5], b"one".to_vec());
+        assert!(index.check_wellformed().is_err());
+        let index = Index1::new(1, 5, vec![0; 10], b"one".to_vec());
+        assert!(index.check_wellformed().is_err());
+    }
+
+    #[test]
+    fn round_trip() {
+        let index = Index2::from_bytes(["one", "two", "three"]);
+        let bytes = crate::dump_table(&index).unwrap();
+        // trailing data after the INDEX is not captured
+        let mut data = bytes.clone();
+        data.extend_from_slice(&[0xFF; 8]);
+        let read = Index2::read(FontData::new(&data)).unwrap();
+        assert_eq!(read, index);
+        assert_eq!(
+            read.iter().collect::<Vec<_>>(),
+            [b"one".as_slice(), b"two", b"three"]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "exceeds the INDEX count")]
+    fn push_to_full_index() {
+        let mut index = Index1::from_bytes(vec![b"x"; u16::MAX as usize]);
+        index.push(b"y");
+    }
+
+    #[test]
+    #[should_panic(expected = "doesn't fit in 1 bytes")]
+    fn write_offset_too_small() {
+        write_offset(0x100, 1, &mut Vec::new());
+    }
+
+    proptest! {
+        #[test]
+        fn write_offset_round_trips(off_size in 1..=4u8, value: u32, index in 0..4usize) {
+            // keep only the bytes that fit in the offset size
+            let value = value >> (8 * (4 - off_size as u32));
+            let mut offsets = Vec::new();
+            for _ in 0..index {
+                write_offset(1, off_size, &mut offsets);
+            }
+            write_offset(value, off_size, &mut offsets);
+            prop_assert_eq!(offsets.len(), (index + 1) * off_size as usize);
+            prop_assert_eq!(read_offset(&offsets, off_size, index), Some(value as usize));
+        }
+    }
+}
@@ -0,0 +1,519 @@
+//! Private and Font DICTs shared by the CFF and CFF2 tables.
+
+use super::{
+    dict::{self, encoded_offset, offset_operand, private_dict_range, DictKey, Entry, Operand},
+    split_off, write_to_vec, CffError, CffIndex,
+};
+use crate::FontWrite;
+use read_fonts::{FontData, FontRead, ReadError};
+
+/// A Private DICT and its local subroutines.
+///
+/// `T` is the INDEX type of the table containing the DICT.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PrivateDict<T> {
+    /// The DICT data.
+    pub dict: Vec<u8>,
+    /// The local subroutines, if any.
+    pub subrs: Option<T>,
+}
+
+/// A Font DICT in an FDArray.
+///
+/// `T` is the INDEX type of the table containing the DICT.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FontDict<T> {
+    /// The DICT data.
+    pub dict: Vec<u8>,
+    /// The Private DICT referenced by this Font DICT.
+    ///
+    /// An empty Private DICT is written if this is `None`.
+    pub private_dict: Option<PrivateDict<T>>,
+}
+
+/// A DICT value that may vary in a variable font.
+///
+/// In a CFF table, or for a value without a `blend` operator, `deltas` is
+/// empty.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BlendedValue {
+    /// The value at the default location.
+    pub default: f64,
+    /// The delta for each region of the variation data referenced by the
+    /// DICT's `vsindex`.
+    pub deltas: Vec<f64>,
+}
+
+/// The hinting values of a Private DICT.
+///
+/// Arrays that are delta encoded in the DICT (such as `BlueValues`) are
+/// stored here as absolute values. Entries that are not represented by a
+/// field are available in `raw_entries`.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PrivateDictData {
+    /// The index of the item variation data used by blended values.
+    pub vsindex: Option<i32>,
+    pub blue_values: Vec<BlendedValue>,
+    pub other_blues: Vec<BlendedValue>,
+    pub family_blues: Vec<BlendedValue>,
+    pub family_other_blues: Vec<BlendedValue>,
+    pub std_hw: Option<BlendedValue>,
+    pub std_vw: Option<BlendedValue>,
+    pub stem_snap_h: Vec<BlendedValue>,
+    pub stem_snap_v: Vec<BlendedValue>,
+    pub blue_scale: Option<BlendedValue>,
+    pub blue_shift: Option<BlendedValue>,
+    pub blue_fuzz: Option<BlendedValue>,
+    pub default_width_x: Option<BlendedValue>,
+    pub nominal_width_x: Option<BlendedValue>,
+    /// The remaining entries of the DICT, in their original order.
+    ///
+    /// The `Subrs` operator is omitted since the location of the local
+    /// subroutines is computed when the table is written.
+    pub raw_entries: Vec<Entry>,
+}
+
+impl<T> PrivateDict<T> {
+    /// Reads a Private DICT and its local subroutines from the given
+    /// `(size, offset)` range.
+    ///
+    /// Returns [`ReadError::MalformedData`] if the range or the local
+    /// subroutines lie outside of `data`.
+    pub(crate) fn read<'a>(
+        data: FontData<'a>,
+        (size, offset): (usize, usize),
+    ) -> Result<Self, ReadError>
+    where
+        T: FontRead<'a>,
+    {
+        let dict = offset
+            .checked_add(size)
+            .and_then(|end| data.slice(offset..end))
+            .ok_or(ReadError::MalformedData(
+                "Private DICT extends past the end of the table",
+            ))?
+            .as_bytes();
+        let entries =
+            dict::parse(dict).map_err(|_| ReadError::MalformedData("invalid Private DICT"))?;
+        // the subrs offset is relative to the start of the Private DICT
+        let subrs = offset_operand(&entries, DictKey::SubrsOffset)
+            .map(|subrs_offset| {
+                let subrs = offset
+                    .checked_add(subrs_offset)
+                    .filter(|start| *start < data.len())
+                    .and_then(|start| data.split_off(start))
+                    .ok_or(ReadError::MalformedData(
+                        "local subroutines are outside of the table",
+                    ))?;
+                T::read(subrs)
+            })
+            .transpose()?;
+        Ok(PrivateDict {
+            dict: dict.to_vec(),
+            subrs,
+        })
+    }
+
+    /// Compiles the DICT and local subroutines.
+    ///
+    /// Returns the data and the size of the DICT alone.
+    pub(crate) fn compile(&self) -> (Vec<u8>, usize)
+    where
+        T: FontWrite,
+    {
+        let Some(subrs) = &self.subrs else {
+            let dict = dict::rewrite(&self.dict, &[(DictKey::SubrsOffset, None)]);
+            let len = dict.len();
+            return (dict, len);
+        };
+        // the subroutines immediately follow the DICT
+        let placeholder = dict::rewrite(
+            &self.dict,
+            &[(DictKey::SubrsOffset, Some(encoded_offset(0)))],
+        );
+        let dict_len = placeholder.len();
+        let mut data = dict::rewrite(
+            &self.dict,
+            &[(DictKey::SubrsOffset, Some(encoded_offset(dict_len)))],
+        );
+        data.extend(write_to_vec(subrs));
+        (data, dict_len)
+    }
+
+    /// Returns the length of the data returned by
+    /// [`compile`](Self::compile), without encoding the subroutines.
+    pub(crate) fn compiled_len(&self) -> usize
+    where
+        T: CffIndex,
+    {
+        // the subrs offset is encoded with a fixed width, so its value
+        // doesn't affect the size of the DICT
+        let subrs_offset = self.subrs.as_ref().map(|_| encoded_offset(0));
+        dict::rewrite(&self.dict, &[(DictKey::SubrsOffset, subrs_offset)]).len()
+            + self.subrs.as_ref().map_or(0, CffIndex::byte_len)
+    }
+}
+
+impl<T> FontDict<T> {
+    /// Reads the Font DICTs of the FDArray at the given offset, along with
+    /// their Private DICTs.
+    pub(crate) fn read_fd_array<'a>(
+        data: FontData<'a>,
+        offset: usize,
+        is_cff2: bool,
+    ) -> Result<Vec<Self>, ReadError>
+    where
+        T: FontRead<'a>,
+    {
+        let fd_array = read_fonts::tables::postscript::Index::new(
+            split_off(data, offset)?.as_bytes(),
+            is_cff2,
+        )
+        .map_err(|_| ReadError::OutOfBounds)?;
+        (0..fd_array.count() as usize)
+            .map(|i| {
+                let font_dict = fd_array.get(i).map_err(|_| ReadError::OutOfBounds)?;
+                let entries = dict::parse(font_dict)
+                    .map_err(|_| ReadError::MalformedData("invalid Font DICT"))?;
+                let private_dict = private_dict_range(&entries)
+                    .map(|range| PrivateDict::read(data, range))
+                    .transpose()?;
+                Ok(FontDict {
+                    dict: font_dict.to_vec(),
+                    private_dict,
+                })
+            })
+            .collect()
+    }
+}
+
+impl BlendedValue {
+    /// Returns the sum of two values, treating missing deltas as zero.
+    fn add(&self, other: &BlendedValue) -> BlendedValue {
+        let len = self.deltas.len().max(other.deltas.len());
+        let delta = |value: &BlendedValue, i: usize| value.deltas.get(i).copied().unwrap_or(0.0);
+        BlendedValue {
+            default: self.default + other.default,
+            deltas: (0..len).map(|i| delta(self, i) + delta(other, i)).collect(),
+        }
+    }
+
+    /// Returns the value multiplied by `factor`, rounded to the nearest
+    /// integer.
+    fn scale(&self, factor: f64) -> BlendedValue {
+        BlendedValue {
+            default: (self.default * factor).round(),
+            deltas: self
+                .deltas
+                .iter()
+                .map(|delta| (delta * factor).round())
+                .collect(),
+        }
+    }
+
+    /// Returns the value at the location with the given region scalars.
+    fn instance(&self, scalars: &[f64]) -> BlendedValue {
+        let delta = self
+            .deltas
+            .iter()
+            .zip(scalars)
+            .map(|(delta, scalar)| delta * scalar)
+            .sum::<f64>();
+        (self.default + delta).into()
+    }
+
+    /// Returns the difference of two values, treating missing deltas as zero.
+    fn sub(&self, other: &BlendedValue) -> BlendedValue {
+        let negated = BlendedValue {
+            default: -other.default,
+            deltas: other.deltas.iter().map(|delta| -delta).collect(),
+        };
+        self.add(&negated)
+    }
+}
+
+impl From<f64> for BlendedValue {
+    fn from(default: f64) -> Self {
+        BlendedValue {
+            default,
+            deltas: Vec::new(),
+        }
+    }
+}
+
+impl PrivateDictData {
+    /// Parses the given Private DICT data.
+    ///
+    /// `region_counts` contains the number of regions for each item
+    /// variation data in the table's variation store, and is used to
+    /// evaluate the `blend` operator.
+    pub(crate) fn parse(data: &[u8], region_counts: &[usize]) -> Result<Self, CffError> {
+        let mut result = PrivateDictData::default();
+        let mut stack: Vec<BlendedValue> = Vec::new();
+        // the source entries contributing to the current stack, retained so
+        // that unknown entries can be preserved exactly
+        let mut pending: Vec<Entry> = Vec::new();
+        for entry in dict::parse(data)? {
+            stack.extend(entry.operands.iter().map(|op| op.to_f64().into()));
+            match entry.key {
+                DictKey::Blend => {
+                    let vsindex = result.vsindex.unwrap_or(0);
+                    let region_count = usize::try_from(vsindex)
+                        .ok()
+                        .and_then(|i| region_counts.get(i))
+                        .copied()
+                        .ok_or(CffError::InvalidDict)?;
+                    let count = stack
+                        .pop()
+                        .and_then(|n| usize::try_from(n.default as i64).ok())
+                        .ok_or(CffError::InvalidDict)?;
+                    let operand_count = count * (region_count + 1);
+                    let start = stack
+                        .len()
+                        .checked_sub(operand_count)
+                        .ok_or(CffError::InvalidDict)?;
+                    let operands = stack.split_off(start);
+                    let (defaults, deltas) = operands.split_at(count);
+                    stack.extend(defaults.iter().enumerate().map(|(i, value)| {
+                        BlendedValue {
+                            default: value.default,
+                            deltas: deltas[i * region_count..(i + 1) * region_count]
+                                .iter()
+                                .map(|delta| delta.default)
+                                .collect(),
+                        }
+                    }));
+                    pending.push(entry);
+                    continue;
+                }
+                DictKey::VariationStoreIndex => {
+                    result.vsindex = entry.operands.first().copied().and_then(Operand::to_i32);
+                }
+                DictKey::SubrsOffset => (),
+                key => {
+                    let values = std::mem::take(&mut stack);
+                    if let Some(field) = result.array_field(key) {
+                        *field = values
+                            .iter()
+                            .scan(BlendedValue::default(), |acc, value| {
+                                *acc = acc.add(value);
+                                Some(acc.clone())
+                            })
+                            .collect();
+                    } else if let Some(field) = result.value_field(key) {
+                        *field = values.into_iter().last();
+                    } else {
+                        result.raw_entries.append(&mut pending);
+                        result.raw_entries.push(entry);
+                    }
+                }
+            }
+            stack.clear();
+            pending.clear();
+        }
+        Ok(result)
+    }
+
+    /// Encodes the values as DICT data.
+    ///
+    /// The `Subrs` operator is not included.
+    pub(crate) fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        if let Some(vsindex) = self.vsindex {
+            dict::encode_int(vsindex, &mut out);
+            dict::encode_operator(DictKey::VariationStoreIndex, &mut out);
+        }
+        let mut data = self.clone();
+        for key in ARRAY_KEYS {
+            let values = data.array_field(key).unwrap();
+            if values.is_empty() {
+                continue;
+            }
+            let mut prev = BlendedValue::default();
+            let deltas = values
+                .iter()
+                .map(|value| {
+                    let delta = value.sub(&prev);
+                    prev = value.clone();
+                    delta
+                })
+                .collect::<Vec<_>>();
+            encode_blended(&deltas, &mut out);
+            dict::encode_operator(key, &mut out);
+        }
+        for key in VALUE_KEYS {
+            if let Some(value) = data.value_field(key).unwrap() {
+                encode_blended(std::slice::from_ref(value), &mut out);
+                dict::encode_operator(key, &mut out);
+            }
+        }
+        for entry in &self.raw_entries {
+            entry.encode(&mut out);
+        }
+        out
+    }
+
+    /// Scales the values measured in font units by `factor`, rounding to
+    /// the nearest integer.
+    ///
+    /// `BlueScale` is independent of the units per em and is unchanged.
+    pub(crate) fn scale(&mut self, factor: f64) {
+        for key in ARRAY_KEYS {
+            let values = self.array_field(key).unwrap();
+            *values = values.iter().map(|value| value.scale(factor)).collect();
+        }
+        for key in VALUE_KEYS
+            .into_iter()
+            .filter(|key| *key != DictKey::BlueScale)
+        {
+            if let Some(value) = self.value_field(key).unwrap() {
+                *value = value.scale(factor);
+            }
+        }
+    }
+
+    /// Resolves the blended values at a location in the design space.
+    ///
+    /// `scalars` contains the scalar of each region of each item variation
+    /// data at the location. The `vsindex` is removed.
+    pub(crate) fn instance(&mut self, scalars: &[Vec<f64>]) {
+        let vsindex = self.vsindex.take().unwrap_or(0);
+        let scalars = usize::try_from(vsindex)
+            .ok()
+            .and_then(|vsindex| scalars.get(vsindex))
+            .map_or(&[][..], Vec::as_slice);
+        for key in ARRAY_KEYS {
+            let values = self.array_field(key).unwrap();
+            *values = values.iter().map(|value| value.instance(scalars)).collect();
+        }
+        for key in VALUE_KEYS {
+            if let Some(value) = self.value_field(key).unwrap() {
+                *value = value.instance(scalars);
+            }
+        }
+    }
+
+    /// Removes the alignment zones and standard stem widths, which only
+    /// affect hinting.
+    pub(crate) fn clear_hints(&mut self) {
+        for key in ARRAY_KEYS {
+            self.array_field(key).unwrap().clear();
+        }
+        self.std_hw = None;
+        self.std_vw = None;
+    }
+
+    fn array_field(&mut self, key: DictKey) -> Option<&mut Vec<BlendedValue>> {
+        match key {
+            DictKey::BlueValues => Some(&mut self.blue_values),
+            DictKey::OtherBlues => Some(&mut self.other_blues),
+            DictKey::FamilyBlues => Some(&mut self.family_blues),
+            DictKey::FamilyOtherBlues => Some(&mut self.family_other_blues),
+            DictKey::StemSnapH => Some(&mut self.stem_snap_h),
+            DictKey::StemSnapV => Some(&mut self.stem_snap_v),
+            _ => None,
+        }
+    }
+
+    fn value_field(&mut self, key: DictKey) -> Option<&mut Option<BlendedValue>> {
+        match key {
+            DictKey::StdHw => Some(&mut self.std_hw),
+            DictKey::StdVw => Some(&mut self.std_vw),
+            DictKey::BlueScale => Some(&mut self.blue_scale),
+            DictKey::BlueShift => Some(&mut self.blue_shift),
+            DictKey::BlueFuzz => Some(&mut self.blue_fuzz),
+            DictKey::DefaultWidthX => Some(&mut self.default_width_x),
+            DictKey::NominalWidthX => Some(&mut self.nominal_width_x),
+            _ => None,
+        }
+    }
+}
+
+/// The operators with delta encoded array values, in the order they are
+/// written.
+const ARRAY_KEYS: [DictKey; 6] = [
+    DictKey::BlueValues,
+    DictKey::OtherBlues,
+    DictKey::FamilyBlues,
+    DictKey::FamilyOtherBlues,
+    DictKey::StemSnapH,
+    DictKey::StemSnapV,
+];
+
+/// The operators with a single value, in the order they are written.
+const VALUE_KEYS: [DictKey; 7] = [
+    DictKey::StdHw,
+    DictKey::StdVw,
+    DictKey::BlueScale,
+    DictKey::BlueShift,
+    DictKey::BlueFuzz,
+    DictKey::DefaultWidthX,
+    DictKey::NominalWidthX,
+];
+
+/// Encodes the operands for a list of values, using a `blend` operator if
+/// any of the values have deltas.
+fn encode_blended(values: &[BlendedValue], out: &mut Vec<u8>) {
+    let region_count = values.iter().map(|value| value.deltas.len()).max();
+    for value in values {
+        dict::encode_number(value.default, out);
+    }
+    let Some(region_count) = region_count.filter(|count| *count > 0) else {
+        return;
+    };
+    for value in values {
+        for i in 0..region_count {
+            dict::encode_number(value.deltas.get(i).copied().unwrap_or(0.0), out);
+        }
+    }
+    dict::encode_int(values.len() as i32, out);
+    dict::encode_operator(DictKey::Blend, out);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blended_round_trip() {
+        let mut data = Vec::new();
+        // BlueValues: -10 0 500 510, with deltas for two regions
+        for value in [-10, 10, 490, 10, 1, 2, 0, 0, 5, 6, 0, 0, 4] {
+            dict::encode_int(value, &mut data);
+        }
+        dict::encode_operator(DictKey::Blend, &mut data);
+        dict::encode_operator(DictKey::BlueValues, &mut data);
+        dict::encode_real(0.039625, &mut data);
+        dict::encode_operator(DictKey::BlueScale, &mut data);
+        dict::encode_int(1, &mut data);
+        dict::encode_operator(DictKey::LanguageGroup, &mut data);
+
+        let private = PrivateDictData::parse(&data, &[2]).unwrap();
+        let values = private
+            .blue_values
+            .iter()
+            .map(|value| (value.default, value.deltas.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            values,
+            [
+                (-10.0, vec![1.0, 2.0]),
+                (0.0, vec![1.0, 2.0]),
+                (490.0, vec![6.0, 8.0]),
+                (500.0, vec![6.0, 8.0]),
+            ]
+        );
+        assert_eq!(private.blue_scale, Some(0.039625.into()));
+        assert_eq!(private.raw_entries.len(), 1);
+        assert_eq!(private.raw_entries[0].key, DictKey::LanguageGroup);
+
+        let encoded = private.encode();
+        assert_eq!(PrivateDictData::parse(&encoded, &[2]).unwrap(), private);
+        // blend requires variation data
+        assert!(PrivateDictData::parse(&data, &[]).is_err());
+    }
+}
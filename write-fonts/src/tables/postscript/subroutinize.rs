@@ -0,0 +1,277 @@
+//! Extraction of repeated charstring sequences into subroutines.
+
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, HashSet},
+};
+
+use super::charstring::{self, Operand, Operator, Token};
+
+/// The maximum number of operators in a subroutine.
+const MAX_SUBR_OPS: usize = 16;
+
+/// The estimated size of a subroutine call: the subroutine number and the
+/// `callgsubr` operator.
+const CALL_SIZE: usize = 3;
+
+/// The maximum number of subroutines that are created.
+const MAX_SUBRS: usize = u16::MAX as usize;
+
+/// An element of a charstring being subroutinized.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Symbol {
+    /// An operator and its operands, which may be moved into a subroutine.
+    Op(u32),
+    /// An operator and its operands that must remain in the charstring.
+    Fixed(u32),
+    /// A call to the subroutine with the given creation order.
+    Call(u32),
+}
+
+/// Extracts repeated sequences of path operators from the given charstrings
+/// into global subroutines.
+///
+/// The charstrings must not call any subroutines. Each operator is kept
+/// together with its operands, and only path construction operators are
+/// moved, so that hints, the stem count and `endchar` are unaffected by the
+/// calls. Sequences are chosen greedily by their estimated saving in bytes.
+///
+/// The candidate sequences are counted once, and each extraction only
+/// recounts the charstrings that contain the extracted sequence, rather than
+/// every charstring, so the time taken grows with the number of occurrences
+/// of the extracted sequences and not with the number of subroutines times
+/// the size of the font.
+///
+/// Returns the global subroutines and the rewritten charstrings.
+pub(crate) fn subroutinize(charstrings: &[Vec<Token>]) -> (Vec<Vec<u8>>, Vec<Vec<u8>>) {
+    let mut units: Vec<Vec<u8>> = Vec::new();
+    let mut unit_ids: HashMap<Vec<u8>, u32> = HashMap::new();
+    let mut sequences = charstrings
+        .iter()
+        .map(|tokens| {
+            let mut symbols = Vec::new();
+            let mut start = 0;
+            for (i, token) in tokens.iter().enumerate() {
+                let is_last = i + 1 == tokens.len();
+                if !matches!(token, Token::Operand(_)) || is_last {
+                    let unit = &tokens[start..=i];
+                    start = i + 1;
+                    let bytes = charstring::encode(unit);
+                    let movable = matches!(token, Token::Operator(op) if is_path_operator(*op));
+                    symbols.push(if movable {
+                        let id = *unit_ids.entry(bytes.clone()).or_insert_with(|| {
+                            units.push(bytes);
+                            units.len() as u32 - 1
+                        });
+                        Symbol::Op(id)
+                    } else {
+                        units.push(bytes);
+                        Symbol::Fixed(units.len() as u32 - 1)
+                    });
+                }
+            }
+            symbols
+        })
+        .collect::<Vec<_>>();
+
+    // the number of non-overlapping occurrences of each sequence of
+    // operators, which is updated for the charstrings changed by each
+    // extraction rather than recounted, and the charstrings containing
+    // each operator
+    let mut counts: HashMap<Vec<u32>, usize> = HashMap::new();
+    let mut containing: HashMap<u32, Vec<usize>> = HashMap::new();
+    for (seq_ix, sequence) in sequences.iter().enumerate() {
+        for (window, count) in window_counts(sequence) {
+            *counts.entry(window).or_default() += count;
+        }
+        for symbol in sequence {
+            if let Symbol::Op(id) = symbol {
+                let seqs = containing.entry(*id).or_default();
+                if seqs.last() != Some(&seq_ix) {
+                    seqs.push(seq_ix);
+                }
+            }
+        }
+    }
+    // candidates by their saving; an entry is stale once the count of its
+    // operators changes, and is then skipped
+    let mut candidates = counts
+        .iter()
+        .filter_map(|(ops, count)| Some((saving(ops, *count, &units)?, Reverse(ops.clone()))))
+        .collect::<BinaryHeap<_>>();
+
+    // the operators of each subroutine and the number of calls to it
+    let mut subrs: Vec<(Vec<u32>, usize)> = Vec::new();
+    while subrs.len() < MAX_SUBRS {
+        let Some((best_saving, Reverse(ops))) = candidates.pop() else {
+            break;
+        };
+        let count = counts.get(&ops).copied().unwrap_or_default();
+        if saving(&ops, count, &units) != Some(best_saving) {
+            continue;
+        }
+        let subr = Symbol::Call(subrs.len() as u32);
+        let symbols = ops.iter().copied().map(Symbol::Op).collect::<Vec<_>>();
+        // only charstrings containing the least common operator can contain
+        // the sequence
+        let rarest = ops
+            .iter()
+            .min_by_key(|op| containing.get(*op).map_or(0, Vec::len))
+            .and_then(|op| containing.get(op))
+            .cloned()
+            .unwrap_or_default();
+        let mut changed = HashSet::new();
+        let mut calls = 0;
+        for seq_ix in rarest {
+            let sequence = &mut sequences[seq_ix];
+            if !sequence
+                .windows(symbols.len())
+                .any(|window| window == symbols)
+            {
+                continue;
+            }
+            for (window, count) in window_counts(sequence) {
+                if let Some(total) = counts.get_mut(&window) {
+                    *total -= count;
+                }
+                changed.insert(window);
+            }
+            let mut i = 0;
+            while i + symbols.len() <= sequence.len() {
+                if sequence[i..i + symbols.len()] == symbols[..] {
+                    sequence.splice(i..i + symbols.len(), [subr]);
+                    calls += 1;
+                }
+                i += 1;
+            }
+            for (window, count) in window_counts(sequence) {
+                *counts.entry(window.clone()).or_default() += count;
+                changed.insert(window);
+            }
+        }
+        for window in changed {
+            let count = counts.get(&window).copied().unwrap_or_default();
+            match saving(&window, count, &units) {
+                Some(saving) => candidates.push((saving, Reverse(window))),
+                None if count == 0 => {
+                    counts.remove(&window);
+                }
+                None => (),
+            }
+        }
+        subrs.push((ops, calls));
+    }
+
+    // the most frequently called subroutines get the smallest numbers
+    let mut order = (0..subrs.len()).collect::<Vec<_>>();
+    order.sort_by_key(|ix| std::cmp::Reverse(subrs[*ix].1));
+    let mut numbers = vec![0; subrs.len()];
+    for (number, ix) in order.iter().enumerate() {
+        numbers[*ix] = number as i32;
+    }
+    let bias = charstring::subr_bias(subrs.len());
+    let global_subrs = order
+        .iter()
+        .map(|ix| {
+            let mut data = Vec::new();
+            for op in &subrs[*ix].0 {
+                data.extend_from_slice(&units[*op as usize]);
+            }
+            charstring::encode_operator(Operator::Return, &mut data);
+            data
+        })
+        .collect();
+    let charstrings = sequences
+        .iter()
+        .map(|sequence| {
+            let mut data = Vec::new();
+            for symbol in sequence {
+                match symbol {
+                    Symbol::Op(id) | Symbol::Fixed(id) => {
+                        data.extend_from_slice(&units[*id as usize])
+                    }
+                    Symbol::Call(subr) => {
+                        let number = numbers[*subr as usize] - bias;
+                        charstring::encode_operand(Operand::Integer(number), &mut data);
+                        charstring::encode_operator(Operator::CallGsubr, &mut data);
+                    }
+                }
+            }
+            data
+        })
+        .collect();
+    (global_subrs, charstrings)
+}
+
+/// Returns the number of non-overlapping occurrences, counted from the start
+/// of the charstring, of each sequence of at most [`MAX_SUBR_OPS`] operators
+/// that can be moved into a subroutine.
+fn window_counts(sequence: &[Symbol]) -> HashMap<Vec<u32>, usize> {
+    // the number of occurrences and the end of the last one
+    let mut counts: HashMap<&[Symbol], (usize, usize)> = HashMap::new();
+    for start in 0..sequence.len() {
+        for len in 1..=MAX_SUBR_OPS.min(sequence.len() - start) {
+            let window = &sequence[start..start + len];
+            if !matches!(window[len - 1], Symbol::Op(_)) {
+                break;
+            }
+            let entry = counts.entry(window).or_insert((0, 0));
+            if entry.1 <= start {
+                *entry = (entry.0 + 1, start + len);
+            }
+        }
+    }
+    counts
+        .into_iter()
+        .map(|(window, (count, _))| {
+            let ops = window
+                .iter()
+                .filter_map(|symbol| match symbol {
+                    Symbol::Op(id) => Some(*id),
+                    _ => None,
+                })
+                .collect();
+            (ops, count)
+        })
+        .collect()
+}
+
+/// Returns the estimated saving in bytes of extracting a sequence of
+/// operators with the given number of occurrences, if it saves space.
+fn saving(ops: &[u32], count: usize, units: &[Vec<u8>]) -> Option<usize> {
+    let size = ops
+        .iter()
+        .map(|op| units[*op as usize].len())
+        .sum::<usize>();
+    // each call replaces the sequence, and the subroutine adds the
+    // sequence, a return and an offset
+    let saved = count * size;
+    let cost = count * CALL_SIZE + size + 1 + 2;
+    (saved > cost).then(|| saved - cost)
+}
+
+/// Returns `true` if the operator only constructs the path, and can be
+/// moved into a subroutine without affecting the width or hints.
+fn is_path_operator(operator: Operator) -> bool {
+    use Operator::*;
+    matches!(
+        operator,
+        RMoveTo
+            | HMoveTo
+            | VMoveTo
+            | RLineTo
+            | HLineTo
+            | VLineTo
+            | RrCurveTo
+            | RCurveLine
+            | RLineCurve
+            | VvCurveTo
+            | HhCurveTo
+            | VhCurveTo
+            | HvCurveTo
+            | HFlex
+            | Flex
+            | HFlex1
+            | Flex1
+    )
+}